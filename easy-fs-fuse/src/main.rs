@@ -1,5 +1,5 @@
 use clap::{App, Arg};
-use easy_fs::{BlockDevice, EasyFileSystem};
+use easy_fs::{BlockDevice, BlockError, EasyFileSystem};
 use std::fs::{read_dir, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::Arc;
@@ -10,18 +10,23 @@ const BLOCK_SZ: usize = 512;
 struct BlockFile(Mutex<File>);
 
 impl BlockDevice for BlockFile {
-    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
         let mut file = self.0.lock().unwrap();
         file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
-            .expect("Error when seeking!");
-        assert_eq!(file.read(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+            .and_then(|_| file.read_exact(buf))
+            .map_err(|_| BlockError::ReadFailed(block_id))
     }
 
-    fn write_block(&self, block_id: usize, buf: &[u8]) {
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
         let mut file = self.0.lock().unwrap();
         file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
-            .expect("Error when seeking!");
-        assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+            .and_then(|_| file.write_all(buf))
+            .map_err(|_| BlockError::WriteFailed(block_id))
+    }
+
+    fn num_blocks(&self) -> usize {
+        let file = self.0.lock().unwrap();
+        file.metadata().expect("Error when reading metadata!").len() as usize / BLOCK_SZ
     }
 }
 
@@ -1,5 +1,7 @@
 use super::{get_block_cache, BlockDevice, BLOCK_SZ};
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 /// A bitmap block
 /// 磁盘数据结构，它将位图区域中的一个磁盘块解释为长度为 64 的一个 u64 数组
 /// 每个 u64 打包了一组 64 bits，于是整个数组包含64 X 64 = 4096bits且可以以组为单位进行操作。 
@@ -12,6 +14,15 @@ pub struct Bitmap {
     start_block_id: usize,
     /// 区域内的块数
     blocks: usize,
+    /// 已知全部分配出去的块，分配时跳过它们而不必锁住其块缓存
+    full: Vec<bool>,
+}
+
+/// A copy of the bitmap blocks of a filesystem taken by
+/// [`EasyFileSystem::snapshot_bitmaps`](crate::EasyFileSystem::snapshot_bitmaps)
+pub struct BitmapSnapshot {
+    pub(crate) inode: Vec<BitmapBlock>,
+    pub(crate) data: Vec<BitmapBlock>,
 }
 
 /// Decompose bits into (block_pos, bits64_pos, inner_pos)
@@ -28,36 +39,40 @@ impl Bitmap {
         Self {
             start_block_id,
             blocks,
+            full: vec![false; blocks],
         }
     }
     
     /// Allocate a new block from a block device
-    pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
+    pub fn alloc(&mut self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
         // 遍历所有块，找到第一个未被使用的块
         // 找到一个尚未被全部分配出去的组，最后在里面分配一个bit
         // 它将会返回分配的bit所在的位置，等同于索引节点/数据块的编号
         // 如果所有bit均已经被分配出去了，则返回 None
         for block_id in 0..self.blocks {
+            if self.full[block_id] {
+                continue;
+            }
             // 对当前遍历到的块，调用 get_block_cache 函数获取其对应的块缓存
-            let pos = get_block_cache(
+            let (pos, full) = get_block_cache(
                 block_id + self.start_block_id as usize,  //使用 block_id + self.start_block_id 来计算实际的块编号
                 Arc::clone(block_device),
             )
             .lock()
             .modify(0, |bitmap_block: &mut BitmapBlock| {
-                if let Some((bits64_pos, inner_pos)) = bitmap_block
+                let pos = bitmap_block
                     .iter()
                     .enumerate()
                     .find(|(_, bits64)| **bits64 != u64::MAX)
-                    .map(|(bits64_pos, bits64)| (bits64_pos, bits64.trailing_ones() as usize))
-                {
+                    .map(|(bits64_pos, bits64)| (bits64_pos, bits64.trailing_ones() as usize));
+                let pos = pos.map(|(bits64_pos, inner_pos)| {
                     // modify cache
                     bitmap_block[bits64_pos] |= 1u64 << inner_pos;
-                    Some(block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos as usize)
-                } else {
-                    None
-                }
+                    block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos as usize
+                });
+                (pos, bitmap_block.iter().all(|bits64| *bits64 == u64::MAX))
             });
+            self.full[block_id] = full;
             if pos.is_some() {
                 return pos;
             }
@@ -65,9 +80,51 @@ impl Bitmap {
         None
     }
     
+    /// Allocate a new block like [`Bitmap::alloc`], preferring bits close
+    /// after `goal` in the same bitmap block so that blocks allocated one
+    /// after another stay contiguous: `goal` itself if free, else the start
+    /// of a fully free group of 64 bits after it, else any free bit after
+    /// it. Fall back to any free bit when that region is full
+    pub fn alloc_near(&mut self, block_device: &Arc<dyn BlockDevice>, goal: usize) -> Option<usize> {
+        let (block_pos, goal_bits64, goal_inner) = decomposition(goal);
+        if block_pos < self.blocks && !self.full[block_pos] {
+            let (pos, full) = get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+                .lock()
+                .modify(0, |bitmap_block: &mut BitmapBlock| {
+                    // 第一组中 goal 之前的位视为已分配，只向后查找
+                    let groups = (goal_bits64..64).map(|bits64_pos| {
+                        let skipped = if bits64_pos == goal_bits64 { (1u64 << goal_inner) - 1 } else { 0 };
+                        (bits64_pos, bitmap_block[bits64_pos] | skipped)
+                    });
+                    let pos = if bitmap_block[goal_bits64] & (1u64 << goal_inner) == 0 {
+                        Some((goal_bits64, goal_inner))
+                    } else {
+                        // 紧接的块已被占用时，跳到一段完全空闲的区域继续连续分配，
+                        // 而不是填进零散的空洞
+                        groups
+                            .clone()
+                            .find(|(_, bits64)| *bits64 == 0)
+                            .or_else(|| groups.clone().find(|(_, bits64)| *bits64 != u64::MAX))
+                            .map(|(bits64_pos, bits64)| (bits64_pos, bits64.trailing_ones() as usize))
+                    };
+                    let pos = pos.map(|(bits64_pos, inner_pos)| {
+                        bitmap_block[bits64_pos] |= 1u64 << inner_pos;
+                        block_pos * BLOCK_BITS + bits64_pos * 64 + inner_pos
+                    });
+                    (pos, bitmap_block.iter().all(|bits64| *bits64 == u64::MAX))
+                });
+            self.full[block_pos] = full;
+            if pos.is_some() {
+                return pos;
+            }
+        }
+        self.alloc(block_device)
+    }
+
     /// Deallocate a block
-    pub fn dealloc(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
+    pub fn dealloc(&mut self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
         let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        self.full[block_pos] = false;
         get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
             .lock()
             .modify(0, |bitmap_block: &mut BitmapBlock| {
@@ -75,6 +132,41 @@ impl Bitmap {
                 bitmap_block[bits64_pos] -= 1u64 << inner_pos;
             });
     }
+    /// Number of bits currently allocated
+    pub fn allocated(&self, block_device: &Arc<dyn BlockDevice>) -> usize {
+        (0..self.blocks)
+            .map(|block_id| {
+                get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |bitmap_block: &BitmapBlock| {
+                        bitmap_block.iter().map(|bits64| bits64.count_ones() as usize).sum::<usize>()
+                    })
+            })
+            .sum()
+    }
+    /// Copy out every block of the bitmap
+    pub fn snapshot(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<BitmapBlock> {
+        (0..self.blocks)
+            .map(|block_id| {
+                get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |bitmap_block: &BitmapBlock| *bitmap_block)
+            })
+            .collect()
+    }
+    /// Write back the blocks taken by [`Bitmap::snapshot`]
+    pub fn restore(&mut self, block_device: &Arc<dyn BlockDevice>, blocks: &[BitmapBlock]) {
+        assert_eq!(blocks.len(), self.blocks);
+        for (block_id, saved) in blocks.iter().enumerate() {
+            self.full[block_id] = saved.iter().all(|bits64| *bits64 == u64::MAX);
+            let cache = get_block_cache(block_id + self.start_block_id, Arc::clone(block_device));
+            let mut cache = cache.lock();
+            // 内容相同时不写，避免把未改动的块标脏
+            if cache.read(0, |bitmap_block: &BitmapBlock| bitmap_block != saved) {
+                cache.modify(0, |bitmap_block: &mut BitmapBlock| *bitmap_block = *saved);
+            }
+        }
+    }
     /// Get the max number of allocatable blocks
     pub fn maximum(&self) -> usize {
         self.blocks * BLOCK_BITS
@@ -1,6 +1,8 @@
-use super::{BlockDevice, BLOCK_SZ};
-use alloc::collections::VecDeque;
+use super::{BlockDevice, BlockError, BLOCK_SZ};
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::*;
 use spin::Mutex;
 /// Cached block inside memory
@@ -22,16 +24,20 @@ pub struct BlockCache {
 impl BlockCache {
     /// Load a new BlockCache from disk.
     /// 创建 BlockCache 时，将一个块从磁盘读到缓冲区 cache
-    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Result<Self, BlockError> {
+        // 越过设备末尾的块既不能读也不能写
+        if block_id >= block_device.num_blocks() {
+            return Err(BlockError::OutOfRange(block_id));
+        }
         let mut cache = [0u8; BLOCK_SZ];
         // 从磁盘读取编号为 block_id 的块到缓冲区中
-        block_device.read_block(block_id, &mut cache);
-        Self {
+        block_device.try_read_block(block_id, &mut cache)?;
+        Ok(Self {
             cache,
             block_id,
             block_device,
             modified: false,
-        }
+        })
         // 一旦磁盘块已经存在于内存缓存中，CPU 就可以直接访问磁盘块数据
     }
     /// Get the address of an offset inside the cached block data
@@ -79,34 +85,89 @@ impl BlockCache {
     }
 
     /// 如果自身确实被修改过的话才会将缓冲区的内容写回磁盘
-    pub fn sync(&mut self) {
+    /// 写回失败时仍保持脏标记，以便之后重试
+    pub fn sync(&mut self) -> Result<(), BlockError> {
         if self.modified {
+            self.block_device.try_write_block(self.block_id, &self.cache)?;
             self.modified = false;
-            self.block_device.write_block(self.block_id, &self.cache);
         }
+        Ok(())
     }
 }
 
 impl Drop for BlockCache {
     fn drop(&mut self) {
-        self.sync()
+        // 无法向上传递错误，宁可 panic 也不悄悄丢失数据
+        if let Err(err) = self.sync() {
+            panic!("{} when dropping BlockCache", err);
+        }
     }
 }
-/// Use a block cache of 16 blocks
+/// Use a block cache of 16 blocks, exceeded only while every cached block
+/// is in use or pinned
 /// 为了避免在块缓存上浪费过多内存，我们希望内存中同时只能驻留有限个磁盘块的缓冲区
 const BLOCK_CACHE_SIZE: usize = 16;
+/// Max number of blocks one readahead loads, half of the cache so that the
+/// index blocks needed by the read that follows do not evict them
+pub(crate) const READAHEAD_MAX_BLOCKS: usize = BLOCK_CACHE_SIZE / 2;
 
 /// Block cache manager
 pub struct BlockCacheManager {
-    /// 块编号和块缓存的二元组队列
-    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    /// 设备标识、块编号、块缓存和最近一次访问时间的队列，挂载多个文件系统时不同设备的同号块互不混淆
+    queue: VecDeque<(usize, usize, Arc<Mutex<BlockCache>>, usize)>,
+    /// 被固定的 (设备标识, 块编号) 及其被固定的次数，无论引用计数如何都不会被替换出去
+    pinned: BTreeMap<(usize, usize), usize>,
 }
 
 impl BlockCacheManager {
     pub fn new() -> Self {
         Self {
             queue: VecDeque::new(),
+            pinned: BTreeMap::new(),
+        }
+    }
+
+    /// Keep block `block_id` of `block_device` resident once loaded: it is
+    /// never chosen as the victim of a replacement until unpinned.
+    /// Pinned blocks still take slots of the cache, so pin only a few
+    pub fn pin(&mut self, block_id: usize, block_device: &Arc<dyn BlockDevice>) {
+        *self.pinned.entry((device_id(block_device), block_id)).or_insert(0) += 1;
+    }
+
+    /// Undo one `pin` of block `block_id` of `block_device`, it can be
+    /// replaced again once every pin is undone
+    pub fn unpin(&mut self, block_id: usize, block_device: &Arc<dyn BlockDevice>) {
+        let key = (device_id(block_device), block_id);
+        if let Some(count) = self.pinned.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                self.pinned.remove(&key);
+            }
+        }
+    }
+
+    /// Drop block `block_id` of `block_device` from the cache, writing it
+    /// back first if dirty. A block still in use or pinned stays cached.
+    /// Return whether the block is no longer cached
+    pub fn invalidate(
+        &mut self,
+        block_id: usize,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Result<bool, BlockError> {
+        let device_id = device_id(block_device);
+        let Some(idx) = self
+            .queue
+            .iter()
+            .position(|pair| pair.0 == device_id && pair.1 == block_id)
+        else {
+            return Ok(true);
+        };
+        if Arc::strong_count(&self.queue[idx].2) != 1 || self.pinned.contains_key(&(device_id, block_id)) {
+            return Ok(false);
         }
+        self.queue[idx].2.lock().sync()?;
+        self.queue.remove(idx);
+        Ok(true)
     }
 
     /// 从块缓存管理器中获取一个编号为 block_id 的块的块缓存
@@ -116,61 +177,193 @@ impl BlockCacheManager {
         &mut self,
         block_id: usize,
         block_device: Arc<dyn BlockDevice>,
-    ) -> Arc<Mutex<BlockCache>> {
-        // 整个队列试图找到一个编号相同的块缓存
-        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == block_id) {
+    ) -> Result<Arc<Mutex<BlockCache>>, BlockError> {
+        let device_id = device_id(&block_device);
+        let now = CACHE_CLOCK.load(Ordering::Relaxed);
+        // 整个队列试图找到一个设备与编号都相同的块缓存
+        if let Some(pair) = self
+            .queue
+            .iter_mut()
+            .find(|pair| pair.0 == device_id && pair.1 == block_id)
+        {
             // hit
-            Arc::clone(&pair.1)
+            pair.3 = now;
+            Ok(Arc::clone(&pair.2))
         } else {
             // substitute
-            // 达到了上限，需要替换
-            if self.queue.len() == BLOCK_CACHE_SIZE {
+            // 达到了上限，需要替换；之前临时超出容量时一并收缩回来
+            while self.queue.len() >= BLOCK_CACHE_SIZE {
                 // from front to tail
-                if let Some((idx, _)) = self
-                    .queue
-                    .iter()
-                    .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)   //该元素的引用计数为 1
-                {
-                    self.queue.drain(idx..=idx);
-                } else {
-                    panic!("Run out of BlockCache!");
-                }
+                let Some(idx) = self.queue.iter().position(|pair| {
+                    //该元素的引用计数为 1，且没有被固定
+                    Arc::strong_count(&pair.2) == 1 && !self.pinned.contains_key(&(pair.0, pair.1))
+                }) else {
+                    // 所有块都在使用或被固定，暂时超出容量而不是 panic
+                    log::warn!(
+                        "block cache over capacity: {} blocks in use or pinned",
+                        self.queue.len()
+                    );
+                    break;
+                };
+                // 先写回被替换的块，失败时保留在队列中
+                self.queue[idx].2.lock().sync()?;
+                self.queue.remove(idx);
             }
             // load block into mem and push back
             // 创建一个新的块缓存
             let block_cache = Arc::new(Mutex::new(BlockCache::new(
                 block_id,
                 Arc::clone(&block_device),
-            )));
+            )?));
             // 将新的块缓存加入到队列尾部
-            self.queue.push_back((block_id, Arc::clone(&block_cache)));
-            block_cache
+            self.queue
+                .push_back((device_id, block_id, Arc::clone(&block_cache), now));
+            Ok(block_cache)
         }
     }
+
+    /// Drop the blocks last accessed `ttl` or longer before `now`, writing
+    /// dirty ones back first. Blocks in use or pinned stay, and so does a
+    /// dirty block failing to write back. Return the number of blocks dropped
+    pub fn sweep(&mut self, now: usize, ttl: usize) -> usize {
+        let before = self.queue.len();
+        let pinned = &self.pinned;
+        self.queue.retain(|(device_id, block_id, cache, last_access)| {
+            if now.saturating_sub(*last_access) < ttl
+                || Arc::strong_count(cache) != 1
+                || pinned.contains_key(&(*device_id, *block_id))
+            {
+                return true;
+            }
+            cache.lock().sync().is_err()
+        });
+        before - self.queue.len()
+    }
 }
 
+/// Identify a block device by the address of the object behind it
+pub(crate) fn device_id(block_device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(block_device) as *const u8 as usize
+}
+
+/// Time of the latest sweep, stamped on blocks as their last access.
+/// The unit is whatever the caller of [`sweep_block_cache`] uses
+static CACHE_CLOCK: AtomicUsize = AtomicUsize::new(0);
+
 lazy_static! {
     /// The global block cache manager
     pub static ref BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> =
         Mutex::new(BlockCacheManager::new());
 }
 
+/// Get the block cache corresponding to the given block id and block device,
+/// reporting device failure when the block has to be loaded or another one evicted
+pub fn try_get_block_cache(
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+) -> Result<Arc<Mutex<BlockCache>>, BlockError> {
+    BLOCK_CACHE_MANAGER
+        .lock()
+        .get_block_cache(block_id, block_device)
+}
+
 /// Get the block cache corresponding to the given block id and block device
-/// 请求块缓存
+/// 请求块缓存，设备出错时 panic
 pub fn get_block_cache(
     block_id: usize,
     block_device: Arc<dyn BlockDevice>,
 ) -> Arc<Mutex<BlockCache>> {
+    match try_get_block_cache(block_id, block_device) {
+        Ok(block_cache) => block_cache,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+/// Keep block `block_id` of `block_device` resident in the block cache,
+/// e.g. the block holding the root inode
+pub fn pin_block(block_id: usize, block_device: &Arc<dyn BlockDevice>) {
+    BLOCK_CACHE_MANAGER.lock().pin(block_id, block_device);
+}
+
+/// Let block `block_id` of `block_device` be evicted again
+pub fn unpin_block(block_id: usize, block_device: &Arc<dyn BlockDevice>) {
+    BLOCK_CACHE_MANAGER.lock().unpin(block_id, block_device);
+}
+
+/// Drop block `block_id` of `block_device` from the block cache unless it is
+/// in use or pinned, writing it back first if dirty
+pub fn invalidate_block(block_id: usize, block_device: &Arc<dyn BlockDevice>) -> Result<bool, BlockError> {
+    BLOCK_CACHE_MANAGER.lock().invalidate(block_id, block_device)
+}
+
+/// Whether block `block_id` of `block_device` is in the block cache
+pub fn block_cached(block_id: usize, block_device: &Arc<dyn BlockDevice>) -> bool {
+    let device_id = device_id(block_device);
     BLOCK_CACHE_MANAGER
         .lock()
-        .get_block_cache(block_id, block_device)
+        .queue
+        .iter()
+        .any(|pair| pair.0 == device_id && pair.1 == block_id)
+}
+
+/// Hint the device to start reading `block_id` unless it is already cached
+pub fn prefetch_block(block_id: usize, block_device: &Arc<dyn BlockDevice>) {
+    if !block_cached(block_id, block_device) {
+        block_device.prefetch(block_id);
+    }
+}
+
+/// Advance the clock of the block cache to `now` and drop the blocks not
+/// accessed for `ttl` or longer, see [`BlockCacheManager::sweep`]. Meant to
+/// be called periodically, e.g. from the timer, with a monotonic `now`
+pub fn sweep_block_cache(now: usize, ttl: usize) -> usize {
+    CACHE_CLOCK.store(now, Ordering::Relaxed);
+    BLOCK_CACHE_MANAGER.lock().sweep(now, ttl)
+}
+
+/// Like [`sweep_block_cache`] but never waits for the block cache: if the
+/// manager is locked, e.g. by the code an interrupt handler interrupted,
+/// only the clock advances and None is returned. Safe to call from the
+/// timer interrupt, where waiting for the lock would deadlock
+pub fn try_sweep_block_cache(now: usize, ttl: usize) -> Option<usize> {
+    CACHE_CLOCK.store(now, Ordering::Relaxed);
+    // 被打断的代码持有锁时跳过本次清理，留给下一次时钟中断
+    Some(BLOCK_CACHE_MANAGER.try_lock()?.sweep(now, ttl))
+}
+
+/// Sync all block cache to block device, stopping at the first failure
+pub fn try_block_cache_sync_all() -> Result<(), BlockError> {
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    for (_, _, cache, _) in manager.queue.iter() {
+        cache.lock().sync()?;
+    }
+    Ok(())
+}
+
+/// Sync all block cache to block device in ascending `rank(block_id)`, blocks
+/// of equal rank keep the queue order. The caller imposes write ordering with
+/// it, e.g. inode blocks before the directory blocks referring to them
+pub fn try_block_cache_sync_ordered(rank: impl Fn(usize) -> usize) -> Result<(), BlockError> {
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    let mut caches: Vec<&(usize, usize, Arc<Mutex<BlockCache>>, usize)> =
+        manager.queue.iter().collect();
+    caches.sort_by_key(|(_, block_id, _, _)| rank(*block_id));
+    for (_, _, cache, _) in caches {
+        cache.lock().sync()?;
+    }
+    Ok(())
+}
+
+/// Sync all block cache to block device in ascending `rank(block_id)`
+pub fn block_cache_sync_ordered(rank: impl Fn(usize) -> usize) {
+    if let Err(err) = try_block_cache_sync_ordered(rank) {
+        panic!("{}", err);
+    }
 }
 
 /// Sync all block cache to block device
 pub fn block_cache_sync_all() {
-    let manager = BLOCK_CACHE_MANAGER.lock();
-    for (_, cache) in manager.queue.iter() {
-        cache.lock().sync();
+    if let Err(err) = try_block_cache_sync_all() {
+        panic!("{}", err);
     }
 }
@@ -1,11 +1,57 @@
 use core::any::Any;
+use core::fmt::{self, Display, Formatter};
+
+/// Error reported by a block device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    /// The device failed to read the block with the given id
+    ReadFailed(usize),
+    /// The device failed to write the block with the given id
+    WriteFailed(usize),
+    /// The block id is beyond the end of the device
+    OutOfRange(usize),
+}
+
+impl Display for BlockError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::ReadFailed(block_id) => write!(f, "failed to read block {}", block_id),
+            BlockError::WriteFailed(block_id) => write!(f, "failed to write block {}", block_id),
+            BlockError::OutOfRange(block_id) => write!(f, "block {} is out of range", block_id),
+        }
+    }
+}
+
 /// Trait for block devices
 /// which reads and writes data in the unit of blocks
+///
+/// A device implements the fallible methods, the infallible ones panic on
+/// device failure.
 pub trait BlockDevice: Send + Sync + Any {
     ///Read data form block to buffer
     /// 将编号为 block_id 的块从磁盘读入内存中的缓冲区 buf
-    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.try_read_block(block_id, buf).unwrap()
+    }
     ///Write data from buffer to block
     /// 将内存中的缓冲区 buf 中的数据写入磁盘编号为 block_id 的块
-    fn write_block(&self, block_id: usize, buf: &[u8]);
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.try_write_block(block_id, buf).unwrap()
+    }
+    /// Read data form block to buffer, reporting device failure
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError>;
+    /// Write data from buffer to block, reporting device failure
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError>;
+    /// Total number of blocks of the device, usize::MAX if unknown
+    fn num_blocks(&self) -> usize {
+        usize::MAX
+    }
+    /// Hint that `block_id` will be read soon, a device with real latency may
+    /// start fetching it in the background. Does nothing by default
+    fn prefetch(&self, _block_id: usize) {}
+    /// Make every block written so far durable, e.g. by draining a volatile
+    /// write cache of the device. Does nothing by default
+    fn flush(&self) -> Result<(), BlockError> {
+        Ok(())
+    }
 }
@@ -0,0 +1,64 @@
+//! In-memory name index of large directories, turning lookups in them
+//! from a scan of every dirent into a search of a sorted map
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use lazy_static::*;
+use spin::Mutex;
+
+/// Directories with fewer dirents are scanned without building an index
+pub const DIR_INDEX_MIN_ENTRIES: usize = 64;
+/// Max number of directories indexed at the same time
+const DIR_INDEX_CAPACITY: usize = 16;
+
+/// 以目录的 DiskInode 所在的 (设备标识, 块编号, 块内偏移) 为键
+pub type DirKey = (usize, usize, usize);
+
+lazy_static! {
+    /// Name -> inode id of every dirent of each indexed directory
+    static ref DIR_INDEX: Mutex<BTreeMap<DirKey, BTreeMap<String, u32>>> =
+        Mutex::new(BTreeMap::new());
+}
+
+/// Look `name` up in the index of directory `dir`, None if `dir` is not
+/// indexed, Some(None) if it has no such dirent
+pub fn lookup(dir: DirKey, name: &str) -> Option<Option<u32>> {
+    DIR_INDEX
+        .lock()
+        .get(&dir)
+        .map(|entries| entries.get(name).copied())
+}
+
+/// Index directory `dir` with all its dirents `entries`, dropping the index
+/// of another directory if too many are indexed
+pub fn build(dir: DirKey, entries: BTreeMap<String, u32>) {
+    let mut index = DIR_INDEX.lock();
+    if index.len() >= DIR_INDEX_CAPACITY && !index.contains_key(&dir) {
+        let victim = *index.keys().next().unwrap();
+        index.remove(&victim);
+    }
+    index.insert(dir, entries);
+}
+
+/// Record the new dirent `name` -> `inode_id` of directory `dir`
+pub fn insert(dir: DirKey, name: &str, inode_id: u32) {
+    if let Some(entries) = DIR_INDEX.lock().get_mut(&dir) {
+        entries.insert(String::from(name), inode_id);
+    }
+}
+
+/// Forget the removed dirent `name` of directory `dir`
+pub fn remove(dir: DirKey, name: &str) {
+    if let Some(entries) = DIR_INDEX.lock().get_mut(&dir) {
+        entries.remove(name);
+    }
+}
+
+/// Drop the index of directory `dir`, e.g. once its inode is freed
+pub fn forget(dir: DirKey) {
+    DIR_INDEX.lock().remove(&dir);
+}
+
+/// Drop the indexes of every directory on device `device_id`
+pub fn forget_device(device_id: usize) {
+    DIR_INDEX.lock().retain(|dir, _| dir.0 != device_id);
+}
@@ -1,10 +1,15 @@
 use super::{
-    block_cache_sync_all, get_block_cache, Bitmap, BlockDevice, DiskInode, DiskInodeType, Inode,
-    SuperBlock,
+    block_cache_sync_all, get_block_cache, pin_block, try_get_block_cache, unpin_block, Bitmap, BitmapSnapshot,
+    BlockDevice, BlockError, DiskInode, DiskInodeType, Inode, SuperBlock, EFS_MIN_VERSION, EFS_VERSION, MAX_FILE_SIZE,
 };
+use crate::block_cache::device_id;
+use crate::dir_index;
+use crate::lock::RwLock;
 use crate::BLOCK_SZ;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
-use spin::Mutex;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
 ///An easy file system on block
 pub struct EasyFileSystem {
     ///Real device
@@ -15,9 +20,80 @@ pub struct EasyFileSystem {
     pub data_bitmap: Bitmap,
     inode_area_start_block: u32,
     data_area_start_block: u32,
+    data_area_blocks: u32,
+    /// 被多个文件共享的数据块 -> 除第一个文件外的引用数，不在表中的块只有一个引用
+    shared: BTreeMap<u32, u32>,
+    /// 引用计数表所在的第一个块，0 表示没有
+    refcount_block: u32,
+    /// 空闲 inode 数，与超级块中的计数同步更新
+    free_inode_count: u32,
+    /// 空闲数据块数，与超级块中的计数同步更新
+    free_data_count: u32,
+    /// 每次分配或回收数据块时加一，索引缓存据此判断是否过期
+    data_epoch: u64,
+    /// 磁盘格式版本
+    version: u32,
 }
 
 type DataBlock = [u8; BLOCK_SZ];
+/// A block of the refcount table: the next table block, the number of
+/// entries, then (data block, extra references) pairs
+type RefcountBlock = [u32; BLOCK_SZ / 4];
+/// Entries held by one refcount table block
+const REFCOUNTS_PER_BLOCK: usize = (BLOCK_SZ / 4 - 2) / 2;
+
+/// Size and usage of a filesystem, see [`EasyFileSystem::stat_fs`]
+#[derive(Debug, Clone, Copy)]
+pub struct FsStat {
+    /// Size of a block in bytes
+    pub block_size: usize,
+    /// Number of blocks in the data area
+    pub total_blocks: usize,
+    /// Number of free data blocks
+    pub free_blocks: usize,
+    /// Number of inodes
+    pub total_inodes: usize,
+    /// Number of free inodes
+    pub free_inodes: usize,
+    /// Max size of a file in bytes
+    pub max_file_size: usize,
+}
+
+/// Reason why a block device cannot be opened as a filesystem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenError {
+    /// Block 0 does not hold a valid super block
+    BadMagic,
+    /// The image has an on-disk format older than supported
+    TooOld(u32),
+    /// The super block claims more blocks than the device has
+    TooLarge {
+        /// Blocks claimed by the super block
+        total_blocks: u32,
+        /// Blocks of the device
+        device_blocks: usize,
+    },
+    /// The device failed to read the super block
+    Device(BlockError),
+}
+
+impl Display for OpenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenError::BadMagic => write!(f, "Error loading EFS!"),
+            OpenError::TooOld(version) => {
+                write!(f, "EFS image version {} is too old, recreate it", version)
+            }
+            OpenError::TooLarge { total_blocks, device_blocks } => write!(
+                f,
+                "EFS image of {} blocks does not fit the device of {} blocks",
+                total_blocks, device_blocks
+            ),
+            OpenError::Device(err) => write!(f, "{}", err),
+        }
+    }
+}
+
 /// An easy fs over a block device
 impl EasyFileSystem {
     /// A data block of block size
@@ -25,7 +101,13 @@ impl EasyFileSystem {
         block_device: Arc<dyn BlockDevice>,
         total_blocks: u32,
         inode_bitmap_blocks: u32,           // 指定了inode位图占用的块数
-    ) -> Arc<Mutex<Self>> {
+    ) -> Arc<RwLock<Self>> {
+        assert!(
+            total_blocks as usize <= block_device.num_blocks(),
+            "EFS image of {} blocks does not fit the device of {} blocks",
+            total_blocks,
+            block_device.num_blocks()
+        );
         // calculate block size of areas & create bitmaps
         // 根据传入的参数计算每个区域各应该包含多少块
         let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
@@ -53,7 +135,16 @@ impl EasyFileSystem {
             data_bitmap,
             inode_area_start_block: 1 + inode_bitmap_blocks,
             data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            data_area_blocks,
+            shared: BTreeMap::new(),
+            refcount_block: 0,
+            free_inode_count: inode_num as u32,
+            free_data_count: data_area_blocks,
+            data_epoch: 0,
+            version: EFS_VERSION,
         };
+        // 每次分配都会更新超级块中的空闲计数，让它常驻缓存
+        pin_block(0, &block_device);
         // clear all blocks
         for i in 0..total_blocks {
             get_block_cache(i as usize, Arc::clone(&block_device))
@@ -90,20 +181,49 @@ impl EasyFileSystem {
             });
         // 同步缓存到磁盘
         block_cache_sync_all();
-        Arc::new(Mutex::new(efs))
+        Arc::new(RwLock::new(efs))
     }
 
     /// Open a block device as a filesystem
     /// 从一个已写入了 easy-fs 镜像的块设备上打开我们的 easy-fs
-    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<RwLock<Self>> {
+        match Self::try_open(block_device) {
+            Ok(efs) => efs,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Open a block device as a filesystem, reporting why the image on it
+    /// cannot be used instead of panicking
+    pub fn try_open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<RwLock<Self>>, OpenError> {
         // read SuperBlock
-        get_block_cache(0, Arc::clone(&block_device))
+        let super_block_cache =
+            try_get_block_cache(0, Arc::clone(&block_device)).map_err(OpenError::Device)?;
+        let super_block_cache = super_block_cache.lock();
+        super_block_cache.read(0, |super_block: &SuperBlock| {
+            if !super_block.is_valid() {
+                return Err(OpenError::BadMagic);
+            }
+            if super_block.version < EFS_MIN_VERSION {
+                return Err(OpenError::TooOld(super_block.version));
+            }
+            // 超级块声称的大小超过设备时，后面的块根本无法访问
+            if super_block.total_blocks as usize > block_device.num_blocks() {
+                return Err(OpenError::TooLarge {
+                    total_blocks: super_block.total_blocks,
+                    device_blocks: block_device.num_blocks(),
+                });
+            }
+            Ok(())
+        })?;
+        drop(super_block_cache);
+        pin_block(0, &block_device);
+        let mut efs = get_block_cache(0, Arc::clone(&block_device))
             .lock()
             .read(0, |super_block: &SuperBlock| {
-                assert!(super_block.is_valid(), "Error loading EFS!");
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
-                let efs = Self {
+                Self {
                     block_device,
                     inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
                     data_bitmap: Bitmap::new(
@@ -112,16 +232,26 @@ impl EasyFileSystem {
                     ),
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
                     data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
-                };
-                Arc::new(Mutex::new(efs))
-            })
+                    data_area_blocks: super_block.data_area_blocks,
+                    shared: BTreeMap::new(),
+                    refcount_block: super_block.refcount_block,
+                    free_inode_count: super_block.free_inodes,
+                    free_data_count: super_block.free_data_blocks,
+                    data_epoch: 0,
+                    version: super_block.version,
+                }
+            });
         // 将块设备编号为 0 的块作为超级块读取进来，就可以从中知道 easy-fs 的磁盘布局，由此可以构造 efs 实例
+        // 修正计数要写超级块，须在放开它的块缓存之后
+        efs.load_refcounts();
+        efs.check_free_counts();
+        Ok(Arc::new(RwLock::new(efs)))
     }
     /// Get the root inode of the filesystem
-    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
-        let block_device = Arc::clone(&efs.lock().block_device);
+    pub fn root_inode(efs: &Arc<RwLock<Self>>) -> Inode {
+        let block_device = Arc::clone(&efs.read().block_device);
         // acquire efs lock temporarily
-        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
+        let (block_id, block_offset) = efs.read().get_disk_inode_pos(0);
         // release efs lock
         Inode::new(block_id, block_offset, Arc::clone(efs), block_device)
     }
@@ -135,21 +265,172 @@ impl EasyFileSystem {
             (inode_id % inodes_per_block) as usize * inode_size,
         )
     }
+    /// Get inode id by the position of its disk inode
+    pub fn get_disk_inode_id(&self, block_id: u32, block_offset: usize) -> u32 {
+        let inode_size = core::mem::size_of::<DiskInode>();
+        let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
+        (block_id - self.inode_area_start_block) * inodes_per_block
+            + (block_offset / inode_size) as u32
+    }
+    /// On-disk format version of the filesystem
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+    /// Max size of a file in bytes, as far as the block list of a DiskInode
+    /// can address. Writes stop short and resizes fail beyond it
+    pub fn max_file_size(&self) -> usize {
+        MAX_FILE_SIZE
+    }
+    /// Number of inodes still free
+    pub fn free_inodes(&self) -> usize {
+        self.free_inode_count as usize
+    }
+    /// Number of data blocks still free
+    pub fn free_data_blocks(&self) -> usize {
+        self.free_data_count as usize
+    }
+    /// Count the free (inodes, data blocks) by scanning both bitmaps
+    pub fn scan_free(&self) -> (usize, usize) {
+        (
+            self.inode_bitmap.maximum() - self.inode_bitmap.allocated(&self.block_device),
+            self.data_area_blocks as usize - self.data_bitmap.allocated(&self.block_device),
+        )
+    }
+    /// Recount the free inodes and data blocks from the bitmaps, fixing the
+    /// counters of the super block if they are stale, e.g. after a crash
+    fn check_free_counts(&mut self) {
+        let (free_inodes, free_data) = self.scan_free();
+        if (free_inodes as u32, free_data as u32) != (self.free_inode_count, self.free_data_count) {
+            self.free_inode_count = free_inodes as u32;
+            self.free_data_count = free_data as u32;
+            self.save_free_counts();
+        }
+    }
+    /// Write the free counters into the super block
+    fn save_free_counts(&self) {
+        let (free_inodes, free_data) = (self.free_inode_count, self.free_data_count);
+        get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |super_block: &mut SuperBlock| {
+                super_block.free_inodes = free_inodes;
+                super_block.free_data_blocks = free_data;
+            });
+    }
+    /// Report the size and usage of the data and inode areas
+    pub fn stat_fs(&self) -> FsStat {
+        FsStat {
+            block_size: BLOCK_SZ,
+            total_blocks: self.data_area_blocks as usize,
+            free_blocks: self.free_data_blocks(),
+            total_inodes: self.inode_bitmap.maximum(),
+            free_inodes: self.free_inodes(),
+            max_file_size: self.max_file_size(),
+        }
+    }
+    /// Copy the inode and data bitmaps, the caller holds the fs lock so the
+    /// copy is consistent
+    pub fn snapshot_bitmaps(&self) -> BitmapSnapshot {
+        BitmapSnapshot {
+            inode: self.inode_bitmap.snapshot(&self.block_device),
+            data: self.data_bitmap.snapshot(&self.block_device),
+        }
+    }
+    /// Roll the bitmaps back to `snapshot`, releasing every inode and data
+    /// block allocated since it was taken. Only the bitmaps are restored,
+    /// the caller undoes any reference to the released ones
+    pub fn restore_bitmaps(&mut self, snapshot: &BitmapSnapshot) {
+        self.inode_bitmap.restore(&self.block_device, &snapshot.inode);
+        self.data_bitmap.restore(&self.block_device, &snapshot.data);
+        self.data_epoch += 1;
+        self.check_free_counts();
+    }
     /// Get data block by id
     pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
         self.data_area_start_block + data_block_id
     }
+    /// Count of data block allocations and deallocations so far, the mapping
+    /// from file offsets to data blocks only changes when it moves
+    pub fn data_epoch(&self) -> u64 {
+        self.data_epoch
+    }
     /// Allocate a new inode
     pub fn alloc_inode(&mut self) -> u32 {
-        self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
+        self.try_alloc_inode().unwrap()
+    }
+    /// Allocate a new inode, None if every inode is in use
+    pub fn try_alloc_inode(&mut self) -> Option<u32> {
+        #[cfg(any(test, feature = "fail_inject"))]
+        if crate::fail::injected_failure() {
+            return None;
+        }
+        let inode_id = self.inode_bitmap.alloc(&self.block_device)?;
+        self.free_inode_count -= 1;
+        self.save_free_counts();
+        Some(inode_id as u32)
+    }
+
+    /// Deallocate an inode
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap.dealloc(&self.block_device, inode_id as usize);
+        self.free_inode_count += 1;
+        self.save_free_counts();
     }
 
     /// Allocate a data block
     pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
+        self.try_alloc_data().unwrap()
+    }
+    /// Allocate a data block, None if the data area is full
+    pub fn try_alloc_data(&mut self) -> Option<u32> {
+        self.alloc_data_bit(None)
+    }
+    /// Allocate a data block like [`EasyFileSystem::try_alloc_data`],
+    /// preferring a free block at or after the block `goal`, e.g. right
+    /// after the last block of the growing file. Any free block is taken if
+    /// there is none nearby or `goal` is outside of the data area
+    pub fn try_alloc_data_near(&mut self, goal: u32) -> Option<u32> {
+        let goal = goal
+            .checked_sub(self.data_area_start_block)
+            .filter(|bit| *bit < self.data_area_blocks);
+        self.alloc_data_bit(goal.map(|bit| bit as usize))
+    }
+    /// 在数据位图中分配一位，有 goal 时优先从它开始向后查找
+    fn alloc_data_bit(&mut self, goal: Option<usize>) -> Option<u32> {
+        #[cfg(any(test, feature = "fail_inject"))]
+        if crate::fail::injected_failure() {
+            return None;
+        }
+        let mut bit = match goal {
+            Some(goal) => self.data_bitmap.alloc_near(&self.block_device, goal)?,
+            None => self.data_bitmap.alloc(&self.block_device)?,
+        };
+        // goal 之后的查找可能越过数据区末尾，此时改为从头查找
+        if goal.is_some() && bit >= self.data_area_blocks as usize {
+            self.data_bitmap.dealloc(&self.block_device, bit);
+            bit = self.data_bitmap.alloc(&self.block_device)?;
+        }
+        // 位图的位数多于数据区的块数，超出数据区的位不能分配
+        if bit >= self.data_area_blocks as usize {
+            self.data_bitmap.dealloc(&self.block_device, bit);
+            return None;
+        }
+        self.data_epoch += 1;
+        self.free_data_count -= 1;
+        self.save_free_counts();
+        Some(bit as u32 + self.data_area_start_block)
     }
-    /// Deallocate a data block
+    /// Deallocate a data block. A block shared with other files only loses
+    /// one reference and keeps its data
     pub fn dealloc_data(&mut self, block_id: u32) {
+        if let Some(extra) = self.shared.get_mut(&block_id) {
+            *extra -= 1;
+            if *extra == 0 {
+                self.shared.remove(&block_id);
+            }
+            // 表只会缩小，不需要分配块
+            assert!(self.save_refcounts());
+            return;
+        }
         get_block_cache(block_id as usize, Arc::clone(&self.block_device))
             .lock()
             .modify(0, |data_block: &mut DataBlock| {
@@ -160,6 +441,113 @@ impl EasyFileSystem {
         self.data_bitmap.dealloc(
             &self.block_device,
             (block_id - self.data_area_start_block) as usize,
-        )
+        );
+        self.data_epoch += 1;
+        self.free_data_count += 1;
+        self.save_free_counts();
+    }
+    /// Number of files referring to data block `block_id`
+    pub fn data_refs(&self, block_id: u32) -> u32 {
+        1 + self.shared.get(&block_id).copied().unwrap_or(0)
+    }
+    /// Add one reference to each of the data blocks `block_ids`,
+    /// which are then freed only after every file drops them.
+    /// False with nothing changed if the disk has no room for the
+    /// larger refcount table
+    pub fn share_data(&mut self, block_ids: &[u32]) -> bool {
+        for block_id in block_ids {
+            *self.shared.entry(*block_id).or_insert(0) += 1;
+        }
+        if self.save_refcounts() {
+            return true;
+        }
+        for block_id in block_ids {
+            let extra = self.shared.get_mut(block_id).unwrap();
+            *extra -= 1;
+            if *extra == 0 {
+                self.shared.remove(block_id);
+            }
+        }
+        false
+    }
+    /// Blocks of the refcount table on disk, in chain order
+    fn refcount_chain(&self) -> Vec<u32> {
+        let mut chain = Vec::new();
+        let mut block_id = self.refcount_block;
+        while block_id != 0 {
+            chain.push(block_id);
+            block_id = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .read(0, |refcount_block: &RefcountBlock| refcount_block[0]);
+        }
+        chain
+    }
+    /// Read the refcount table from disk
+    fn load_refcounts(&mut self) {
+        for block_id in self.refcount_chain() {
+            get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .read(0, |refcount_block: &RefcountBlock| {
+                    let count = refcount_block[1] as usize;
+                    for pair in refcount_block[2..2 + count * 2].chunks(2) {
+                        self.shared.insert(pair[0], pair[1]);
+                    }
+                });
+        }
+    }
+    /// Write the whole refcount table back, growing or shrinking its chain.
+    /// False with the table on disk untouched if the chain cannot grow
+    fn save_refcounts(&mut self) -> bool {
+        let mut chain = self.refcount_chain();
+        let needed = self.shared.len().div_ceil(REFCOUNTS_PER_BLOCK);
+        let old_len = chain.len();
+        while chain.len() < needed {
+            match self.try_alloc_data() {
+                Some(block_id) => chain.push(block_id),
+                None => {
+                    // 先分配完再写入，失败时只需归还本次分配的块
+                    for block_id in chain.drain(old_len..) {
+                        self.dealloc_data(block_id);
+                    }
+                    return false;
+                }
+            }
+        }
+        while chain.len() > needed {
+            let block_id = chain.pop().unwrap();
+            self.dealloc_data(block_id);
+        }
+        let entries: Vec<(u32, u32)> = self.shared.iter().map(|(k, v)| (*k, *v)).collect();
+        for (i, block_id) in chain.iter().enumerate() {
+            let next = chain.get(i + 1).copied().unwrap_or(0);
+            let part = entries.chunks(REFCOUNTS_PER_BLOCK).nth(i).unwrap();
+            get_block_cache(*block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .modify(0, |refcount_block: &mut RefcountBlock| {
+                    refcount_block[0] = next;
+                    refcount_block[1] = part.len() as u32;
+                    for (j, (block_id, extra)) in part.iter().enumerate() {
+                        refcount_block[2 + j * 2] = *block_id;
+                        refcount_block[3 + j * 2] = *extra;
+                    }
+                });
+        }
+        let first = chain.first().copied().unwrap_or(0);
+        if first != self.refcount_block {
+            self.refcount_block = first;
+            get_block_cache(0, Arc::clone(&self.block_device))
+                .lock()
+                .modify(0, |super_block: &mut SuperBlock| {
+                    super_block.refcount_block = first;
+                });
+        }
+        true
+    }
+}
+
+impl Drop for EasyFileSystem {
+    fn drop(&mut self) {
+        unpin_block(0, &self.block_device);
+        dir_index::forget_device(device_id(&self.block_device));
     }
 }
@@ -0,0 +1,19 @@
+//! Allocation failure injection, built for the host tests and with the `fail_inject` feature
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Allocations left before the injected failure, 0 when disabled
+static FAIL_AFTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Make the `n`th next inode or data block allocation fail, e.g. 1 fails the
+/// very next one. 0 disables the injection. Only one failure is injected
+pub fn fail_alloc_after(n: usize) {
+    FAIL_AFTER.store(n, Ordering::SeqCst);
+}
+
+/// Count one allocation, true if it is the one that has to fail
+pub(crate) fn injected_failure() -> bool {
+    // 计数减到 0 的那一次分配失败，之后不再注入
+    FAIL_AFTER
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1))
+        .is_ok_and(|left| left == 1)
+}
@@ -5,10 +5,24 @@ use core::fmt::{Debug, Formatter, Result};
 
 /// Magic number for sanity check
 const EFS_MAGIC: u32 = 0x3b800001;
+/// On-disk format version
+/// version 1: DirEntry carries the type of the entry
+/// version 2: DiskInode carries permission bits
+/// version 3: DiskInode carries the owner uid/gid in place of its last direct index
+/// version 4: DiskInode carries atime/mtime in place of two more direct indexes
+/// version 5: DiskInode carries a generation in place of one more direct index
+/// version 6: DiskInode carries its link count in place of one more direct index
+pub const EFS_VERSION: u32 = 6;
+/// Images older than this lay out DiskInode differently and cannot be opened
+pub const EFS_MIN_VERSION: u32 = 6;
+/// Permission bits of a newly created file, rw-r--r--
+pub const DEFAULT_FILE_MODE: u16 = 0o644;
+/// Permission bits of a newly created directory, rwxr-xr-x
+pub const DEFAULT_DIR_MODE: u16 = 0o755;
 /// The max number of direct inodes
-const INODE_DIRECT_COUNT: usize = 28;
+pub(crate) const INODE_DIRECT_COUNT: usize = 23;
 /// The max length of inode name
-const NAME_LENGTH_LIMIT: usize = 27;
+pub const NAME_LENGTH_LIMIT: usize = 26;
 /// The max number of indirect1 inodes
 const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
 /// The max number of indirect2 inodes
@@ -18,8 +32,12 @@ const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
 /// The upper bound of indirect1 inode index
 const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
 /// The upper bound of indirect2 inode indexs
-#[allow(unused)]
 const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+/// Max size of a file in bytes, bounded by the blocks its block list can address
+pub const MAX_FILE_SIZE: usize = INDIRECT2_BOUND * BLOCK_SZ;
+/// Block id recorded in the block list for a hole of a sparse file.
+/// Block 0 always holds the super block, so it is never a data block.
+pub const HOLE_BLOCK_ID: u32 = 0;
 /// Super block of a filesystem
 /// 超级块
 #[repr(C)]
@@ -32,6 +50,14 @@ pub struct SuperBlock {
     pub inode_area_blocks: u32,
     pub data_bitmap_blocks: u32,
     pub data_area_blocks: u32,
+    /// 磁盘格式版本
+    pub version: u32,
+    /// 共享数据块引用计数表的第一个块，0 表示没有被共享的块
+    pub refcount_block: u32,
+    /// 空闲 inode 数，挂载时与位图核对
+    pub free_inodes: u32,
+    /// 空闲数据块数，挂载时与位图核对
+    pub free_data_blocks: u32,
 }
 
 impl Debug for SuperBlock {
@@ -42,6 +68,10 @@ impl Debug for SuperBlock {
             .field("inode_area_blocks", &self.inode_area_blocks)
             .field("data_bitmap_blocks", &self.data_bitmap_blocks)
             .field("data_area_blocks", &self.data_area_blocks)
+            .field("version", &self.version)
+            .field("refcount_block", &self.refcount_block)
+            .field("free_inodes", &self.free_inodes)
+            .field("free_data_blocks", &self.free_data_blocks)
             .finish()
     }
 }
@@ -66,6 +96,10 @@ impl SuperBlock {
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            version: EFS_VERSION,
+            refcount_block: 0,
+            free_inodes: 0,
+            free_data_blocks: 0,
         }
     }
     /// Check if a super block is valid using efs magic
@@ -85,6 +119,34 @@ pub enum DiskInodeType {
 type IndirectBlock = [u32; BLOCK_SZ / 4];
 /// A data block
 type DataBlock = [u8; BLOCK_SZ];
+
+/// A copy of the index block that last translated a data block of a file,
+/// so random reads mapped by the same index block skip reading it again
+pub struct IndexCache {
+    /// 复制时文件系统的数据块分配代数，之后有分配或回收时作废
+    epoch: u64,
+    /// 该索引块映射的第一个数据块的 inner id
+    first: usize,
+    entries: Option<IndirectBlock>,
+}
+
+impl IndexCache {
+    /// An empty cache
+    pub const fn new() -> Self {
+        Self {
+            epoch: 0,
+            first: 0,
+            entries: None,
+        }
+    }
+}
+
+impl Default for IndexCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A disk inode
 #[repr(C)]
 pub struct DiskInode {
@@ -96,8 +158,22 @@ pub struct DiskInode {
     pub indirect1: u32,
     /// 二级间接索引
     pub indirect2: u32,
+    /// 所有者的用户 id
+    pub uid: u16,
+    /// 所有者的组 id
+    pub gid: u16,
+    /// 最近一次访问的时间，单位为秒
+    pub atime: u32,
+    /// 最近一次修改内容的时间，单位为秒
+    pub mtime: u32,
+    /// 每次重新初始化时加一，用来识别指向已被回收重用的 inode 的旧句柄
+    pub generation: u32,
+    /// 指向该 inode 的目录项数，不含 . 和 ..，减到 0 时回收
+    pub nlink: u32,
     /// 索引节点的类型
     type_: DiskInodeType,
+    /// 权限位 rwxrwxrwx
+    pub mode: u16,
 }
 
 impl DiskInode {
@@ -109,6 +185,18 @@ impl DiskInode {
         self.direct.iter_mut().for_each(|v| *v = 0);
         self.indirect1 = 0;
         self.indirect2 = 0;
+        self.uid = 0;
+        self.gid = 0;
+        self.atime = 0;
+        self.mtime = 0;
+        // 不清零：回收后再分配的 inode 要与之前的使用者区分开
+        self.generation = self.generation.wrapping_add(1);
+        // 新建时只有父目录中的一个目录项
+        self.nlink = 1;
+        self.mode = match type_ {
+            DiskInodeType::File => DEFAULT_FILE_MODE,
+            DiskInodeType::Directory => DEFAULT_DIR_MODE,
+        };
         self.type_ = type_;
     }
     
@@ -123,6 +211,15 @@ impl DiskInode {
         self.type_ == DiskInodeType::File
     }
 
+    /// Type of the directory entry pointing to this inode
+    pub fn dirent_type(&self) -> DirEntryType {
+        if self.is_dir() {
+            DirEntryType::Directory
+        } else {
+            DirEntryType::File
+        }
+    }
+
     /// Return block number correspond to size.
     /// 可以计算为了容纳自身 size 字节的内容需要多少个数据块
     pub fn data_blocks(&self) -> u32 {
@@ -191,6 +288,64 @@ impl DiskInode {
         }
     }
 
+    /// Get id of block given inner id like [`DiskInode::get_block_id`], looking
+    /// up blocks mapped by an index block in `cache` first. `epoch` is the
+    /// data allocation epoch of the filesystem, a cache from an older epoch
+    /// is dropped
+    pub fn get_block_id_cached(
+        &self,
+        inner_id: u32,
+        block_device: &Arc<dyn BlockDevice>,
+        cache: &mut IndexCache,
+        epoch: u64,
+    ) -> u32 {
+        let inner_id = inner_id as usize;
+        if inner_id < INODE_DIRECT_COUNT {
+            return self.direct[inner_id];
+        }
+        // 映射该数据块的是一级索引块，或二级索引下的某个索引块
+        let first = if inner_id < INDIRECT1_BOUND {
+            INODE_DIRECT_COUNT
+        } else {
+            let last = inner_id - INDIRECT1_BOUND;
+            INDIRECT1_BOUND + last / INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT
+        };
+        if cache.epoch == epoch && cache.first == first {
+            if let Some(entries) = &cache.entries {
+                return entries[inner_id - first];
+            }
+        }
+        let index_block = if inner_id < INDIRECT1_BOUND {
+            self.indirect1
+        } else {
+            get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[(first - INDIRECT1_BOUND) / INODE_INDIRECT1_COUNT]
+                })
+        };
+        let entries = get_block_cache(index_block as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |indirect_block: &IndirectBlock| *indirect_block);
+        let block_id = entries[inner_id - first];
+        *cache = IndexCache {
+            epoch,
+            first,
+            entries: Some(entries),
+        };
+        block_id
+    }
+
+    /// Iterate over ids of all data blocks in file order, stopping at size.
+    /// Holes of a sparse file are yielded as `HOLE_BLOCK_ID`.
+    /// 依次经过直接索引、一级索引和二级索引，不包含索引块本身
+    pub fn iter_blocks<'a>(
+        &'a self,
+        block_device: &'a Arc<dyn BlockDevice>,
+    ) -> impl Iterator<Item = u32> + 'a {
+        (0..self.data_blocks()).map(move |inner_id| self.get_block_id(inner_id, block_device))
+    }
+
     /// Inncrease the size of current disk inode
     /// new_size 表示容量扩充之后的文件大小
     /// new_blocks 是一个保存了本次容量扩充所需块编号的向量
@@ -199,6 +354,35 @@ impl DiskInode {
         new_size: u32,
         new_blocks: Vec<u32>,
         block_device: &Arc<dyn BlockDevice>,
+    ) {
+        self.grow(new_size, new_blocks, false, block_device);
+    }
+
+    /// Get the number of index blocks that have to be allocated given the new size of data
+    /// 稀疏扩容时只需要分配索引块，数据块留作空洞
+    pub fn index_blocks_needed(&self, new_size: u32) -> u32 {
+        self.blocks_num_needed(new_size) - (Self::_data_blocks(new_size) - self.data_blocks())
+    }
+
+    /// Increase the size of current disk inode, leaving the new data blocks as holes
+    /// index_blocks only holds the indirect blocks needed by the new size
+    pub fn increase_size_sparse(
+        &mut self,
+        new_size: u32,
+        index_blocks: Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        self.grow(new_size, index_blocks, true, block_device);
+    }
+
+    /// Fill the block list up to new_size, taking index blocks and (unless sparse)
+    /// data blocks from new_blocks in order
+    fn grow(
+        &mut self,
+        new_size: u32,
+        new_blocks: Vec<u32>,
+        sparse: bool,
+        block_device: &Arc<dyn BlockDevice>,
     ) {
         let mut current_blocks = self.data_blocks();
         self.size = new_size;
@@ -206,7 +390,11 @@ impl DiskInode {
         let mut new_blocks = new_blocks.into_iter();
         // fill direct
         while current_blocks < total_blocks.min(INODE_DIRECT_COUNT as u32) {
-            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
+            self.direct[current_blocks as usize] = if sparse {
+                HOLE_BLOCK_ID
+            } else {
+                new_blocks.next().unwrap()
+            };
             current_blocks += 1;
         }
         // alloc indirect1
@@ -224,7 +412,11 @@ impl DiskInode {
             .lock()
             .modify(0, |indirect1: &mut IndirectBlock| {
                 while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
-                    indirect1[current_blocks as usize] = new_blocks.next().unwrap();
+                    indirect1[current_blocks as usize] = if sparse {
+                        HOLE_BLOCK_ID
+                    } else {
+                        new_blocks.next().unwrap()
+                    };
                     current_blocks += 1;
                 }
             });
@@ -252,10 +444,15 @@ impl DiskInode {
                         indirect2[a0] = new_blocks.next().unwrap();
                     }
                     // fill current
+                    let data = if sparse {
+                        HOLE_BLOCK_ID
+                    } else {
+                        new_blocks.next().unwrap()
+                    };
                     get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
                         .lock()
                         .modify(0, |indirect1: &mut IndirectBlock| {
-                            indirect1[b0] = new_blocks.next().unwrap();
+                            indirect1[b0] = data;
                         });
                     // move to next
                     b0 += 1;
@@ -267,8 +464,40 @@ impl DiskInode {
             });
     }
 
+    /// Set id of block given inner id, the index blocks on the way must exist
+    /// 用于给空洞分配数据块后回填索引
+    pub fn set_block_id(
+        &mut self,
+        inner_id: u32,
+        block_id: u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        let inner_id = inner_id as usize;
+        if inner_id < INODE_DIRECT_COUNT {
+            self.direct[inner_id] = block_id;
+        } else if inner_id < INDIRECT1_BOUND {
+            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+                .lock()
+                .modify(0, |indirect_block: &mut IndirectBlock| {
+                    indirect_block[inner_id - INODE_DIRECT_COUNT] = block_id;
+                });
+        } else {
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[last / INODE_INDIRECT1_COUNT]
+                });
+            get_block_cache(indirect1 as usize, Arc::clone(block_device))
+                .lock()
+                .modify(0, |indirect1: &mut IndirectBlock| {
+                    indirect1[last % INODE_INDIRECT1_COUNT] = block_id;
+                });
+        }
+    }
+
     /// Clear size to zero and return blocks that should be deallocated.
-    /// We will clear the block contents to zero later.
+    /// We will clear the block contents to zero later. Holes are skipped.
     /// 清空文件的内容并回收所有数据和索引块
     pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
         let mut v: Vec<u32> = Vec::new();
@@ -277,7 +506,9 @@ impl DiskInode {
         let mut current_blocks = 0usize;
         // direct
         while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
-            v.push(self.direct[current_blocks]);
+            if self.direct[current_blocks] != HOLE_BLOCK_ID {
+                v.push(self.direct[current_blocks]);
+            }
             self.direct[current_blocks] = 0;
             current_blocks += 1;
         }
@@ -294,7 +525,9 @@ impl DiskInode {
             .lock()
             .modify(0, |indirect1: &mut IndirectBlock| {
                 while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
-                    v.push(indirect1[current_blocks]);
+                    if indirect1[current_blocks] != HOLE_BLOCK_ID {
+                        v.push(indirect1[current_blocks]);
+                    }
                     //indirect1[current_blocks] = 0;
                     current_blocks += 1;
                 }
@@ -320,7 +553,7 @@ impl DiskInode {
                     get_block_cache(*entry as usize, Arc::clone(block_device))
                         .lock()
                         .modify(0, |indirect1: &mut IndirectBlock| {
-                            for entry in indirect1.iter() {
+                            for entry in indirect1.iter().filter(|id| **id != HOLE_BLOCK_ID) {
                                 v.push(*entry);
                             }
                         });
@@ -331,7 +564,11 @@ impl DiskInode {
                     get_block_cache(indirect2[a1] as usize, Arc::clone(block_device))
                         .lock()
                         .modify(0, |indirect1: &mut IndirectBlock| {
-                            for entry in indirect1.iter().take(b1) {
+                            for entry in indirect1
+                                .iter()
+                                .take(b1)
+                                .filter(|id| **id != HOLE_BLOCK_ID)
+                            {
                                 v.push(*entry);
                             }
                         });
@@ -341,6 +578,70 @@ impl DiskInode {
         self.indirect2 = 0;
         v
     }
+
+    /// Decrease the size of current disk inode and return blocks that should be deallocated,
+    /// including the index blocks no longer needed. Freed slots become holes.
+    /// 截断文件：回收 new_size 之后的数据块和多余的索引块
+    pub fn decrease_size(
+        &mut self,
+        new_size: u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Vec<u32> {
+        assert!(new_size <= self.size);
+        let mut v: Vec<u32> = Vec::new();
+        let old_blocks = self.data_blocks();
+        let new_blocks = Self::_data_blocks(new_size);
+        // data blocks, index blocks are still needed to locate them
+        for inner_id in new_blocks..old_blocks {
+            let block_id = self.get_block_id(inner_id, block_device);
+            if block_id != HOLE_BLOCK_ID {
+                v.push(block_id);
+                self.set_block_id(inner_id, HOLE_BLOCK_ID, block_device);
+            }
+        }
+        // zero the tail of the last block, so that growing again reads zeros
+        let tail = new_size as usize % BLOCK_SZ;
+        if tail != 0 {
+            let block_id = self.get_block_id(new_blocks - 1, block_device);
+            if block_id != HOLE_BLOCK_ID {
+                get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .modify(0, |data_block: &mut DataBlock| {
+                        data_block[tail..].fill(0);
+                    });
+            }
+        }
+        let (old_blocks, new_blocks) = (old_blocks as usize, new_blocks as usize);
+        // low-level indirect1 blocks under indirect2
+        if old_blocks > INDIRECT1_BOUND {
+            let sub_count = |blocks: usize| {
+                blocks.saturating_sub(INDIRECT1_BOUND).div_ceil(INODE_INDIRECT1_COUNT)
+            };
+            get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+                .lock()
+                .modify(0, |indirect2: &mut IndirectBlock| {
+                    for entry in indirect2
+                        .iter_mut()
+                        .take(sub_count(old_blocks))
+                        .skip(sub_count(new_blocks))
+                    {
+                        v.push(*entry);
+                        *entry = 0;
+                    }
+                });
+            if new_blocks <= INDIRECT1_BOUND {
+                v.push(self.indirect2);
+                self.indirect2 = 0;
+            }
+        }
+        // indirect1 block
+        if old_blocks > INODE_DIRECT_COUNT && new_blocks <= INODE_DIRECT_COUNT {
+            v.push(self.indirect1);
+            self.indirect1 = 0;
+        }
+        self.size = new_size;
+        v
+    }
     
     /// Read data from current disk inode
     pub fn read_at(
@@ -348,6 +649,31 @@ impl DiskInode {
         offset: usize,
         buf: &mut [u8],
         block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        self.read_blocks_at(offset, buf, block_device, |inner_id| {
+            self.get_block_id(inner_id, block_device)
+        })
+    }
+    /// Read data from current disk inode, translating through `cache`,
+    /// see [`DiskInode::get_block_id_cached`]
+    pub fn read_at_cached(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+        cache: &mut IndexCache,
+        epoch: u64,
+    ) -> usize {
+        self.read_blocks_at(offset, buf, block_device, |inner_id| {
+            self.get_block_id_cached(inner_id, block_device, cache, epoch)
+        })
+    }
+    fn read_blocks_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+        mut block_id_of: impl FnMut(u32) -> u32,
     ) -> usize {
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
@@ -363,15 +689,19 @@ impl DiskInode {
             // read and update read size
             let block_read_size = end_current_block - start;
             let dst = &mut buf[read_size..read_size + block_read_size];
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
-                Arc::clone(block_device),
-            )
-            .lock()
-            .read(0, |data_block: &DataBlock| {
-                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
-                dst.copy_from_slice(src);
-            });
+            let block_id = block_id_of(start_block as u32);
+            if block_id == HOLE_BLOCK_ID {
+                // 空洞部分读出全 0
+                dst.fill(0);
+            } else {
+                get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |data_block: &DataBlock| {
+                        let src =
+                            &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                        dst.copy_from_slice(src);
+                    });
+            }
             read_size += block_read_size;
             // move to next block
             if end_current_block == end {
@@ -383,7 +713,7 @@ impl DiskInode {
         read_size
     }
     /// Write data into current disk inode
-    /// size must be adjusted properly beforehand
+    /// size must be adjusted properly beforehand, and holes in the range filled
     pub fn write_at(
         &mut self,
         offset: usize,
@@ -422,10 +752,24 @@ impl DiskInode {
         write_size
     }
 }
+/// Type of a directory entry, recorded in the entry itself
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirEntryType {
+    /// A type byte easy-fs never writes, e.g. of a corrupted entry
+    Unknown = 0,
+    /// Regular file
+    File = 1,
+    /// Directory
+    Directory = 2,
+}
+
 /// A directory entry
 #[repr(C)]
 pub struct DirEntry {
     name: [u8; NAME_LENGTH_LIMIT + 1],
+    /// 不认识的类型读出为 Unknown
+    type_: u8,
     inode_id: u32,
 }
 /// Size of a directory entry
@@ -436,15 +780,17 @@ impl DirEntry {
     pub fn empty() -> Self {
         Self {
             name: [0u8; NAME_LENGTH_LIMIT + 1],
+            type_: DirEntryType::Unknown as u8,
             inode_id: 0,
         }
     }
-    /// Crate a directory entry from name and inode number
-    pub fn new(name: &str, inode_id: u32) -> Self {
+    /// Crate a directory entry from name, inode number and type
+    pub fn new(name: &str, inode_id: u32, type_: DirEntryType) -> Self {
         let mut bytes = [0u8; NAME_LENGTH_LIMIT + 1];
         bytes[..name.len()].copy_from_slice(name.as_bytes());
         Self {
             name: bytes,
+            type_: type_ as u8,
             inode_id,
         }
     }
@@ -456,13 +802,27 @@ impl DirEntry {
     pub fn as_bytes_mut(&mut self) -> &mut [u8] {
         unsafe { core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, DIRENT_SZ) }
     }
-    /// Get name of the entry
+    /// Get name of the entry. A corrupted name missing its terminating 0
+    /// ends at the end of the field, and is cut before any invalid UTF-8
     pub fn name(&self) -> &str {
-        let len = (0usize..).find(|i| self.name[*i] == 0).unwrap();
-        core::str::from_utf8(&self.name[..len]).unwrap()
+        let len = self.name.iter().position(|&byte| byte == 0).unwrap_or(self.name.len());
+        let bytes = &self.name[..len];
+        match core::str::from_utf8(bytes) {
+            Ok(name) => name,
+            // 只保留合法的 UTF-8 前缀，损坏的目录项不致 panic
+            Err(err) => core::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap(),
+        }
     }
     /// Get inode number of the entry
     pub fn inode_id(&self) -> u32 {
         self.inode_id
     }
+    /// Get type of the entry
+    pub fn entry_type(&self) -> DirEntryType {
+        match self.type_ {
+            1 => DirEntryType::File,
+            2 => DirEntryType::Directory,
+            _ => DirEntryType::Unknown,
+        }
+    }
 }
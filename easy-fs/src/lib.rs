@@ -5,14 +5,33 @@ extern crate alloc;
 mod bitmap;
 mod block_cache;
 mod block_dev;
+mod dir_index;
 mod efs;
+#[cfg(any(test, feature = "fail_inject"))]
+mod fail;
 mod layout;
+mod lock;
+#[cfg(test)]
+mod tests;
 mod vfs;
 /// Use a block size of 512 bytes
 pub const BLOCK_SZ: usize = 512;
 use bitmap::Bitmap;
-use block_cache::{block_cache_sync_all, get_block_cache};
-pub use block_dev::BlockDevice;
-pub use efs::EasyFileSystem;
+pub use bitmap::BitmapSnapshot;
+use block_cache::{block_cache_sync_all, get_block_cache, prefetch_block};
+pub use block_cache::{
+    block_cache_sync_ordered, block_cached, invalidate_block, pin_block, sweep_block_cache,
+    try_block_cache_sync_all, try_block_cache_sync_ordered, try_get_block_cache,
+    try_sweep_block_cache, unpin_block, BlockCache,
+};
+pub use block_dev::{BlockDevice, BlockError};
+pub use efs::{EasyFileSystem, FsStat, OpenError};
+#[cfg(any(test, feature = "fail_inject"))]
+pub use fail::fail_alloc_after;
+pub use lock::{set_lock_yield, RwLock};
+pub use layout::{
+    DirEntryType, DEFAULT_DIR_MODE, DEFAULT_FILE_MODE, EFS_VERSION, MAX_FILE_SIZE,
+    NAME_LENGTH_LIMIT,
+};
 use layout::*;
-pub use vfs::Inode;
+pub use vfs::{Inode, RemoveError};
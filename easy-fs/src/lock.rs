@@ -0,0 +1,54 @@
+//! Reader-writer lock around the filesystem which spins for a short while
+//! and then gives up the CPU instead of busy-waiting for a descheduled holder
+use spin::{Once, RwLockReadGuard, RwLockWriteGuard};
+
+/// Failed attempts to spin before yielding
+const SPIN_LIMIT: usize = 64;
+
+/// 由使用者注册的让出 CPU 的方法，未注册时一直自旋
+static YIELD_HOOK: Once<fn()> = Once::new();
+
+/// Register how a waiter gives up the CPU, e.g. switching to another task.
+/// Only the first registration takes effect
+pub fn set_lock_yield(hook: fn()) {
+    YIELD_HOOK.call_once(|| hook);
+}
+
+/// Adaptive reader-writer lock with the semantics of `spin::RwLock`
+pub struct RwLock<T> {
+    inner: spin::RwLock<T>,
+}
+
+impl<T> RwLock<T> {
+    /// Create a lock guarding `data`
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: spin::RwLock::new(data),
+        }
+    }
+    /// Lock with shared read access
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        acquire(|| self.inner.try_read())
+    }
+    /// Lock with exclusive write access
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        acquire(|| self.inner.try_write())
+    }
+}
+
+/// Retry `try_lock` until it succeeds, spinning first and yielding afterwards
+fn acquire<G>(try_lock: impl Fn() -> Option<G>) -> G {
+    let mut spins = 0;
+    loop {
+        if let Some(guard) = try_lock() {
+            return guard;
+        }
+        match YIELD_HOOK.get() {
+            Some(hook) if spins >= SPIN_LIMIT => hook(),
+            _ => {
+                spins += 1;
+                core::hint::spin_loop();
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use super::{serial, RamDisk};
+use crate::bitmap::Bitmap;
+use crate::{BlockDevice, BLOCK_SZ};
+use alloc::sync::Arc;
+use core::sync::atomic::Ordering;
+
+#[test]
+fn alloc_skips_full_blocks_without_reading_them() {
+    let _serial = serial();
+    const BLOCKS: usize = 40;
+    const BLOCK_BITS: usize = BLOCK_SZ * 8;
+    let disk = RamDisk::new(BLOCKS);
+    let device: Arc<dyn BlockDevice> = disk.clone();
+    let mut bitmap = Bitmap::new(0, BLOCKS);
+    for bit in 0..(BLOCKS - 1) * BLOCK_BITS {
+        assert_eq!(bitmap.alloc(&device), Some(bit));
+    }
+    // 前面的块大多已被换出缓存，逐块检查的话每块都要重新读盘
+    let reads = disk.reads.load(Ordering::SeqCst);
+    assert_eq!(bitmap.alloc(&device), Some((BLOCKS - 1) * BLOCK_BITS));
+    assert_eq!(disk.reads.load(Ordering::SeqCst) - reads, 1);
+    // 释放后该块不再视为已满
+    let freed = 5 * BLOCK_BITS + 7;
+    bitmap.dealloc(&device, freed);
+    assert_eq!(bitmap.alloc(&device), Some(freed));
+}
+
+#[test]
+fn alloc_near_prefers_the_goal_then_a_free_run_after_it() {
+    let _serial = serial();
+    let disk = RamDisk::new(4);
+    let device: Arc<dyn BlockDevice> = disk.clone();
+    let mut bitmap = Bitmap::new(0, 2);
+    assert_eq!(bitmap.alloc_near(&device, 100), Some(100));
+    assert_eq!(bitmap.alloc_near(&device, 101), Some(101));
+    // goal 已占用时跳到其后一段完全空闲的 64 位
+    assert_eq!(bitmap.alloc_near(&device, 100), Some(128));
+    // 所在的位图块已满时退回到任意空闲位
+    let block_bits = BLOCK_SZ * 8;
+    for bit in 0..block_bits {
+        if ![100, 101, 128].contains(&bit) {
+            assert_eq!(bitmap.alloc_near(&device, bit), Some(bit));
+        }
+    }
+    assert_eq!(bitmap.alloc_near(&device, 10), Some(block_bits));
+}
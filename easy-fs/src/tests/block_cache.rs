@@ -0,0 +1,149 @@
+use super::{new_fs, take_warnings};
+use crate::block_cache::BLOCK_CACHE_MANAGER;
+use crate::{
+    block_cache_sync_all, block_cached, get_block_cache, pin_block, sweep_block_cache,
+    try_block_cache_sync_all, try_block_cache_sync_ordered, try_get_block_cache,
+    try_sweep_block_cache, unpin_block, BlockError, BLOCK_SZ,
+};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+#[test]
+fn device_errors_reach_the_caller() {
+    let fs = new_fs(4096);
+    block_cache_sync_all();
+    let block_id = 4000;
+    fs.disk.fail.store(true, Ordering::SeqCst);
+    assert_eq!(
+        try_get_block_cache(block_id, Arc::clone(&fs.device)).err(),
+        Some(BlockError::ReadFailed(block_id))
+    );
+    fs.disk.fail.store(false, Ordering::SeqCst);
+    let cache = try_get_block_cache(block_id, Arc::clone(&fs.device)).unwrap();
+    cache.lock().modify(0, |data: &mut [u8; BLOCK_SZ]| data.fill(0x5a));
+    // 写回失败时块仍是脏的，设备恢复后再写回
+    fs.disk.fail.store(true, Ordering::SeqCst);
+    assert_eq!(try_block_cache_sync_all(), Err(BlockError::WriteFailed(block_id)));
+    fs.disk.fail.store(false, Ordering::SeqCst);
+    assert_eq!(fs.disk.block(block_id), [0; BLOCK_SZ]);
+    assert_eq!(try_block_cache_sync_all(), Ok(()));
+    assert_eq!(fs.disk.block(block_id), [0x5a; BLOCK_SZ]);
+}
+
+#[test]
+fn ordered_sync_writes_blocks_by_rank() {
+    let fs = new_fs(4096);
+    block_cache_sync_all();
+    for block_id in [4000, 4001, 4002] {
+        get_block_cache(block_id, Arc::clone(&fs.device))
+            .lock()
+            .modify(0, |data: &mut [u8; BLOCK_SZ]| data[0] = 1);
+    }
+    fs.disk.written.lock().unwrap().clear();
+    assert_eq!(try_block_cache_sync_ordered(|block_id| usize::MAX - block_id), Ok(()));
+    assert_eq!(*fs.disk.written.lock().unwrap(), [4002, 4001, 4000]);
+}
+
+#[test]
+fn new_inode_reaches_the_disk_before_its_dirent() {
+    let fs = new_fs(4096);
+    fs.root.create("first").unwrap();
+    block_cache_sync_all();
+    fs.disk.written.lock().unwrap().clear();
+    let file = fs.root.create("second").unwrap();
+    let dir_block = fs.disk_inode(&fs.root, |disk_inode| disk_inode.get_block_id(0, &fs.device)) as usize;
+    let written = fs.disk.written.lock().unwrap().clone();
+    let position = |block_id| written.iter().position(|id| *id == block_id).unwrap();
+    assert!(position(file.block_id) < position(dir_block));
+}
+
+#[test]
+fn pinned_blocks_survive_a_full_cache() {
+    let fs = new_fs(4096);
+    let pinned = 3000;
+    let reads_of = |block_id| fs.disk.read.lock().unwrap().iter().filter(|id| **id == block_id).count();
+    get_block_cache(pinned, Arc::clone(&fs.device));
+    // 固定两次，撤销一次后仍然固定
+    pin_block(pinned, &fs.device);
+    pin_block(pinned, &fs.device);
+    unpin_block(pinned, &fs.device);
+    let touch = |range: core::ops::Range<usize>| {
+        for block_id in range {
+            get_block_cache(block_id, Arc::clone(&fs.device));
+        }
+    };
+    touch(3100..3140);
+    assert!(block_cached(pinned, &fs.device));
+    let reads = reads_of(pinned);
+    get_block_cache(pinned, Arc::clone(&fs.device));
+    assert_eq!(reads_of(pinned), reads);
+    unpin_block(pinned, &fs.device);
+    touch(3200..3240);
+    assert!(!block_cached(pinned, &fs.device));
+}
+
+#[test]
+fn the_cache_grows_past_capacity_while_every_block_is_in_use() {
+    let fs = new_fs(4096);
+    block_cache_sync_all();
+    take_warnings();
+    // 持有的块多于缓存容量，都无法替换
+    let held: Vec<_> = (3000..3020)
+        .map(|block_id| get_block_cache(block_id, Arc::clone(&fs.device)))
+        .collect();
+    assert!((3000..3020).all(|block_id| block_cached(block_id, &fs.device)));
+    assert!(take_warnings().iter().any(|warning| warning.contains("over capacity")));
+    // 释放之后，下一次换入收缩回容量以内
+    drop(held);
+    get_block_cache(3100, Arc::clone(&fs.device));
+    let cached = (3000..3020).filter(|block_id| block_cached(*block_id, &fs.device)).count();
+    assert!(cached < 16);
+    assert!(take_warnings().is_empty());
+}
+
+#[test]
+fn the_sweep_drops_blocks_idle_past_the_ttl() {
+    let fs = new_fs(4096);
+    block_cache_sync_all();
+    // 时钟是全局的，从远大于其他测试用到的时间开始
+    let (base, ttl) = (1_000_000, 10);
+    sweep_block_cache(base, ttl);
+    get_block_cache(4000, Arc::clone(&fs.device));
+    get_block_cache(4001, Arc::clone(&fs.device))
+        .lock()
+        .modify(0, |data: &mut [u8; BLOCK_SZ]| data[0] = 1);
+    pin_block(4002, &fs.device);
+    get_block_cache(4002, Arc::clone(&fs.device));
+    let in_use = get_block_cache(4003, Arc::clone(&fs.device));
+    // 还没到期的块都留着
+    assert_eq!(sweep_block_cache(base + ttl / 2, ttl), 0);
+    get_block_cache(4004, Arc::clone(&fs.device));
+    sweep_block_cache(base + ttl, ttl);
+    // 脏块写回后丢弃，固定的、正在使用的和最近访问过的块不动
+    assert!(!block_cached(4000, &fs.device));
+    assert!(!block_cached(4001, &fs.device));
+    assert_eq!(fs.disk.block(4001)[0], 1);
+    assert!(block_cached(4002, &fs.device));
+    assert!(block_cached(4003, &fs.device));
+    assert!(block_cached(4004, &fs.device));
+    drop(in_use);
+    unpin_block(4002, &fs.device);
+}
+
+#[test]
+fn the_sweep_skips_a_held_cache_but_still_moves_the_clock() {
+    let fs = new_fs(4096);
+    block_cache_sync_all();
+    let (base, ttl) = (2_000_000, 10);
+    // 模拟时钟中断打断了持有块缓存锁的代码
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    assert_eq!(try_sweep_block_cache(base, ttl), None);
+    drop(manager);
+    // 之后访问的块按新的时钟计时，不会被当作早已过期
+    get_block_cache(4010, Arc::clone(&fs.device));
+    assert!(try_sweep_block_cache(base + ttl / 2, ttl).is_some());
+    assert!(block_cached(4010, &fs.device));
+    assert_eq!(try_sweep_block_cache(base + ttl, ttl), Some(1));
+    assert!(!block_cached(4010, &fs.device));
+}
@@ -0,0 +1,87 @@
+use super::{new_fs, RamDisk};
+use crate::layout::SuperBlock;
+use crate::{block_cache_sync_all, get_block_cache, BlockDevice, EasyFileSystem, OpenError};
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+#[test]
+fn restoring_a_bitmap_snapshot_releases_later_allocations() {
+    let fs = new_fs(4096);
+    let mut efs = fs.efs.write();
+    let (free_inodes, free_blocks) = (efs.free_inodes(), efs.free_data_blocks());
+    let snapshot = efs.snapshot_bitmaps();
+    let inode_id = efs.try_alloc_inode().unwrap();
+    let blocks: Vec<u32> = (0..3).map(|_| efs.try_alloc_data().unwrap()).collect();
+    assert_eq!(efs.free_inodes(), free_inodes - 1);
+    assert_eq!(efs.free_data_blocks(), free_blocks - 3);
+    efs.restore_bitmaps(&snapshot);
+    assert_eq!(efs.free_inodes(), free_inodes);
+    assert_eq!(efs.free_data_blocks(), free_blocks);
+    // 释放的编号可以再次分配
+    assert_eq!(efs.try_alloc_inode(), Some(inode_id));
+    assert_eq!(efs.try_alloc_data(), Some(blocks[0]));
+}
+
+#[test]
+fn free_counters_match_a_bitmap_scan() {
+    let fs = new_fs(4096);
+    for round in 0..3 {
+        for i in 0..20 {
+            let (file, _) = fs.root.find_or_create(&format!("f{}", i)).unwrap();
+            let len = (i * 7 + round * 13) % 40 * 100;
+            assert_eq!(file.write_at(0, &alloc::vec![1u8; len]), len);
+        }
+        for i in (0..20).step_by(round + 2) {
+            assert_eq!(fs.root.unlink(&format!("f{}", i)), 0);
+        }
+        let efs = fs.efs.read();
+        assert_eq!((efs.free_inodes(), efs.free_data_blocks()), efs.scan_free());
+    }
+    // 超级块中的计数过期（如未正常卸载）时，挂载会按位图重新计数
+    let expected = fs.efs.read().scan_free();
+    get_block_cache(0, Arc::clone(&fs.device))
+        .lock()
+        .modify(0, |super_block: &mut SuperBlock| {
+            super_block.free_inodes = 1;
+            super_block.free_data_blocks = 2;
+        });
+    let reopened = EasyFileSystem::open(Arc::clone(&fs.device));
+    let efs = reopened.read();
+    assert_eq!((efs.free_inodes(), efs.free_data_blocks()), expected);
+}
+
+#[test]
+fn opening_an_image_larger_than_the_device_fails() {
+    let fs = new_fs(4096);
+    block_cache_sync_all();
+    // 镜像被截断到一半大小
+    let small = RamDisk::new(2048);
+    for block_id in 0..2048 {
+        small.try_write_block(block_id, &fs.disk.block(block_id)).unwrap();
+    }
+    let result = EasyFileSystem::try_open(small);
+    assert!(matches!(
+        result,
+        Err(OpenError::TooLarge { total_blocks: 4096, device_blocks: 2048 })
+    ));
+    assert!(matches!(EasyFileSystem::try_open(RamDisk::new(16)), Err(OpenError::BadMagic)));
+}
+
+#[test]
+fn files_grow_exactly_up_to_the_reported_maximum() {
+    let fs = new_fs(4096);
+    let max = fs.efs.read().max_file_size();
+    assert_eq!(fs.efs.read().stat_fs().max_file_size, max);
+    let file = fs.root.create("file").unwrap();
+    // 最后一个字节需要二级索引，写得进去；再往后一个字节都写不进
+    assert_eq!(file.write_at(max - 1, &[1]), 1);
+    assert_eq!(file.size() as usize, max);
+    assert_eq!(file.write_at(max, &[1]), 0);
+    assert_eq!(file.write_at(max - 1, &[2, 2]), 1);
+    assert_eq!(file.size() as usize, max);
+    assert!(!file.truncate(max as u32 + 1));
+    assert!(!file.allocate(max, 1));
+    assert!(file.truncate(0));
+    assert!(file.truncate(max as u32));
+}
@@ -0,0 +1,55 @@
+use super::new_fs;
+use crate::layout::{DirEntry, DirEntryType, HOLE_BLOCK_ID, INODE_DIRECT_COUNT, NAME_LENGTH_LIMIT};
+use crate::{get_block_cache, BLOCK_SZ};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+#[test]
+fn iter_blocks_lists_data_blocks_in_file_order() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    // 超过直接索引的块数，一级索引中的块也要按顺序列出
+    let blocks = INODE_DIRECT_COUNT + 8;
+    for k in 0..blocks {
+        assert_eq!(file.write_at(k * BLOCK_SZ, &[k as u8 + 1; BLOCK_SZ]), BLOCK_SZ);
+    }
+    let ids: Vec<u32> = fs.disk_inode(&file, |disk_inode| disk_inode.iter_blocks(&fs.device).collect());
+    assert_eq!(ids.len(), blocks);
+    for (k, block_id) in ids.iter().enumerate() {
+        let first = get_block_cache(*block_id as usize, Arc::clone(&fs.device))
+            .lock()
+            .read(0, |data: &[u8; BLOCK_SZ]| data[0]);
+        assert_eq!(first, k as u8 + 1);
+    }
+}
+
+#[test]
+fn writing_past_the_end_leaves_holes() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("sparse").unwrap();
+    let free = fs.efs.read().free_data_blocks();
+    assert_eq!(file.write_at(10 * BLOCK_SZ, b"tail"), 4);
+    assert_eq!(file.size() as usize, 10 * BLOCK_SZ + 4);
+    // 只有最后一块真正分配
+    assert_eq!(fs.efs.read().free_data_blocks(), free - 1);
+    let ids: Vec<u32> = fs.disk_inode(&file, |disk_inode| disk_inode.iter_blocks(&fs.device).collect());
+    assert!(ids[..10].iter().all(|block_id| *block_id == HOLE_BLOCK_ID));
+    assert_ne!(ids[10], HOLE_BLOCK_ID);
+    let mut buf = [0xffu8; 2 * BLOCK_SZ];
+    assert_eq!(file.read_at(3 * BLOCK_SZ, &mut buf), buf.len());
+    assert!(buf.iter().all(|byte| *byte == 0));
+}
+
+#[test]
+fn corrupted_dirent_names_stay_inside_their_field() {
+    let mut dirent = DirEntry::new("x", 7, DirEntryType::File);
+    // 名字字段填满，没有结尾的 0，紧随其后的类型字节不能算进名字
+    dirent.as_bytes_mut()[..NAME_LENGTH_LIMIT + 1].fill(b'a');
+    assert_eq!(dirent.name().len(), NAME_LENGTH_LIMIT + 1);
+    assert!(dirent.name().bytes().all(|byte| byte == b'a'));
+    assert_eq!(dirent.entry_type(), DirEntryType::File);
+    assert_eq!(dirent.inode_id(), 7);
+    // 非法的 UTF-8 之前的部分仍可读出
+    dirent.as_bytes_mut()[2] = 0xff;
+    assert_eq!(dirent.name(), "aa");
+}
@@ -0,0 +1,142 @@
+//! Host tests of easy-fs over a RAM-backed block device
+extern crate std;
+
+mod bitmap;
+mod block_cache;
+mod efs;
+mod layout;
+mod vfs;
+
+use crate::{get_block_cache, BlockDevice, BlockError, DiskInode, EasyFileSystem, Inode, RwLock, BLOCK_SZ};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// A block device keeping its blocks in memory and counting its I/O
+pub struct RamDisk {
+    blocks: Mutex<Vec<[u8; BLOCK_SZ]>>,
+    /// Number of blocks read so far
+    pub reads: AtomicUsize,
+    /// Ids of the blocks read so far, in order
+    pub read: Mutex<Vec<usize>>,
+    /// Number of blocks written so far
+    pub writes: AtomicUsize,
+    /// Ids of the blocks written so far, in order
+    pub written: Mutex<Vec<usize>>,
+    /// Fail every read and write while set
+    pub fail: AtomicBool,
+}
+
+impl RamDisk {
+    /// A zeroed device of `blocks` blocks
+    pub fn new(blocks: usize) -> Arc<Self> {
+        Arc::new(Self {
+            blocks: Mutex::new(vec![[0u8; BLOCK_SZ]; blocks]),
+            reads: AtomicUsize::new(0),
+            read: Mutex::new(Vec::new()),
+            writes: AtomicUsize::new(0),
+            written: Mutex::new(Vec::new()),
+            fail: AtomicBool::new(false),
+        })
+    }
+    /// Content of block `block_id` on the device, bypassing the block cache
+    pub fn block(&self, block_id: usize) -> [u8; BLOCK_SZ] {
+        self.blocks.lock().unwrap()[block_id]
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        if self.fail.load(Ordering::SeqCst) {
+            return Err(BlockError::ReadFailed(block_id));
+        }
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.read.lock().unwrap().push(block_id);
+        buf.copy_from_slice(&self.blocks.lock().unwrap()[block_id]);
+        Ok(())
+    }
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
+        if self.fail.load(Ordering::SeqCst) {
+            return Err(BlockError::WriteFailed(block_id));
+        }
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        self.written.lock().unwrap().push(block_id);
+        self.blocks.lock().unwrap()[block_id].copy_from_slice(buf);
+        Ok(())
+    }
+    fn num_blocks(&self) -> usize {
+        self.blocks.lock().unwrap().len()
+    }
+}
+
+/// Warnings easy-fs logged since the last [`take_warnings`]
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct WarningLog;
+
+impl log::Log for WarningLog {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            WARNINGS.lock().unwrap().push(record.args().to_string());
+        }
+    }
+    fn flush(&self) {}
+}
+
+/// Take the warnings logged so far, starting to record them on first call
+pub fn take_warnings() -> Vec<String> {
+    static LOGGER: WarningLog = WarningLog;
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+    core::mem::take(&mut *WARNINGS.lock().unwrap())
+}
+
+/// 块缓存、目录索引等都是全局的，测试之间串行执行以免互相换出缓存块
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// A freshly created filesystem and its device, held exclusively by one test
+pub struct TestFs {
+    pub disk: Arc<RamDisk>,
+    pub device: Arc<dyn BlockDevice>,
+    pub efs: Arc<RwLock<EasyFileSystem>>,
+    pub root: Arc<Inode>,
+    _serial: MutexGuard<'static, ()>,
+}
+
+/// Hold the block cache exclusively for the calling test
+pub fn serial() -> MutexGuard<'static, ()> {
+    // 前一个测试失败不影响之后的测试
+    SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Create a filesystem of `blocks` blocks on a new RAM disk
+pub fn new_fs(blocks: usize) -> TestFs {
+    let serial = serial();
+    let disk = RamDisk::new(blocks);
+    let device: Arc<dyn BlockDevice> = disk.clone();
+    let efs = EasyFileSystem::create(Arc::clone(&device), blocks as u32, 1);
+    let root = Arc::new(EasyFileSystem::root_inode(&efs));
+    TestFs {
+        disk,
+        device,
+        efs,
+        root,
+        _serial: serial,
+    }
+}
+
+impl TestFs {
+    /// Call `f` over the disk inode behind `inode`
+    pub fn disk_inode<V>(&self, inode: &Inode, f: impl FnOnce(&DiskInode) -> V) -> V {
+        get_block_cache(inode.block_id, Arc::clone(&self.device))
+            .lock()
+            .read(inode.block_offset, f)
+    }
+}
@@ -0,0 +1,598 @@
+use super::new_fs;
+use crate::layout::{DirEntry, DIRENT_SZ, INODE_DIRECT_COUNT};
+use crate::{
+    block_cache_sync_all, block_cached, fail_alloc_after, DirEntryType, RemoveError, BLOCK_SZ,
+};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+#[test]
+fn truncate_frees_data_and_index_blocks() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    let free = fs.efs.read().free_data_blocks();
+    // 用到二级索引，缩小后一级、二级索引块都应回收
+    let blocks = 200;
+    for k in 0..blocks {
+        assert_eq!(file.write_at(k * BLOCK_SZ, &[0xaa; BLOCK_SZ]), BLOCK_SZ);
+    }
+    assert!(fs.efs.read().free_data_blocks() < free - blocks);
+    assert!(file.truncate(BLOCK_SZ as u32 + 10));
+    assert_eq!(file.size() as usize, BLOCK_SZ + 10);
+    assert_eq!(fs.efs.read().free_data_blocks(), free - 2);
+    // 截掉的尾部重新长出来时读出全 0
+    assert!(file.truncate(3 * BLOCK_SZ as u32));
+    let mut buf = [0xffu8; BLOCK_SZ];
+    assert_eq!(file.read_at(BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert!(buf[..10].iter().all(|byte| *byte == 0xaa));
+    assert!(buf[10..].iter().all(|byte| *byte == 0));
+    assert_eq!(fs.efs.read().free_data_blocks(), free - 2);
+    assert!(file.truncate(0));
+    assert_eq!(fs.efs.read().free_data_blocks(), free);
+}
+
+#[test]
+fn names_are_not_looked_up_in_regular_files() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    // 文件内容恰好是一个目录项，也不能当作目录解析
+    let dirent = DirEntry::new("inner", 0, DirEntryType::File);
+    assert_eq!(file.write_at(0, dirent.as_bytes()), DIRENT_SZ);
+    assert!(fs.root.is_dir());
+    assert!(!file.is_dir());
+    assert!(file.find("inner").is_none());
+    assert!(file.create("new").is_none());
+    assert!(file.find_or_create("new").is_none());
+    assert_eq!(file.size() as usize, DIRENT_SZ);
+}
+
+#[test]
+fn dirents_carry_the_entry_type() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    fs.root.create_dir("dir").unwrap();
+    let entries = fs.root.ls_with_type();
+    assert!(entries.contains(&(String::from("file"), DirEntryType::File)));
+    assert!(entries.contains(&(String::from("dir"), DirEntryType::Directory)));
+    // 类型字节不认识的目录项读出为 Unknown
+    let index = fs.root.dirents().len();
+    let (_, inode_id, _) = fs.root.read_dirent(0).unwrap();
+    let unknown = DirEntry::new("unknown", inode_id, DirEntryType::Unknown);
+    assert_eq!(fs.root.write_at(index * DIRENT_SZ, unknown.as_bytes()), DIRENT_SZ);
+    assert_eq!(
+        fs.root.read_dirent(index),
+        Some((String::from("unknown"), inode_id, DirEntryType::Unknown))
+    );
+    assert!(file.read_dirent(0).is_none());
+}
+
+#[test]
+fn remove_all_frees_the_whole_tree() {
+    let fs = new_fs(4096);
+    let kept = fs.root.create("kept").unwrap();
+    let (free_inodes, free_blocks) = {
+        let efs = fs.efs.read();
+        (efs.free_inodes(), efs.free_data_blocks())
+    };
+    let dir = fs.root.create_dir("dir").unwrap();
+    let file = dir.create("file").unwrap();
+    assert_eq!(file.write_at(0, &[1; 3 * BLOCK_SZ]), 3 * BLOCK_SZ);
+    let sub = dir.create_dir("sub").unwrap();
+    sub.create("inner").unwrap();
+    // 树外的文件在树中有硬链接，删除树后仍然保留
+    assert_eq!(sub.link_inode(&kept, "kept_link"), 0);
+    assert_eq!(fs.root.rmdir("dir"), Err(RemoveError::NotEmpty));
+    assert_eq!(dir.rmdir("file"), Err(RemoveError::NotDir));
+    assert_eq!(fs.root.remove_all(".."), Err(RemoveError::Invalid));
+    assert_eq!(fs.root.remove_all("dir"), Ok(()));
+    assert!(fs.root.find("dir").is_none());
+    assert_eq!(fs.root.remove_all("dir"), Err(RemoveError::NotFound));
+    let efs = fs.efs.read();
+    assert_eq!(efs.free_inodes(), free_inodes);
+    assert_eq!(efs.free_data_blocks(), free_blocks);
+    drop(efs);
+    assert!(fs.root.find("kept").is_some());
+    assert_eq!(kept.write_at(0, b"still here"), 10);
+}
+
+#[test]
+fn create_on_a_full_disk_leaves_nothing_allocated() {
+    let fs = new_fs(2048);
+    let big = fs.root.create("big").unwrap();
+    // 占满所有数据块
+    let mut offset = 0;
+    while big.write_at(offset, &[7; BLOCK_SZ]) == BLOCK_SZ {
+        offset += BLOCK_SZ;
+    }
+    assert_eq!(fs.efs.read().free_data_blocks(), 0);
+    let free_inodes = fs.efs.read().free_inodes();
+    // 新目录放不下 . 和 ..
+    assert!(fs.root.create_dir("dir").is_none());
+    assert!(fs.root.find("dir").is_none());
+    assert_eq!(fs.efs.read().free_inodes(), free_inodes);
+    // 根目录的第一块写满目录项后，再创建就需要新的数据块
+    let mut created = 1;
+    while fs.root.create(&format!("f{}", created)).is_some() {
+        created += 1;
+    }
+    assert_eq!(created, BLOCK_SZ / DIRENT_SZ);
+    assert_eq!(fs.efs.read().free_inodes(), free_inodes - (created - 1));
+    assert!(fs.root.find(&format!("f{}", created)).is_none());
+    assert!(fs.root.find_or_create("another").is_none());
+    assert_eq!(fs.efs.read().free_inodes(), free_inodes - (created - 1));
+}
+
+#[test]
+fn random_reads_reuse_the_cached_index_block() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    // 一级索引映射其后的 100 个数据块，远多于块缓存的容量
+    let blocks = INODE_DIRECT_COUNT + 100;
+    for k in 0..blocks {
+        assert_eq!(file.write_at(k * BLOCK_SZ, &[k as u8; BLOCK_SZ]), BLOCK_SZ);
+    }
+    let indirect1 = fs.disk_inode(&file, |disk_inode| disk_inode.indirect1) as usize;
+    let index_reads = || fs.disk.read.lock().unwrap().iter().filter(|id| **id == indirect1).count();
+    let before = index_reads();
+    let mut seed = 12345usize;
+    for _ in 0..200 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345) % (1 << 31);
+        let k = INODE_DIRECT_COUNT + seed % 100;
+        let mut byte = [0u8];
+        assert_eq!(file.read_at(k * BLOCK_SZ + 7, &mut byte), 1);
+        assert_eq!(byte[0], k as u8);
+    }
+    assert!(index_reads() - before <= 1);
+    // 截断后重新写入，缓存的旧映射作废
+    assert!(file.truncate((INODE_DIRECT_COUNT * BLOCK_SZ) as u32));
+    for k in INODE_DIRECT_COUNT..blocks {
+        assert_eq!(file.write_at(k * BLOCK_SZ, &[!(k as u8); BLOCK_SZ]), BLOCK_SZ);
+    }
+    for k in INODE_DIRECT_COUNT..blocks {
+        let mut byte = [0u8];
+        assert_eq!(file.read_at(k * BLOCK_SZ, &mut byte), 1);
+        assert_eq!(byte[0], !(k as u8));
+    }
+}
+
+#[test]
+fn reflinked_files_diverge_on_write() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    for k in 0..3 {
+        assert_eq!(file.write_at(k * BLOCK_SZ, &[k as u8; BLOCK_SZ]), BLOCK_SZ);
+    }
+    let copy = fs.root.reflink("file", "copy").unwrap();
+    assert_eq!(copy.size(), file.size());
+    assert_eq!(copy.write_at(BLOCK_SZ, &[0xff; BLOCK_SZ]), BLOCK_SZ);
+    let blocks_of = |inode| {
+        fs.disk_inode(inode, |disk_inode| disk_inode.iter_blocks(&fs.device).collect::<Vec<u32>>())
+    };
+    let (blocks, copied) = (blocks_of(&file), blocks_of(&copy));
+    // 只有被写的块分开了
+    assert_eq!((blocks[0], blocks[2]), (copied[0], copied[2]));
+    assert_ne!(blocks[1], copied[1]);
+    let efs = fs.efs.read();
+    assert_eq!(efs.data_refs(blocks[0]), 2);
+    assert_eq!(efs.data_refs(blocks[1]), 1);
+    drop(efs);
+    let mut buf = [0u8; BLOCK_SZ];
+    assert_eq!(file.read_at(BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert!(buf.iter().all(|byte| *byte == 1));
+    assert_eq!(copy.read_at(BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert!(buf.iter().all(|byte| *byte == 0xff));
+}
+
+#[test]
+fn reflink_rolls_back_when_the_refcount_table_cannot_grow() {
+    let fs = new_fs(2048);
+    let file = fs.root.create("file").unwrap();
+    for k in 0..3 {
+        assert_eq!(file.write_at(k * BLOCK_SZ, &[k as u8; BLOCK_SZ]), BLOCK_SZ);
+    }
+    // 占满所有数据块，引用计数表分配不到第一个块
+    let big = fs.root.create("big").unwrap();
+    let mut offset = 0;
+    while big.write_at(offset, &[7; BLOCK_SZ]) == BLOCK_SZ {
+        offset += BLOCK_SZ;
+    }
+    assert_eq!(fs.efs.read().free_data_blocks(), 0);
+    let free_inodes = fs.efs.read().free_inodes();
+    assert!(fs.root.reflink("file", "copy").is_none());
+    assert!(fs.root.find("copy").is_none());
+    assert_eq!(fs.efs.read().free_inodes(), free_inodes);
+    assert_eq!(fs.efs.read().free_data_blocks(), 0);
+    let blocks = fs.disk_inode(&file, |disk_inode| disk_inode.iter_blocks(&fs.device).collect::<Vec<u32>>());
+    assert!(blocks.iter().all(|block_id| fs.efs.read().data_refs(*block_id) == 1));
+    let mut buf = [0u8; BLOCK_SZ];
+    assert_eq!(file.read_at(2 * BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert!(buf.iter().all(|byte| *byte == 2));
+    // 腾出空间后可以再次 reflink
+    assert_eq!(fs.root.unlink("big"), 0);
+    assert!(fs.root.reflink("file", "copy").is_some());
+}
+
+#[test]
+fn drop_cache_evicts_the_range_after_writing_it_back() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    for k in 0..4 {
+        assert_eq!(file.write_at(k * BLOCK_SZ, &[k as u8 + 1; BLOCK_SZ]), BLOCK_SZ);
+    }
+    let blocks = fs.disk_inode(&file, |disk_inode| {
+        disk_inode.iter_blocks(&fs.device).collect::<Vec<u32>>()
+    });
+    assert!(blocks.iter().all(|block_id| block_cached(*block_id as usize, &fs.device)));
+    // 范围的末尾落在第三块中间，第三块也被丢弃
+    assert_eq!(file.drop_cache(0, 2 * BLOCK_SZ + 1), Ok(()));
+    for (k, block_id) in blocks.iter().enumerate() {
+        let block_id = *block_id as usize;
+        assert_eq!(block_cached(block_id, &fs.device), k == 3);
+        if k < 3 {
+            assert_eq!(fs.disk.block(block_id), [k as u8 + 1; BLOCK_SZ]);
+        }
+    }
+    let mut buf = [0u8; BLOCK_SZ];
+    assert_eq!(file.read_at(BLOCK_SZ, &mut buf), BLOCK_SZ);
+    assert_eq!(buf, [2; BLOCK_SZ]);
+}
+
+#[test]
+fn large_directories_are_searched_through_the_index() {
+    let fs = new_fs(4096);
+    let dir = fs.root.create_dir("dir").unwrap();
+    // 目录项占用的块多于块缓存的容量，逐项扫描必然重新读盘
+    let count = 400;
+    for i in 0..count {
+        dir.create(&format!("f{}", i)).unwrap();
+    }
+    let dirent_blocks = fs.disk_inode(&dir, |disk_inode| {
+        disk_inode.iter_blocks(&fs.device).collect::<Vec<u32>>()
+    });
+    assert!(dirent_blocks.len() > 16);
+    let dirent_reads = || {
+        let read = fs.disk.read.lock().unwrap();
+        read.iter().filter(|id| dirent_blocks.contains(&(**id as u32))).count()
+    };
+    assert!(dir.find("f0").is_some());
+    let before = dirent_reads();
+    for i in 0..count {
+        assert!(dir.find(&format!("f{}", i)).is_some());
+    }
+    assert!(dir.find("missing").is_none());
+    assert_eq!(dirent_reads(), before);
+    // 删除和新建同步更新索引
+    assert_eq!(dir.unlink("f7"), 0);
+    assert!(dir.find("f7").is_none());
+    dir.create("new").unwrap();
+    assert!(dir.find("new").is_some());
+    assert!(dir.find(&format!("f{}", count - 1)).is_some());
+}
+
+#[test]
+fn a_write_spanning_private_and_shared_blocks_copies_only_the_shared_one() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    assert_eq!(file.write_at(0, &[1; 3 * BLOCK_SZ]), 3 * BLOCK_SZ);
+    let copy = fs.root.reflink("file", "copy").unwrap();
+    let blocks_of = |inode| {
+        fs.disk_inode(inode, |disk_inode| disk_inode.iter_blocks(&fs.device).collect::<Vec<u32>>())
+    };
+    assert_eq!(copy.write_at(BLOCK_SZ, &[2; 8]), 8);
+    let private = blocks_of(&copy)[1];
+    let free = fs.efs.read().free_data_blocks();
+    // 跨越已私有的第 1 块和仍共享的第 2 块
+    assert_eq!(copy.write_at(2 * BLOCK_SZ - 4, &[3; 8]), 8);
+    let (blocks, copied) = (blocks_of(&file), blocks_of(&copy));
+    assert_eq!(copied[1], private);
+    assert_ne!(copied[2], blocks[2]);
+    assert_eq!(copied[0], blocks[0]);
+    assert_eq!(fs.efs.read().free_data_blocks(), free - 1);
+    let efs = fs.efs.read();
+    assert_eq!(efs.data_refs(blocks[2]), 1);
+    assert_eq!(efs.data_refs(copied[2]), 1);
+    assert_eq!(efs.data_refs(blocks[0]), 2);
+    drop(efs);
+    let mut buf = [0u8; 3 * BLOCK_SZ];
+    assert_eq!(copy.read_at(0, &mut buf), 3 * BLOCK_SZ);
+    assert!(buf[BLOCK_SZ..BLOCK_SZ + 8].iter().all(|byte| *byte == 2));
+    assert!(buf[2 * BLOCK_SZ - 4..2 * BLOCK_SZ + 4].iter().all(|byte| *byte == 3));
+    assert!(buf[2 * BLOCK_SZ + 4..].iter().all(|byte| *byte == 1));
+    assert_eq!(file.read_at(0, &mut buf), 3 * BLOCK_SZ);
+    assert!(buf.iter().all(|byte| *byte == 1));
+}
+
+#[test]
+fn growing_past_a_full_disk_is_a_short_write() {
+    let fs = new_fs(2048);
+    let file = fs.root.create("file").unwrap();
+    let big = fs.root.create("big").unwrap();
+    // 只留下几个空闲块
+    let mut offset = 0;
+    while fs.efs.read().free_data_blocks() > 5 {
+        assert_eq!(big.write_at(offset, &[7; BLOCK_SZ]), BLOCK_SZ);
+        offset += BLOCK_SZ;
+    }
+    let free = fs.efs.read().free_data_blocks();
+    let data = [9u8; 10 * BLOCK_SZ];
+    let written = file.write_at(0, &data);
+    // 前几块都是直接索引，不需要索引块
+    assert_eq!(written, free * BLOCK_SZ);
+    assert_eq!(file.size() as usize, written);
+    let mut buf = [0u8; 10 * BLOCK_SZ];
+    assert_eq!(file.read_at(0, &mut buf), written);
+    assert!(buf[..written].iter().all(|byte| *byte == 9));
+    // 删除后空闲块全部回来，没有泄漏
+    assert_eq!(fs.root.unlink("file"), 0);
+    assert_eq!(fs.efs.read().free_data_blocks(), free);
+    assert_eq!(fs.efs.read().scan_free().1, free);
+}
+
+#[test]
+fn combined_appends_reach_the_disk_a_block_at_a_time() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("log").unwrap();
+    file.set_write_combining(true);
+    block_cache_sync_all();
+    let writes = fs.disk.writes.load(Ordering::SeqCst);
+    // 每次追加后都同步，不合并时每次都要写数据块和 inode
+    let appends = 1000;
+    for k in 0..appends {
+        let record = [k as u8; 10];
+        assert_eq!(file.write_at(k * 10, &record), 10);
+        block_cache_sync_all();
+        // 还在缓冲中的数据也能读到
+        let mut buf = [0u8; 10];
+        assert_eq!(file.read_at(k * 10, &mut buf), 10);
+        assert_eq!(buf, record);
+    }
+    assert_eq!(file.size() as usize, appends * 10);
+    assert!(file.flush_appends());
+    block_cache_sync_all();
+    assert!(fs.disk.writes.load(Ordering::SeqCst) - writes < appends / 10);
+    // 刷出后其他句柄看到全部数据
+    let other = fs.root.find("log").unwrap();
+    let mut buf = vec![0u8; appends * 10];
+    assert_eq!(other.read_at(0, &mut buf), appends * 10);
+    assert!(buf.chunks(10).enumerate().all(|(k, record)| record.iter().all(|byte| *byte == k as u8)));
+}
+
+#[test]
+fn allocate_backs_the_range_with_zeroed_blocks() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    assert_eq!(file.write_at(0, &[5; 10]), 10);
+    let free = fs.efs.read().free_data_blocks();
+    // 从已有数据的块开始，已分配的块不动
+    assert!(file.allocate(4, 8 * BLOCK_SZ));
+    assert_eq!(file.size() as usize, 4 + 8 * BLOCK_SZ);
+    assert_eq!(fs.efs.read().free_data_blocks(), free - 8);
+    let mut buf = vec![0xffu8; 9 * BLOCK_SZ];
+    assert_eq!(file.read_at(0, &mut buf), 4 + 8 * BLOCK_SZ);
+    assert!(buf[..10].iter().all(|byte| *byte == 5));
+    assert!(buf[10..4 + 8 * BLOCK_SZ].iter().all(|byte| *byte == 0));
+    // 之后写入范围内不再分配块
+    assert_eq!(file.write_at(3 * BLOCK_SZ, &[6; BLOCK_SZ]), BLOCK_SZ);
+    assert_eq!(fs.efs.read().free_data_blocks(), free - 8);
+    // 范围在文件内时大小不变
+    assert!(file.allocate(0, BLOCK_SZ));
+    assert_eq!(file.size() as usize, 4 + 8 * BLOCK_SZ);
+}
+
+#[test]
+fn allocate_on_a_full_disk_changes_nothing() {
+    let fs = new_fs(2048);
+    let file = fs.root.create("file").unwrap();
+    let big = fs.root.create("big").unwrap();
+    let mut offset = 0;
+    while fs.efs.read().free_data_blocks() > 5 {
+        assert_eq!(big.write_at(offset, &[7; BLOCK_SZ]), BLOCK_SZ);
+        offset += BLOCK_SZ;
+    }
+    assert_eq!(file.write_at(0, &[1; 10]), 10);
+    let free = fs.efs.read().free_data_blocks();
+    // 留出空洞再分配，撤销时空洞和大小都要恢复
+    assert!(!file.allocate(2 * BLOCK_SZ, 10 * BLOCK_SZ));
+    assert_eq!(file.size(), 10);
+    assert_eq!(fs.efs.read().free_data_blocks(), free);
+    assert_eq!(fs.efs.read().scan_free().1, free);
+    assert!(file.allocate(BLOCK_SZ, free * BLOCK_SZ));
+    assert_eq!(fs.efs.read().free_data_blocks(), 0);
+}
+
+#[test]
+fn stale_handles_fail_once_the_inode_is_reused() {
+    let fs = new_fs(4096);
+    let old = fs.root.create("old").unwrap();
+    assert_eq!(old.write_at(0, &[1; 10]), 10);
+    let old_id = fs.efs.read().get_disk_inode_id(old.block_id as u32, old.block_offset);
+    assert!(!old.is_stale());
+    assert_eq!(fs.root.unlink("old"), 0);
+    // 回收的 inode 被新文件重用
+    let new = fs.root.create("new").unwrap();
+    let new_id = fs.efs.read().get_disk_inode_id(new.block_id as u32, new.block_offset);
+    assert_eq!(new_id, old_id);
+    assert_eq!(new.write_at(0, &[2; 10]), 10);
+    assert!(old.is_stale());
+    assert!(!new.is_stale());
+    let mut buf = [0u8; 10];
+    assert_eq!(old.read_at(0, &mut buf), 0);
+    assert_eq!(old.write_at(0, &[3; 10]), 0);
+    assert!(!old.truncate(0));
+    assert!(!old.allocate(0, BLOCK_SZ));
+    old.clear();
+    // 新文件不受旧句柄的操作影响
+    assert_eq!(new.size(), 10);
+    assert_eq!(new.read_at(0, &mut buf), 10);
+    assert_eq!(buf, [2; 10]);
+}
+
+#[test]
+fn disk_usage_counts_shared_blocks_once() {
+    let fs = new_fs(4096);
+    let dir = fs.root.create_dir("dir").unwrap();
+    let file = dir.create("file").unwrap();
+    assert_eq!(file.write_at(0, &[1; 3 * BLOCK_SZ]), 3 * BLOCK_SZ);
+    let sub = dir.create_dir("sub").unwrap();
+    let other = sub.create("other").unwrap();
+    assert_eq!(other.write_at(0, &[2; 2 * BLOCK_SZ]), 2 * BLOCK_SZ);
+    // 空洞不占块
+    let sparse = sub.create("sparse").unwrap();
+    assert_eq!(sparse.write_at(5 * BLOCK_SZ, &[3; BLOCK_SZ]), BLOCK_SZ);
+    // 硬链接和 reflink 都不重复计算
+    assert_eq!(sub.link_inode(&file, "link"), 0);
+    dir.reflink("file", "copy").unwrap();
+    // 两个目录各一块，加上 3 + 2 + 1 个数据块
+    assert_eq!(dir.disk_usage(), (2 + 3 + 2 + 1) as u64 * BLOCK_SZ as u64);
+    assert_eq!(sub.disk_usage(), (1 + 3 + 2 + 1) as u64 * BLOCK_SZ as u64);
+    assert_eq!(file.disk_usage(), 3 * BLOCK_SZ as u64);
+    // 写入后 reflink 的副本分出自己的块
+    let copy = dir.find("copy").unwrap();
+    assert_eq!(copy.write_at(0, &[4; BLOCK_SZ]), BLOCK_SZ);
+    assert_eq!(dir.disk_usage(), (2 + 3 + 2 + 1 + 1) as u64 * BLOCK_SZ as u64);
+}
+
+#[test]
+fn rename_over_a_file_frees_the_replaced_one() {
+    let fs = new_fs(4096);
+    let dir = fs.root.create_dir("dir").unwrap();
+    let (free_inodes, free_blocks) = {
+        let efs = fs.efs.read();
+        (efs.free_inodes(), efs.free_data_blocks())
+    };
+    let file = fs.root.create("file").unwrap();
+    assert_eq!(file.write_at(0, &[1; BLOCK_SZ]), BLOCK_SZ);
+    let target = dir.create("target").unwrap();
+    assert_eq!(target.write_at(0, &[2; 3 * BLOCK_SZ]), 3 * BLOCK_SZ);
+    let target_id = fs.efs.read().get_disk_inode_id(target.block_id as u32, target.block_offset);
+    assert_eq!(fs.root.rename("file", &dir, "target"), 0);
+    // 被替换的文件的 inode 和数据块都回收了，只剩移过来的文件
+    let efs = fs.efs.read();
+    assert_eq!(efs.free_inodes(), free_inodes - 1);
+    assert_eq!(efs.free_data_blocks(), free_blocks - 1);
+    assert_eq!(efs.scan_free(), (free_inodes - 1, free_blocks - 1));
+    drop(efs);
+    // 回收的 inode 可以重新分配
+    let new = fs.root.create("new").unwrap();
+    assert_eq!(fs.efs.read().get_disk_inode_id(new.block_id as u32, new.block_offset), target_id);
+    assert!(fs.root.find("file").is_none());
+    let moved = dir.find("target").unwrap();
+    let mut buf = [0u8; 2 * BLOCK_SZ];
+    assert_eq!(moved.read_at(0, &mut buf), BLOCK_SZ);
+    assert!(buf[..BLOCK_SZ].iter().all(|byte| *byte == 1));
+    // 被硬链接的目标只去掉一个链接
+    assert_eq!(fs.root.unlink("new"), 0);
+    let kept = fs.root.create("kept").unwrap();
+    assert_eq!(kept.write_at(0, &[3; BLOCK_SZ]), BLOCK_SZ);
+    assert_eq!(dir.link_inode(&kept, "alias"), 0);
+    fs.root.create("other").unwrap();
+    assert_eq!(fs.root.rename("other", &dir, "alias"), 0);
+    assert_eq!(kept.read_at(0, &mut buf), BLOCK_SZ);
+    assert!(buf[..BLOCK_SZ].iter().all(|byte| *byte == 3));
+}
+
+#[test]
+fn interleaved_growth_keeps_each_file_contiguous() {
+    let fs = new_fs(4096);
+    let a = fs.root.create("a").unwrap();
+    let b = fs.root.create("b").unwrap();
+    // 两个文件交替追加，按首个空闲位分配时块会交错
+    let blocks = 40;
+    for k in 0..blocks {
+        assert_eq!(a.write_at(k * BLOCK_SZ, &[1; BLOCK_SZ]), BLOCK_SZ);
+        assert_eq!(b.write_at(k * BLOCK_SZ, &[2; BLOCK_SZ]), BLOCK_SZ);
+    }
+    for file in [&a, &b] {
+        let ids: Vec<u32> =
+            fs.disk_inode(file, |disk_inode| disk_inode.iter_blocks(&fs.device).collect());
+        assert_eq!(ids.len(), blocks);
+        // 只在换到一级索引时可能断开一次
+        let breaks = ids.windows(2).filter(|pair| pair[1] != pair[0] + 1).count();
+        assert!(breaks <= 2, "{:?}", ids);
+    }
+}
+
+#[test]
+fn a_dirent_snapshot_survives_unlinking_an_entry_already_read() {
+    let fs = new_fs(4096);
+    let names: Vec<String> = (0..8).map(|i| format!("f{}", i)).collect();
+    for name in &names {
+        fs.root.create(name).unwrap();
+    }
+    // getdents 读到一半时删除一个已读过的目录项
+    let snapshot = fs.root.dirents();
+    let mut seen: Vec<String> = snapshot[..4].iter().map(|(name, _, _)| name.clone()).collect();
+    assert_eq!(fs.root.unlink(&seen[1]), 0);
+    // 最后一项被移到空位，按位置继续读会漏掉它
+    let (moved, _, _) = fs.root.read_dirent(1).unwrap();
+    assert_eq!(moved, snapshot[7].0);
+    assert!(fs.root.dirents()[4..].iter().all(|(name, _, _)| *name != moved));
+    // 快照中的剩余项每个恰好出现一次
+    seen.extend(snapshot[4..].iter().map(|(name, _, _)| name.clone()));
+    let removed = seen.remove(1);
+    seen.sort();
+    assert_eq!(seen, names.iter().filter(|name| **name != removed).cloned().collect::<Vec<_>>());
+}
+
+#[test]
+fn create_dir_rolls_back_when_any_allocation_fails() {
+    let fs = new_fs(4096);
+    // 父目录的目录项恰好填满整块，新目录项还要再分配一块
+    let mut i = 0;
+    while fs.root.dirents().len() * DIRENT_SZ % BLOCK_SZ != 0 {
+        fs.root.create(&format!("f{}", i)).unwrap();
+        i += 1;
+    }
+    let state = || {
+        let efs = fs.efs.read();
+        let snapshot = efs.snapshot_bitmaps();
+        (snapshot.inode, snapshot.data, efs.free_inodes(), efs.free_data_blocks(), efs.scan_free())
+    };
+    let before = state();
+    let dirents = fs.root.dirents();
+    // 依次让第 1、2、3 次分配失败，直到每一次分配都成功为止
+    let mut n = 1;
+    loop {
+        fail_alloc_after(n);
+        let dir = fs.root.create_dir("dir");
+        fail_alloc_after(0);
+        if dir.is_some() {
+            break;
+        }
+        assert!(state() == before, "allocation {} left the disk changed", n);
+        assert_eq!(fs.root.dirents(), dirents);
+        assert_eq!(fs.root.lookup("dir"), None);
+        n += 1;
+    }
+    // inode、父目录的数据块和新目录的数据块
+    assert_eq!(n, 4);
+    assert!(fs.root.lookup("dir").is_some());
+}
+
+#[test]
+fn nlink_counts_links_from_every_directory() {
+    let fs = new_fs(4096);
+    let file = fs.root.create("file").unwrap();
+    let dir = fs.root.create_dir("dir").unwrap();
+    assert_eq!(file.nlink(), 1);
+    assert_eq!(dir.nlink(), 1);
+    assert!(fs.root.link("file", "same_dir").is_some());
+    assert_eq!(dir.link_inode(&file, "other_dir"), 0);
+    assert_eq!(file.nlink(), 3);
+    // 失败的链接不计数
+    assert_eq!(dir.link_inode(&file, "other_dir"), -1);
+    assert_eq!(file.nlink(), 3);
+    assert_eq!(fs.root.unlink("same_dir"), 0);
+    assert_eq!(file.nlink(), 2);
+    // 删除子树时树内的链接也要减掉
+    assert_eq!(fs.root.remove_all("dir"), Ok(()));
+    assert_eq!(file.nlink(), 1);
+    let free_inodes = fs.efs.read().free_inodes();
+    // 被 rename 替换掉的最后一个链接回收 inode
+    fs.root.create("other").unwrap();
+    assert_eq!(fs.root.rename("other", &fs.root, "file"), 0);
+    assert_eq!(fs.efs.read().free_inodes(), free_inodes);
+}
@@ -1,11 +1,69 @@
 use super::{
-    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
-    EasyFileSystem, DIRENT_SZ,
+    block_cache_sync_all, get_block_cache, invalidate_block, prefetch_block, BlockDevice,
+    BlockError, DirEntry, DirEntryType, DiskInode, DiskInodeType, EasyFileSystem, FsStat,
+    IndexCache, BLOCK_SZ, DIRENT_SZ, HOLE_BLOCK_ID, MAX_FILE_SIZE, NAME_LENGTH_LIMIT,
 };
+use crate::block_cache::{device_id, READAHEAD_MAX_BLOCKS};
+use crate::dir_index::{self, DirKey, DIR_INDEX_MIN_ENTRIES};
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
-use spin::{Mutex, MutexGuard};
+use core::fmt::{self, Display, Formatter};
+use crate::lock::RwLock;
+use spin::{Mutex, RwLockWriteGuard};
+
+/// Max number of directory levels [`Inode::disk_usage`] descends
+const DU_MAX_DEPTH: usize = 64;
+
+/// Why a file could not grow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GrowError {
+    /// Not enough free data blocks
+    NoSpace,
+    /// The new size is beyond [`MAX_FILE_SIZE`]
+    TooLarge,
+}
+
+/// Small appends gathered in memory by an inode with write combining on
+struct AppendBuffer {
+    /// 缓冲数据在文件中的起始偏移，即缓冲第一个字节时文件在磁盘上的大小
+    start: usize,
+    data: Vec<u8>,
+}
+
+impl AppendBuffer {
+    /// Offset right past the buffered data
+    fn end(&self) -> usize {
+        self.start + self.data.len()
+    }
+}
+
+/// Why removing a directory entry failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoveError {
+    /// No entry of the given name
+    NotFound,
+    /// The entry is not a directory
+    NotDir,
+    /// The directory still holds entries besides `.` and `..`
+    NotEmpty,
+    /// `.` and `..` cannot be removed
+    Invalid,
+}
+
+impl Display for RemoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoveError::NotFound => write!(f, "no such file or directory"),
+            RemoveError::NotDir => write!(f, "not a directory"),
+            RemoveError::NotEmpty => write!(f, "directory not empty"),
+            RemoveError::Invalid => write!(f, "invalid argument"),
+        }
+    }
+}
+
 /// Virtual filesystem layer over easy-fs
 pub struct Inode {
     /// 记录该 Inode 对应的 DiskInode 保存在磁盘上的具体位置
@@ -13,8 +71,15 @@ pub struct Inode {
     /// 偏移量
     pub block_offset: usize,
     // 指向 EasyFileSystem 的一个指针
-    fs: Arc<Mutex<EasyFileSystem>>,
+    // 只读操作（find/read_at 等）持读锁并发进行，分配或修改元数据时持写锁
+    fs: Arc<RwLock<EasyFileSystem>>,
     block_device: Arc<dyn BlockDevice>,
+    /// 最近一次读取用到的索引块
+    index_cache: Mutex<IndexCache>,
+    /// 开启写合并后尚未写入磁盘的追加数据，None 表示未开启
+    append_buffer: Mutex<Option<AppendBuffer>>,
+    /// 打开时 DiskInode 的代数，与磁盘上的不一致说明该 inode 已被回收重用
+    generation: u32,
 }
 
 impl Inode {
@@ -22,16 +87,32 @@ impl Inode {
     pub fn new(
         block_id: u32,
         block_offset: usize,
-        fs: Arc<Mutex<EasyFileSystem>>,
+        fs: Arc<RwLock<EasyFileSystem>>,
         block_device: Arc<dyn BlockDevice>,
     ) -> Self {
+        let generation = get_block_cache(block_id as usize, Arc::clone(&block_device))
+            .lock()
+            .read(block_offset, |disk_inode: &DiskInode| disk_inode.generation);
         Self {
             block_id: block_id as usize,
             block_offset,
             fs,
             block_device,
+            index_cache: Mutex::new(IndexCache::new()),
+            append_buffer: Mutex::new(None),
+            generation,
         }
     }
+    /// Whether the inode this handle was opened on has since been freed and
+    /// reused for another file (ESTALE), in which case reads, writes and
+    /// resizes through the handle fail without touching the new file
+    pub fn is_stale(&self) -> bool {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| self.stale(disk_inode))
+    }
+    fn stale(&self, disk_inode: &DiskInode) -> bool {
+        disk_inode.generation != self.generation
+    }
     /// Call a function over a disk inode to read it
     fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
         get_block_cache(self.block_id, Arc::clone(&self.block_device))
@@ -44,13 +125,31 @@ impl Inode {
             .lock()
             .modify(self.block_offset, f)
     }
-    /// Find inode under a disk inode by name
+    /// Find inode under a disk inode by name, None if the disk inode is not a directory
     fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
-        // assert it is a directory
-        assert!(disk_inode.is_dir());
+        // 普通文件下没有目录项 (ENOTDIR)
+        if !disk_inode.is_dir() {
+            return None;
+        }
+        if let Some(found) = dir_index::lookup(self.dir_key(), name) {
+            return found;
+        }
         let file_count = (disk_inode.size as usize) / DIRENT_SZ;
         let mut dirent = DirEntry::empty();
+        if file_count >= DIR_INDEX_MIN_ENTRIES {
+            // 大目录完整扫描一遍建立索引，之后的查找不再读取目录项
+            let mut entries = BTreeMap::new();
+            for i in 0..file_count {
+                self.prefetch_dirents(disk_inode, i);
+                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                entries.insert(String::from(dirent.name()), dirent.inode_id());
+            }
+            let found = entries.get(name).copied();
+            dir_index::build(self.dir_key(), entries);
+            return found;
+        }
         for i in 0..file_count {
+            self.prefetch_dirents(disk_inode, i);
             assert_eq!(
                 disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device,),
                 DIRENT_SZ,
@@ -61,74 +160,383 @@ impl Inode {
         }
         None
     }
+    /// Key of current inode in the index of large directories
+    fn dir_key(&self) -> DirKey {
+        (device_id(&self.block_device), self.block_id, self.block_offset)
+    }
+    /// When a directory scan reaches the first dirent `i` of a data block,
+    /// hint the device to fetch the next data block of the directory
+    fn prefetch_dirents(&self, disk_inode: &DiskInode, i: usize) {
+        if (i * DIRENT_SZ) % BLOCK_SZ != 0 {
+            return;
+        }
+        let next = ((i * DIRENT_SZ) / BLOCK_SZ + 1) as u32;
+        if next < disk_inode.data_blocks() {
+            let block_id = disk_inode.get_block_id(next, &self.block_device);
+            if block_id != HOLE_BLOCK_ID {
+                prefetch_block(block_id as usize, &self.block_device);
+            }
+        }
+    }
     /// Find inode under current inode by name
     pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
-        let fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode).map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
-        })
+        let fs = self.fs.read();
+        // 子 inode 可能与当前 inode 在同一块中，先放开块缓存再构造
+        let inode_id = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))?;
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Some(Arc::new(Self::new(
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        )))
+    }
+    /// The block a new block of the file is best placed at: right after the
+    /// last data block, 0 (no preference) if the file ends with a hole or is empty
+    fn alloc_goal(&self, disk_inode: &DiskInode) -> u32 {
+        match disk_inode.data_blocks() {
+            0 => 0,
+            blocks => match disk_inode.get_block_id(blocks - 1, &self.block_device) {
+                HOLE_BLOCK_ID => 0,
+                block_id => block_id + 1,
+            },
+        }
+    }
+    /// Allocate `count` data blocks as close to each other after `goal` as
+    /// possible, none if there are not enough free ones
+    fn alloc_blocks(
+        count: u32,
+        mut goal: u32,
+        fs: &mut RwLockWriteGuard<EasyFileSystem>,
+    ) -> Result<Vec<u32>, GrowError> {
+        let mut v: Vec<u32> = Vec::new();
+        for _ in 0..count {
+            match fs.try_alloc_data_near(goal) {
+                Some(block_id) => {
+                    goal = block_id + 1;
+                    v.push(block_id);
+                }
+                None => {
+                    // 回收已分配的部分，不泄漏数据块
+                    for block_id in v {
+                        fs.dealloc_data(block_id);
+                    }
+                    return Err(GrowError::NoSpace);
+                }
+            }
+        }
+        Ok(v)
     }
-    /// Increase the size of a disk inode
+    /// Increase the size of a disk inode, leaving it untouched if there are
+    /// not enough free data blocks or `new_size` exceeds [`MAX_FILE_SIZE`]
     fn increase_size(
         &self,
         new_size: u32,
         disk_inode: &mut DiskInode,
-        fs: &mut MutexGuard<EasyFileSystem>,
-    ) {
+        fs: &mut RwLockWriteGuard<EasyFileSystem>,
+    ) -> Result<(), GrowError> {
         if new_size < disk_inode.size {
-            return;
+            return Ok(());
         }
-        let blocks_needed = disk_inode.blocks_num_needed(new_size);
-        let mut v: Vec<u32> = Vec::new();
-        for _ in 0..blocks_needed {
-            v.push(fs.alloc_data());
+        if new_size as usize > MAX_FILE_SIZE {
+            return Err(GrowError::TooLarge);
         }
+        let goal = self.alloc_goal(disk_inode);
+        let v = Self::alloc_blocks(disk_inode.blocks_num_needed(new_size), goal, fs)?;
         disk_inode.increase_size(new_size, v, &self.block_device);
+        Ok(())
+    }
+    /// Increase the size of a disk inode without allocating data blocks,
+    /// the gap between the old and new size becomes a hole. Like
+    /// [`Inode::increase_size`] it fails with the inode untouched
+    fn increase_size_sparse(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut RwLockWriteGuard<EasyFileSystem>,
+    ) -> Result<(), GrowError> {
+        if new_size < disk_inode.size {
+            return Ok(());
+        }
+        if new_size as usize > MAX_FILE_SIZE {
+            return Err(GrowError::TooLarge);
+        }
+        let goal = self.alloc_goal(disk_inode);
+        let v = Self::alloc_blocks(disk_inode.index_blocks_needed(new_size), goal, fs)?;
+        disk_inode.increase_size_sparse(new_size, v, &self.block_device);
+        Ok(())
+    }
+    /// Allocate data blocks for the holes covered by [start, end), return
+    /// the offset up to which the range is backed by blocks
+    fn fill_holes(
+        &self,
+        start: usize,
+        end: usize,
+        disk_inode: &mut DiskInode,
+        fs: &mut RwLockWriteGuard<EasyFileSystem>,
+    ) -> usize {
+        let end = end.min(disk_inode.size as usize);
+        if start >= end {
+            return end;
+        }
+        let first = (start / BLOCK_SZ) as u32;
+        // 新块紧跟在前一块之后分配，顺序写入的文件数据块尽量连续
+        let mut prev = match first {
+            0 => HOLE_BLOCK_ID,
+            _ => disk_inode.get_block_id(first - 1, &self.block_device),
+        };
+        for inner_id in first..end.div_ceil(BLOCK_SZ) as u32 {
+            let mut block_id = disk_inode.get_block_id(inner_id, &self.block_device);
+            if block_id == HOLE_BLOCK_ID {
+                let goal = if prev == HOLE_BLOCK_ID { 0 } else { prev + 1 };
+                let Some(new_block_id) = fs.try_alloc_data_near(goal) else {
+                    // 磁盘已满，只能写到这一块之前
+                    return (inner_id as usize * BLOCK_SZ).max(start);
+                };
+                block_id = new_block_id;
+                disk_inode.set_block_id(inner_id, block_id, &self.block_device);
+            }
+            prev = block_id;
+        }
+        end
+    }
+    /// Give the blocks covered by [start, end) that are shared with other
+    /// files a private copy before they are written, return the offset up
+    /// to which the range is private
+    fn unshare_blocks(
+        &self,
+        start: usize,
+        end: usize,
+        disk_inode: &mut DiskInode,
+        fs: &mut RwLockWriteGuard<EasyFileSystem>,
+    ) -> usize {
+        let end = end.min(disk_inode.size as usize);
+        if start >= end {
+            return end;
+        }
+        for inner_id in (start / BLOCK_SZ) as u32..end.div_ceil(BLOCK_SZ) as u32 {
+            let block_id = disk_inode.get_block_id(inner_id, &self.block_device);
+            if fs.data_refs(block_id) == 1 {
+                continue;
+            }
+            let Some(new_block_id) = fs.try_alloc_data() else {
+                return (inner_id as usize * BLOCK_SZ).max(start);
+            };
+            let data = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .read(0, |data: &[u8; BLOCK_SZ]| *data);
+            get_block_cache(new_block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .modify(0, |new_data: &mut [u8; BLOCK_SZ]| *new_data = data);
+            disk_inode.set_block_id(inner_id, new_block_id, &self.block_device);
+            // 只减少旧块的引用计数，其他文件仍在使用它
+            fs.dealloc_data(block_id);
+        }
+        end
+    }
+    /// Make [start, end) of current inode writable: grow the inode, back the
+    /// range with data blocks and unshare them. Return the offset up to which
+    /// the range can be written, a short one if the disk is full or the file
+    /// would exceed [`MAX_FILE_SIZE`]; the size only grows that far
+    fn prepare_write(
+        &self,
+        start: usize,
+        end: usize,
+        disk_inode: &mut DiskInode,
+        fs: &mut RwLockWriteGuard<EasyFileSystem>,
+    ) -> usize {
+        let old_size = disk_inode.size as usize;
+        let end = end.min(MAX_FILE_SIZE);
+        if start >= end || self.increase_size_sparse(end as u32, disk_inode, fs).is_err() {
+            return start;
+        }
+        let end = self.fill_holes(start, end, disk_inode, fs);
+        let end = self.unshare_blocks(start, end, disk_inode, fs);
+        // 没有写到的部分不应让文件变大，回收为它分配的索引块
+        let new_size = end.max(old_size);
+        if new_size < disk_inode.size as usize {
+            for block_id in disk_inode.decrease_size(new_size as u32, &self.block_device) {
+                fs.dealloc_data(block_id);
+            }
+        }
+        end
+    }
+    /// Identify the filesystem current inode lives on, equal for inodes of the same filesystem
+    pub fn fs_id(&self) -> usize {
+        Arc::as_ptr(&self.fs) as *const u8 as usize
+    }
+    /// Size and usage of the filesystem current inode lives on
+    pub fn stat_fs(&self) -> FsStat {
+        self.fs.read().stat_fs()
+    }
+    /// Number of in-memory inodes (including this one) still referring to the filesystem
+    pub fn fs_users(&self) -> usize {
+        Arc::strong_count(&self.fs)
+    }
+    /// Whether current inode is a directory
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+    /// Permission bits of current inode
+    pub fn mode(&self) -> u16 {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| disk_inode.mode)
+    }
+    /// Change the permission bits of current inode, only the low 9 bits are kept
+    pub fn set_mode(&self, mode: u16) {
+        let _fs = self.fs.write();
+        self.modify_disk_inode(|disk_inode| {
+            disk_inode.mode = mode & 0o777;
+        });
+        block_cache_sync_all();
     }
-    /// Create inode under current inode by name
+    /// Owner (uid, gid) of current inode
+    pub fn owner(&self) -> (u16, u16) {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| (disk_inode.uid, disk_inode.gid))
+    }
+    /// Change the owner of current inode
+    pub fn set_owner(&self, uid: u16, gid: u16) {
+        let _fs = self.fs.write();
+        self.modify_disk_inode(|disk_inode| {
+            disk_inode.uid = uid;
+            disk_inode.gid = gid;
+        });
+        block_cache_sync_all();
+    }
+    /// Number of dirents referring to current inode, `.` and `..` aside
+    pub fn nlink(&self) -> u32 {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| disk_inode.nlink)
+    }
+    /// Access and modification time (atime, mtime) of current inode in seconds.
+    /// easy-fs has no clock, the kernel keeps them up to date with `set_times`
+    pub fn times(&self) -> (u32, u32) {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| (disk_inode.atime, disk_inode.mtime))
+    }
+    /// Change the access and modification time of current inode, a `None`
+    /// leaves that time unchanged
+    pub fn set_times(&self, atime: Option<u32>, mtime: Option<u32>) {
+        let _fs = self.fs.write();
+        self.modify_disk_inode(|disk_inode| {
+            if let Some(atime) = atime {
+                disk_inode.atime = atime;
+            }
+            if let Some(mtime) = mtime {
+                disk_inode.mtime = mtime;
+            }
+        });
+        block_cache_sync_all();
+    }
+    /// Create inode under current inode by name, None if it exists, current
+    /// inode is not a directory or the disk is full
     pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
-        let mut fs = self.fs.lock();
+        let mut fs = self.fs.write();
         let op = |root_inode: &DiskInode| {
-            // assert it is a directory
-            assert!(root_inode.is_dir());
             // has the file been created?
-            self.find_inode_id(name, root_inode)
+            !root_inode.is_dir() || self.find_inode_id(name, root_inode).is_some()
         };
-        if self.read_disk_inode(op).is_some() {
+        if name.len() > NAME_LENGTH_LIMIT || self.read_disk_inode(op) {
             return None;
         }
+        self.create_locked(name, DiskInodeType::File, &mut fs)
+        // release efs lock automatically by compiler
+    }
+    /// Create a directory under current inode by name, holding the entries
+    /// `.` and `..`. None if it exists, current inode is not a directory or
+    /// the disk is full
+    pub fn create_dir(&self, name: &str) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.write();
+        let op = |root_inode: &DiskInode| {
+            !root_inode.is_dir() || self.find_inode_id(name, root_inode).is_some()
+        };
+        if name.len() > NAME_LENGTH_LIMIT || self.read_disk_inode(op) {
+            return None;
+        }
+        let parent_id = fs.get_disk_inode_id(self.block_id as u32, self.block_offset);
+        let snapshot = fs.snapshot_bitmaps();
+        let dir = self.create_locked(name, DiskInodeType::Directory, &mut fs)?;
+        let dir_id = fs.get_disk_inode_id(dir.block_id as u32, dir.block_offset);
+        let appended = dir.modify_disk_inode(|dir_inode| {
+            let dot = DirEntry::new(".", dir_id, DirEntryType::Directory);
+            let dotdot = DirEntry::new("..", parent_id, DirEntryType::Directory);
+            dir.append_dirent(dir_inode, &dot, &mut fs)
+                && dir.append_dirent(dir_inode, &dotdot, &mut fs)
+        });
+        if !appended {
+            // 放不下 . 和 ..：撤掉父目录中的目录项，回滚新目录占用的 inode 和数据块
+            self.modify_disk_inode(|root_inode| self.remove_dirent(root_inode, name));
+            fs.restore_bitmaps(&snapshot);
+            block_cache_sync_all();
+            return None;
+        }
+        block_cache_sync_all();
+        Some(dir)
+    }
+    /// Find inode under current inode by name, create it if not found.
+    /// The lookup and the creation happen under one fs lock, so concurrent
+    /// callers never both create the same name. Return (inode, created),
+    /// or None if current inode is not a directory or the disk is full
+    pub fn find_or_create(&self, name: &str) -> Option<(Arc<Inode>, bool)> {
+        let mut fs = self.fs.write();
+        if name.len() > NAME_LENGTH_LIMIT
+            || !self.read_disk_inode(|root_inode| root_inode.is_dir())
+        {
+            return None;
+        }
+        let op = |root_inode: &DiskInode| self.find_inode_id(name, root_inode);
+        if let Some(inode_id) = self.read_disk_inode(op) {
+            let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+            let inode = Arc::new(Self::new(
+                block_id,
+                block_offset,
+                self.fs.clone(),
+                self.block_device.clone(),
+            ));
+            return Some((inode, false));
+        }
+        Some((self.create_locked(name, DiskInodeType::File, &mut fs)?, true))
+    }
+    /// Create an inode of `type_` named `name` under current inode, the caller
+    /// holds the fs lock and has checked that the name does not exist.
+    /// None if the disk is full, in which case nothing stays allocated
+    fn create_locked(
+        &self,
+        name: &str,
+        type_: DiskInodeType,
+        fs: &mut RwLockWriteGuard<EasyFileSystem>,
+    ) -> Option<Arc<Inode>> {
+        let snapshot = fs.snapshot_bitmaps();
         // create a new file
         // alloc a inode with an indirect block
-        let new_inode_id = fs.alloc_inode();
+        let new_inode_id = fs.try_alloc_inode()?;
         // initialize inode
         let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
-        get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
+        let new_inode_cache =
+            get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device));
+        let dirent_type = new_inode_cache
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+                new_inode.initialize(type_);
+                new_inode.dirent_type()
             });
-        self.modify_disk_inode(|root_inode| {
+        // 先把新 inode 写回磁盘，再写指向它的目录项，崩溃时不会留下悬空的目录项
+        if let Err(err) = new_inode_cache.lock().sync() {
+            panic!("{}", err);
+        }
+        let appended = self.modify_disk_inode(|root_inode| {
             // append file in the dirent
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let new_size = (file_count + 1) * DIRENT_SZ;
-            // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
-            // write dirent
-            let dirent = DirEntry::new(name, new_inode_id);
-            root_inode.write_at(
-                file_count * DIRENT_SZ,
-                dirent.as_bytes(),
-                &self.block_device,
-            );
+            let dirent = DirEntry::new(name, new_inode_id, dirent_type);
+            self.append_dirent(root_inode, &dirent, fs)
         });
+        if !appended {
+            // 目录项写不下：目录大小未变，回收刚分配的 inode
+            fs.restore_bitmaps(&snapshot);
+            block_cache_sync_all();
+            return None;
+        }
 
         let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
         block_cache_sync_all();
@@ -139,141 +547,900 @@ impl Inode {
             self.fs.clone(),
             self.block_device.clone(),
         )))
-        // release efs lock automatically by compiler
+    }
+    /// Append a dirent at the end of the directory `root_inode` of current
+    /// inode, false with the directory unchanged if no data block is left
+    fn append_dirent(
+        &self,
+        root_inode: &mut DiskInode,
+        dirent: &DirEntry,
+        fs: &mut RwLockWriteGuard<EasyFileSystem>,
+    ) -> bool {
+        let file_count = (root_inode.size as usize) / DIRENT_SZ;
+        let new_size = (file_count + 1) * DIRENT_SZ;
+        // increase size
+        if self.increase_size(new_size as u32, root_inode, fs).is_err() {
+            return false;
+        }
+        // write dirent
+        root_inode.write_at(file_count * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+        dir_index::insert(self.dir_key(), dirent.name(), dirent.inode_id());
+        true
+    }
+    /// List inodes under current inode together with their types,
+    /// which come from the dirents without reading the inodes
+    pub fn ls_with_type(&self) -> Vec<(String, DirEntryType)> {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut v: Vec<(String, DirEntryType)> = Vec::new();
+            for i in 0..file_count {
+                self.prefetch_dirents(disk_inode, i);
+                let mut dirent = DirEntry::empty();
+                assert_eq!(
+                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
+                    DIRENT_SZ,
+                );
+                v.push((String::from(dirent.name()), dirent.entry_type()));
+            }
+            v
+        })
+    }
+    /// Get the name, inode id and type of the `index`-th dirent,
+    /// None if current inode is not a directory or has fewer dirents
+    pub fn read_dirent(&self, index: usize) -> Option<(String, u32, DirEntryType)> {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() || index >= (disk_inode.size as usize) / DIRENT_SZ {
+                return None;
+            }
+            let mut dirent = DirEntry::empty();
+            disk_inode.read_at(index * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device);
+            Some((String::from(dirent.name()), dirent.inode_id(), dirent.entry_type()))
+        })
+    }
+    /// Get the name, inode id and type of every dirent at once, a
+    /// consistent snapshot of the directory. Empty if current inode is not
+    /// a directory
+    pub fn dirents(&self) -> Vec<(String, u32, DirEntryType)> {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return Vec::new();
+            }
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut v = Vec::with_capacity(file_count);
+            for i in 0..file_count {
+                self.prefetch_dirents(disk_inode, i);
+                let mut dirent = DirEntry::empty();
+                disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device);
+                v.push((String::from(dirent.name()), dirent.inode_id(), dirent.entry_type()));
+            }
+            v
+        })
+    }
+    /// Get the inode id `name` refers to under current inode without
+    /// opening it, None if there is no such dirent
+    pub fn lookup(&self, name: &str) -> Option<u32> {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))
     }
     /// List inodes under current inode
     pub fn ls(&self) -> Vec<String> {
-        let _fs = self.fs.lock();
+        self.ls_filter(|_| true)
+    }
+    /// List inodes under current inode whose names satisfy `f`,
+    /// names are filtered during the scan so only matches are allocated
+    pub fn ls_filter(&self, f: impl Fn(&str) -> bool) -> Vec<String> {
+        let _fs = self.fs.read();
         self.read_disk_inode(|disk_inode| {
             let file_count = (disk_inode.size as usize) / DIRENT_SZ;
             let mut v: Vec<String> = Vec::new();
             for i in 0..file_count {
+                self.prefetch_dirents(disk_inode, i);
                 let mut dirent = DirEntry::empty();
                 assert_eq!(
                     disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
                     DIRENT_SZ,
                 );
-                v.push(String::from(dirent.name()));
+                if f(dirent.name()) {
+                    v.push(String::from(dirent.name()));
+                }
             }
             v
         })
     }
+    /// Turn write combining of this handle of the file on or off. While on,
+    /// appends shorter than a block right at the end of the file gather in
+    /// memory and reach the disk as one write once a block is full, on
+    /// [`Inode::flush_appends`] or when the handle is dropped. Reads, the
+    /// size and other writes through this handle see the gathered data,
+    /// other handles of the same file only once it is flushed
+    pub fn set_write_combining(&self, enable: bool) {
+        if !enable {
+            self.flush_appends();
+            *self.append_buffer.lock() = None;
+        } else {
+            let mut guard = self.append_buffer.lock();
+            if guard.is_none() {
+                *guard = Some(AppendBuffer {
+                    start: 0,
+                    data: Vec::with_capacity(BLOCK_SZ),
+                });
+            }
+        }
+    }
+    /// Write the appends gathered by write combining to the disk, false if
+    /// the disk filled up and part of them was lost
+    pub fn flush_appends(&self) -> bool {
+        let mut guard = self.append_buffer.lock();
+        let Some(buffer) = guard.as_mut().filter(|buffer| !buffer.data.is_empty()) else {
+            return true;
+        };
+        let written = self.write_direct(buffer.start, &buffer.data);
+        // 写不下的部分丢弃，下次追加时从文件在磁盘上的末尾重新开始
+        buffer.start += written;
+        let flushed = written == buffer.data.len();
+        buffer.data.clear();
+        flushed
+    }
+    /// Throw the gathered appends away, for operations emptying the file
+    fn discard_appends(&self) {
+        if let Some(buffer) = self.append_buffer.lock().as_mut() {
+            buffer.data.clear();
+        }
+    }
     /// Read data from current inode
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
-        let _fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+        let read_size = self.read_direct(offset, buf);
+        // 写合并缓冲中的数据尚未落盘，叠加到读出的结果上
+        let guard = self.append_buffer.lock();
+        let Some(buffer) = guard.as_ref().filter(|buffer| !buffer.data.is_empty()) else {
+            return read_size;
+        };
+        let end = buffer.end().min(offset + buf.len());
+        if end <= offset.max(buffer.start) {
+            return read_size;
+        }
+        let start = offset.max(buffer.start);
+        buf[start - offset..end - offset]
+            .copy_from_slice(&buffer.data[start - buffer.start..end - buffer.start]);
+        read_size.max(end - offset)
+    }
+    fn read_direct(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let fs = self.fs.read();
+        // 其他读者正在用索引缓存时不等待，直接查索引块
+        let Some(mut cache) = self.index_cache.try_lock() else {
+            return self.read_disk_inode(|disk_inode| {
+                if self.stale(disk_inode) {
+                    return 0;
+                }
+                disk_inode.read_at(offset, buf, &self.block_device)
+            });
+        };
+        let epoch = fs.data_epoch();
+        self.read_disk_inode(|disk_inode| {
+            if self.stale(disk_inode) {
+                return 0;
+            }
+            disk_inode.read_at_cached(offset, buf, &self.block_device, &mut cache, epoch)
+        })
+    }
+    /// Drop the cached data blocks of current inode covering
+    /// [offset, offset + len) from the block cache, writing dirty ones back.
+    /// Blocks in use elsewhere stay cached
+    pub fn drop_cache(&self, offset: usize, len: usize) -> Result<(), BlockError> {
+        let _fs = self.fs.read();
+        let blocks: Vec<u32> = self.read_disk_inode(|disk_inode| {
+            let end = offset.saturating_add(len).min(disk_inode.size as usize);
+            if offset >= end {
+                return Vec::new();
+            }
+            ((offset / BLOCK_SZ) as u32..end.div_ceil(BLOCK_SZ) as u32)
+                .map(|inner_id| disk_inode.get_block_id(inner_id, &self.block_device))
+                .filter(|block_id| *block_id != HOLE_BLOCK_ID)
+                .collect()
+        });
+        for block_id in blocks {
+            invalidate_block(block_id as usize, &self.block_device)?;
+        }
+        Ok(())
+    }
+    /// Load the data blocks of current inode covering [offset, offset + len)
+    /// into the block cache so that reading them next does not wait for the
+    /// device. Stops at the end of the file and after a few blocks, which
+    /// would otherwise evict each other, return the length of the range
+    /// from `offset` that is now cached
+    pub fn readahead(&self, offset: usize, len: usize) -> usize {
+        let _fs = self.fs.read();
+        let (blocks, end) = self.read_disk_inode(|disk_inode| {
+            let end = offset.saturating_add(len).min(disk_inode.size as usize);
+            if offset >= end || self.stale(disk_inode) {
+                return (Vec::new(), offset);
+            }
+            let blocks: Vec<u32> = ((offset / BLOCK_SZ) as u32..end.div_ceil(BLOCK_SZ) as u32)
+                .take(READAHEAD_MAX_BLOCKS)
+                .map(|inner_id| disk_inode.get_block_id(inner_id, &self.block_device))
+                .collect();
+            (blocks, end)
+        });
+        // 空洞读出全 0，不需要加载
+        for block_id in blocks.iter().filter(|block_id| **block_id != HOLE_BLOCK_ID) {
+            get_block_cache(*block_id as usize, Arc::clone(&self.block_device));
+        }
+        ((offset / BLOCK_SZ + blocks.len()) * BLOCK_SZ).min(end) - offset
     }
     /// Write data to current inode
     pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
-        let mut fs = self.fs.lock();
+        let mut guard = self.append_buffer.lock();
+        if let Some(buffer) = guard.as_mut() {
+            if buffer.data.is_empty() {
+                // 缓冲的数据要到刷新时才检查代数，旧句柄在这里就拒绝
+                if self.is_stale() {
+                    return 0;
+                }
+                buffer.start = self.disk_size();
+            }
+            if offset == buffer.end() && buf.len() < BLOCK_SZ {
+                buffer.data.extend_from_slice(buf);
+                if buffer.data.len() < BLOCK_SZ {
+                    return buf.len();
+                }
+                drop(guard);
+                // 缓冲满了一块，一次写入磁盘；写不下时只报告本次写入落盘的部分
+                let end = self.append_end_after_flush();
+                return end.saturating_sub(offset).min(buf.len());
+            }
+        }
+        drop(guard);
+        self.flush_appends();
+        self.write_direct(offset, buf)
+    }
+    /// Flush the append buffer, return the end of the data now on the disk
+    fn append_end_after_flush(&self) -> usize {
+        self.flush_appends();
+        self.append_buffer.lock().as_ref().map_or(0, AppendBuffer::end)
+    }
+    fn write_direct(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut fs = self.fs.write();
         let size = self.modify_disk_inode(|disk_inode| {
-            self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
-            disk_inode.write_at(offset, buf, &self.block_device)
+            if self.stale(disk_inode) {
+                return 0;
+            }
+            // 只为真正写入的范围分配数据块，跳过的部分留作空洞
+            let end = self.prepare_write(offset, offset + buf.len(), disk_inode, &mut fs);
+            if end <= offset {
+                return 0;
+            }
+            disk_inode.write_at(offset, &buf[..end - offset], &self.block_device)
+        });
+        block_cache_sync_all();
+        size
+    }
+    /// Write several byte slices back to back starting at offset, e.g. the page
+    /// fragments of a user buffer, under one lock and one size adjustment
+    pub fn write_vectored_at<B: AsRef<[u8]>>(&self, offset: usize, bufs: &[B]) -> usize {
+        let len: usize = bufs.iter().map(|buf| buf.as_ref().len()).sum();
+        if len == 0 {
+            return 0;
+        }
+        self.flush_appends();
+        let mut fs = self.fs.write();
+        let size = self.modify_disk_inode(|disk_inode| {
+            if self.stale(disk_inode) {
+                return 0;
+            }
+            let end = self.prepare_write(offset, offset + len, disk_inode, &mut fs);
+            let mut write_size = 0usize;
+            for buf in bufs.iter().map(|buf| buf.as_ref()) {
+                // 空间不足时只写到 end 为止
+                let room = end.saturating_sub(offset + write_size);
+                if room == 0 {
+                    break;
+                }
+                let buf = &buf[..buf.len().min(room)];
+                if !buf.is_empty() {
+                    write_size +=
+                        disk_inode.write_at(offset + write_size, buf, &self.block_device);
+                }
+            }
+            write_size
         });
         block_cache_sync_all();
         size
     }
     /// Clear the data in current inode
     pub fn clear(&self) {
-        let mut fs = self.fs.lock();
+        self.discard_appends();
+        let mut fs = self.fs.write();
         self.modify_disk_inode(|disk_inode| {
+            if self.stale(disk_inode) {
+                return;
+            }
             let size = disk_inode.size;
             let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
-            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+            assert!(data_blocks_dealloc.len() <= DiskInode::total_blocks(size) as usize);
             for data_block in data_blocks_dealloc.into_iter() {
                 fs.dealloc_data(data_block);
             }
         });
         block_cache_sync_all();
     }
+    /// Resize current inode to `new_size`, false with the size unchanged if
+    /// the disk is full or `new_size` exceeds [`MAX_FILE_SIZE`]
+    /// 扩大时新增部分留作空洞（读出全 0），缩小时回收多余的块
+    pub fn truncate(&self, new_size: u32) -> bool {
+        self.flush_appends();
+        let mut fs = self.fs.write();
+        let resized = self.modify_disk_inode(|disk_inode| {
+            if self.stale(disk_inode) {
+                return false;
+            }
+            if new_size >= disk_inode.size {
+                return self.increase_size_sparse(new_size, disk_inode, &mut fs).is_ok();
+            }
+            // 缩小时会把最后一块的尾部清零，这一块与其他文件共享时要先复制
+            if new_size as usize % BLOCK_SZ != 0 {
+                let new_size = new_size as usize;
+                if self.unshare_blocks(new_size, new_size + 1, disk_inode, &mut fs) <= new_size {
+                    return false;
+                }
+            }
+            for data_block in disk_inode.decrease_size(new_size, &self.block_device) {
+                fs.dealloc_data(data_block);
+            }
+            true
+        });
+        block_cache_sync_all();
+        resized
+    }
+    /// Back [offset, offset + len) of current inode with data blocks, which
+    /// read as zeros where they are new, growing the file to offset + len if
+    /// it is smaller. False with the inode untouched if the disk cannot
+    /// hold the whole range or the file would exceed [`MAX_FILE_SIZE`]
+    pub fn allocate(&self, offset: usize, len: usize) -> bool {
+        let Some(end) = offset.checked_add(len).filter(|end| *end <= MAX_FILE_SIZE) else {
+            return false;
+        };
+        self.flush_appends();
+        let mut fs = self.fs.write();
+        let allocated = self.modify_disk_inode(|disk_inode| {
+            if self.stale(disk_inode) {
+                return false;
+            }
+            let old_size = disk_inode.size;
+            if self.increase_size_sparse(end as u32, disk_inode, &mut fs).is_err() {
+                return false;
+            }
+            let mut filled = Vec::new();
+            for inner_id in (offset / BLOCK_SZ) as u32..end.div_ceil(BLOCK_SZ) as u32 {
+                if disk_inode.get_block_id(inner_id, &self.block_device) != HOLE_BLOCK_ID {
+                    continue;
+                }
+                // 回收的数据块都已清零，新分配的块读出全 0
+                if let Some(block_id) = fs.try_alloc_data() {
+                    disk_inode.set_block_id(inner_id, block_id, &self.block_device);
+                    filled.push((inner_id, block_id));
+                    continue;
+                }
+                // 空间不足：撤销已填上的空洞和增长的大小
+                for (inner_id, block_id) in filled {
+                    disk_inode.set_block_id(inner_id, HOLE_BLOCK_ID, &self.block_device);
+                    fs.dealloc_data(block_id);
+                }
+                if disk_inode.size > old_size {
+                    for block_id in disk_inode.decrease_size(old_size, &self.block_device) {
+                        fs.dealloc_data(block_id);
+                    }
+                }
+                return false;
+            }
+            true
+        });
+        block_cache_sync_all();
+        allocated
+    }
+    /// Size of current inode in bytes
+    pub fn size(&self) -> u32 {
+        let size = self.disk_size();
+        match self.append_buffer.lock().as_ref() {
+            Some(buffer) if !buffer.data.is_empty() => size.max(buffer.end()) as u32,
+            _ => size as u32,
+        }
+    }
+    /// Size of current inode on the disk, without the gathered appends
+    fn disk_size(&self) -> usize {
+        let _fs = self.fs.read();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+    /// Bytes of the data and index blocks held by current inode and, if it
+    /// is a directory, by everything below it up to `DU_MAX_DEPTH` levels
+    /// down. A file reached through several hard links or a block shared by
+    /// reflinked files is counted once, holes are not counted
+    pub fn disk_usage(&self) -> u64 {
+        let fs = self.fs.read();
+        let inode_id = fs.get_disk_inode_id(self.block_id as u32, self.block_offset);
+        let mut visited = BTreeSet::new();
+        let mut shared = BTreeSet::new();
+        self.disk_usage_locked(inode_id, 0, &mut visited, &mut shared, &fs)
+    }
+    /// Usage of current inode `inode_id` found `depth` levels below where
+    /// [`Inode::disk_usage`] started. `visited` skips inodes reached twice,
+    /// `shared` the blocks of other files already counted
+    fn disk_usage_locked(
+        &self,
+        inode_id: u32,
+        depth: usize,
+        visited: &mut BTreeSet<u32>,
+        shared: &mut BTreeSet<u32>,
+        fs: &EasyFileSystem,
+    ) -> u64 {
+        if !visited.insert(inode_id) {
+            return 0;
+        }
+        let (blocks, children) = self.read_disk_inode(|disk_inode| {
+            // 索引块不会共享，按大小算出个数
+            let mut blocks =
+                (DiskInode::total_blocks(disk_inode.size) - disk_inode.data_blocks()) as u64;
+            for block_id in disk_inode.iter_blocks(&self.block_device) {
+                if block_id == HOLE_BLOCK_ID {
+                    continue;
+                }
+                if fs.data_refs(block_id) == 1 || shared.insert(block_id) {
+                    blocks += 1;
+                }
+            }
+            if !disk_inode.is_dir() || depth >= DU_MAX_DEPTH {
+                return (blocks, Vec::new());
+            }
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut dirent = DirEntry::empty();
+            let children: Vec<u32> = (0..file_count)
+                .filter_map(|i| {
+                    disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                    (dirent.name() != "." && dirent.name() != "..").then(|| dirent.inode_id())
+                })
+                .collect();
+            (blocks, children)
+        });
+        let mut usage = blocks * BLOCK_SZ as u64;
+        for child_id in children {
+            usage += self
+                .inode_of(child_id, fs)
+                .disk_usage_locked(child_id, depth + 1, visited, shared, fs);
+        }
+        usage
+    }
 
     /// 硬链接实现
     pub fn link(&self, old: &str, new: &str) -> Option<Arc<Inode>> {
-        let mut fs = self.fs.lock();  // 锁定文件系统，确保线程安全
+        let mut fs = self.fs.write();  // 锁定文件系统，确保线程安全
+        if new.len() > NAME_LENGTH_LIMIT {  // 新名字过长，目录项放不下
+            return None;
+        }
+        if self.read_disk_inode(|root_inode| self.find_inode_id(new, root_inode)).is_some() {
+            return None;  // 新名字已存在
+        }
         let op = |root_inode: &DiskInode| {  // 定义一个闭包，用于后面读取inode
-            assert!(root_inode.is_dir());  // 断言：确保当前操作的是目录inode
-            self.find_inode_id(old, root_inode)  // 寻找指定文件名的inode ID
+            self.find_inode_id(old, root_inode)  // 寻找指定文件名的inode ID，非目录时为 None
         };
         if let Some(old_inode_id) = self.read_disk_inode(op) {  // 使用闭包，如果找到old的inode ID
             let new_inode_id = old_inode_id;  // 新硬链接使用相同的inode ID
             let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);  // 获取inode的位置
-            self.modify_disk_inode(|root_inode| {  // 修改根目录的inode来添加新的目录项
-                let file_count = (root_inode.size as usize) / DIRENT_SZ;  // 计算当前目录项的数量
-                let new_size = (file_count + 1) * DIRENT_SZ;  // 计算新的目录大小
-                self.increase_size(new_size as u32, root_inode, &mut fs);  // 增加根目录的大小
-                let dirent = DirEntry::new(new, new_inode_id);  // 创建新的目录项结构体
-                root_inode.write_at(
-                    file_count * DIRENT_SZ,
-                    dirent.as_bytes(),
-                    &self.block_device,
-                );  // 在目录的尾部写入新目录项
-            });
-            Some(Arc::new(Self::new(
+            let dirent_type = get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .read(new_inode_block_offset, |inode: &DiskInode| inode.dirent_type());  // 新目录项的类型与原文件一致
+            let dirent = DirEntry::new(new, new_inode_id, dirent_type);  // 创建新的目录项结构体
+            // 在目录的尾部写入新目录项，磁盘已满时失败
+            if !self.modify_disk_inode(|root_inode| self.append_dirent(root_inode, &dirent, &mut fs)) {
+                return None;
+            }
+            let inode = Arc::new(Self::new(
                 new_inode_block_id,
                 new_inode_block_offset,
                 self.fs.clone(),
                 self.block_device.clone(),
-            )))  // 返回新创建的inode的智能指针
+            ));
+            inode.modify_disk_inode(|disk_inode| disk_inode.nlink += 1);
+            block_cache_sync_all();
+            Some(inode)  // 返回新创建的inode的智能指针
         } else {
             None  // 如果找不到old的inode ID，返回None
         }
     }
     
 
-    /// 删除硬链接
+    /// Add the entry `new` under current inode for the file `target`, which
+    /// may live in another directory of the same filesystem.
+    /// -1 if `target` is a directory, `new` exists or the disk is full
+    pub fn link_inode(&self, target: &Inode, new: &str) -> isize {
+        // 硬链接不能跨文件系统 (EXDEV)
+        if !Arc::ptr_eq(&self.fs, &target.fs) {
+            return -1;
+        }
+        let mut fs = self.fs.write();
+        if new.len() > NAME_LENGTH_LIMIT || new.is_empty() || new == "." || new == ".." {
+            return -1;
+        }
+        let Some(exists) = self.read_disk_inode(|root_inode| {
+            root_inode
+                .is_dir()
+                .then(|| self.find_inode_id(new, root_inode).is_some())
+        }) else {
+            return -1;
+        };
+        let (target_type, target_is_dir) =
+            target.read_disk_inode(|disk_inode| (disk_inode.dirent_type(), disk_inode.is_dir()));
+        if exists || target_is_dir {
+            return -1;
+        }
+        let target_id = fs.get_disk_inode_id(target.block_id as u32, target.block_offset);
+        let appended = self.modify_disk_inode(|root_inode| {
+            let dirent = DirEntry::new(new, target_id, target_type);
+            self.append_dirent(root_inode, &dirent, &mut fs)
+        });
+        if appended {
+            target.modify_disk_inode(|disk_inode| disk_inode.nlink += 1);
+        }
+        block_cache_sync_all();
+        if appended {
+            0
+        } else {
+            -1
+        }
+    }
+    /// Create file `new` under current inode holding the same data as file
+    /// `old` without copying it: both files share the data blocks, which are
+    /// copied only when one of the files writes them. None if `old` is not a
+    /// file, `new` exists or the disk is full
+    pub fn reflink(&self, old: &str, new: &str) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.write();
+        if new.len() > NAME_LENGTH_LIMIT
+            || self.read_disk_inode(|root_inode| self.find_inode_id(new, root_inode)).is_some()
+        {
+            return None;
+        }
+        let old_inode_id = self.read_disk_inode(|root_inode| self.find_inode_id(old, root_inode))?;
+        let old_inode = self.inode_of(old_inode_id, &fs);
+        let (size, mode, blocks) = old_inode.read_disk_inode(|disk_inode| {
+            let blocks: Vec<u32> = disk_inode.iter_blocks(&self.block_device).collect();
+            (disk_inode.size, disk_inode.mode, blocks)
+        });
+        if old_inode.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return None;
+        }
+        let new_inode = self.create_locked(new, DiskInodeType::File, &mut fs)?;
+        // 只分配索引块，数据块直接指向原文件的块
+        let grown = new_inode.modify_disk_inode(|disk_inode| {
+            disk_inode.mode = mode;
+            self.increase_size_sparse(size, disk_inode, &mut fs)
+        });
+        if grown.is_err() {
+            // 索引块也分配不出来时撤销刚创建的文件
+            let new_inode_id = self.read_disk_inode(|root_inode| self.find_inode_id(new, root_inode))?;
+            self.modify_disk_inode(|root_inode| self.remove_dirent(root_inode, new));
+            new_inode.free_locked(new_inode_id, &mut fs);
+            block_cache_sync_all();
+            return None;
+        }
+        let shared: Vec<(u32, u32)> = new_inode.modify_disk_inode(|disk_inode| {
+            blocks
+                .into_iter()
+                .enumerate()
+                .filter(|(_, block_id)| *block_id != HOLE_BLOCK_ID)
+                .map(|(inner_id, block_id)| {
+                    disk_inode.set_block_id(inner_id as u32, block_id, &self.block_device);
+                    (inner_id as u32, block_id)
+                })
+                .collect()
+        });
+        let block_ids: Vec<u32> = shared.iter().map(|(_, block_id)| *block_id).collect();
+        if !fs.share_data(&block_ids) {
+            // 引用计数表写不下时撤销：新文件先放开共享的块，删除时只回收它自己的索引块
+            new_inode.modify_disk_inode(|disk_inode| {
+                for (inner_id, _) in &shared {
+                    disk_inode.set_block_id(*inner_id, HOLE_BLOCK_ID, &self.block_device);
+                }
+            });
+            let new_inode_id = self.read_disk_inode(|root_inode| self.find_inode_id(new, root_inode))?;
+            self.modify_disk_inode(|root_inode| self.remove_dirent(root_inode, new));
+            new_inode.free_locked(new_inode_id, &mut fs);
+            block_cache_sync_all();
+            return None;
+        }
+        block_cache_sync_all();
+        Some(new_inode)
+    }
+
+    /// 删除硬链接，删除最后一个链接时回收 inode 和数据块
+    /// 目录需要用 rmdir 删除，此时返回 -1
     pub fn unlink(&self, name: &str) -> isize {
-        let _fs = self.fs.lock();
+        let mut fs = self.fs.write();
         let op = |root_inode: &DiskInode| {
-            // assert it is a directory
-            assert!(root_inode.is_dir());
-            // has the file been created?
+            // has the file been created? None if not a directory
             self.find_inode_id(name, root_inode)
         };
-        // Only when we find the path name, can we unlink it 
-        if let Some(_) = self.read_disk_inode(op) {
-            self.modify_disk_inode(|root_inode| {
-                let mut buf = DirEntry::empty();
-                let mut swap = DirEntry::empty();
-                let file_count = (root_inode.size as usize) / DIRENT_SZ;
+        // Only when we find the path name, can we unlink it
+        let Some(inode_id) = self.read_disk_inode(op) else {
+            // cannot find the file
+            return -1;
+        };
+        let inode = self.inode_of(inode_id, &fs);
+        if inode.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return -1;
+        }
+        self.modify_disk_inode(|root_inode| self.remove_dirent(root_inode, name));
+        inode.unlink_locked(inode_id, &mut fs);
+        block_cache_sync_all();
+        0
+    }
+
+    /// Remove the dirent `name` from the directory `root_inode` of current inode,
+    /// the last dirent is moved into its slot. Return whether it was found
+    fn remove_dirent(&self, root_inode: &mut DiskInode, name: &str) -> bool {
+        let mut buf = DirEntry::empty();
+        let mut swap = DirEntry::empty();
+        let file_count = (root_inode.size as usize) / DIRENT_SZ;
+        for i in 0..file_count {
+            let len = root_inode.read_at(DIRENT_SZ * i, buf.as_bytes_mut(), &self.block_device);
+            if len == DIRENT_SZ && buf.name() == name {
+                // we are asked not to delete the node so we overwrite the node
+                let last = DIRENT_SZ * (file_count - 1);
+                root_inode.read_at(last, swap.as_bytes_mut(), &self.block_device);
+                root_inode.write_at(DIRENT_SZ * i, swap.as_bytes_mut(), &self.block_device);
+                root_inode.size -= DIRENT_SZ as u32;
+                dir_index::remove(self.dir_key(), name);
+                // unlink one per call
+                return true;
+            }
+        }
+        false
+    }
+    /// Build the vfs inode of `inode_id` on the same filesystem as current inode
+    fn inode_of(&self, inode_id: u32, fs: &EasyFileSystem) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Arc::new(Self::new(
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ))
+    }
+    /// Number of entries of the directory `disk_inode` besides `.` and `..`
+    fn dirent_count(&self, disk_inode: &DiskInode) -> usize {
+        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        (0..file_count)
+            .filter(|&i| {
+                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                dirent.name() != "." && dirent.name() != ".."
+            })
+            .count()
+    }
+
+    /// Move the entry `old` of current directory to `new` under the directory
+    /// `new_parent` of the same filesystem, all under one fs lock. An existing
+    /// `new` is replaced if it is a file (when `old` is a file) or an empty
+    /// directory (when `old` is a directory). Return 0 on success, -1 otherwise
+    pub fn rename(&self, old: &str, new_parent: &Inode, new: &str) -> isize {
+        // 跨文件系统无法只移动目录项 (EXDEV)
+        if !Arc::ptr_eq(&self.fs, &new_parent.fs) {
+            return -1;
+        }
+        let mut fs = self.fs.write();
+        let is_special = |name: &str| name.is_empty() || name == "." || name == "..";
+        if new.len() > NAME_LENGTH_LIMIT || is_special(old) || is_special(new) {
+            return -1;
+        }
+        let Some(old_id) = self.read_disk_inode(|root_inode| self.find_inode_id(old, root_inode))
+        else {
+            return -1;
+        };
+        if !new_parent.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return -1;
+        }
+        let moved = self.inode_of(old_id, &fs);
+        let (moved_type, moved_is_dir) =
+            moved.read_disk_inode(|disk_inode| (disk_inode.dirent_type(), disk_inode.is_dir()));
+        let self_id = fs.get_disk_inode_id(self.block_id as u32, self.block_offset);
+        let parent_id = fs.get_disk_inode_id(new_parent.block_id as u32, new_parent.block_offset);
+        if moved_is_dir && parent_id != self_id {
+            // 目录不能移动到自己的子树中：沿着 .. 从新的父目录向上检查
+            let mut cur = parent_id;
+            while cur != 0 {
+                if cur == old_id {
+                    return -1;
+                }
+                let dir = self.inode_of(cur, &fs);
+                match dir.read_disk_inode(|disk_inode| dir.find_inode_id("..", disk_inode)) {
+                    Some(up) if up != cur => cur = up,
+                    _ => break,
+                }
+            }
+        }
+        let target =
+            new_parent.read_disk_inode(|disk_inode| new_parent.find_inode_id(new, disk_inode));
+        let mut replaced = None;
+        if let Some(target_id) = target {
+            if target_id == old_id {
+                return 0;
+            }
+            let inode = self.inode_of(target_id, &fs);
+            let (target_is_dir, target_count) = inode
+                .read_disk_inode(|disk_inode| (disk_inode.is_dir(), inode.dirent_count(disk_inode)));
+            if target_is_dir != moved_is_dir || (target_is_dir && target_count != 0) {
+                return -1;
+            }
+            replaced = Some((target_id, inode));
+        }
+        // 先追加新目录项，磁盘已满时什么都还没有改动
+        let appended = new_parent.modify_disk_inode(|disk_inode| {
+            let dirent = DirEntry::new(new, old_id, moved_type);
+            new_parent.append_dirent(disk_inode, &dirent, &mut fs)
+        });
+        if !appended {
+            return -1;
+        }
+        if let Some((target_id, replaced)) = replaced {
+            // 同名的旧目录项排在刚追加的目录项之前，先被删掉
+            new_parent.modify_disk_inode(|disk_inode| new_parent.remove_dirent(disk_inode, new));
+            // 索引中的同名项随旧目录项一起被删掉，补回刚追加的目录项
+            dir_index::insert(new_parent.dir_key(), new, old_id);
+            // 被替换者的最后一个目录项被删掉时回收它
+            replaced.unlink_locked(target_id, &mut fs);
+        }
+        self.modify_disk_inode(|root_inode| self.remove_dirent(root_inode, old));
+        if moved_is_dir && parent_id != self_id {
+            // 让被移动目录的 .. 指向新的父目录
+            moved.modify_disk_inode(|disk_inode| {
+                let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+                let mut dirent = DirEntry::empty();
                 for i in 0..file_count {
-                    if root_inode.read_at(DIRENT_SZ * i, buf.as_bytes_mut(), &self.block_device) == DIRENT_SZ {
-                        if buf.name() == name {
-                            // we are asked not to delete the node so we overwrite the node
-                            root_inode.read_at(DIRENT_SZ *(file_count - 1), swap.as_bytes_mut(), &self.block_device);
-                            root_inode.write_at(DIRENT_SZ * i, swap.as_bytes_mut(), &self.block_device);
-                            root_inode.size -= DIRENT_SZ as u32;
-                            // unlink one per call
-                            break;
-                        }
+                    disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                    if dirent.name() == ".." {
+                        let dotdot = DirEntry::new("..", parent_id, DirEntryType::Directory);
+                        disk_inode.write_at(DIRENT_SZ * i, dotdot.as_bytes(), &self.block_device);
+                        dir_index::insert(moved.dir_key(), "..", parent_id);
+                        break;
                     }
                 }
             });
-            0
-        } else {
-            // cannot find the file
-            -1
         }
+        block_cache_sync_all();
+        0
     }
 
-    /// get link number of thn given file
-    pub fn get_link_num(&self, block_id: usize, block_offset: usize) -> u32 {
-        let fs = self.fs.lock();
-        let mut count = 0;
-        self.read_disk_inode(|root_inode| {
-            let mut buf = DirEntry::empty();
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            for i in 0..file_count {
-                assert_eq!(
-                    root_inode.read_at(DIRENT_SZ * i, buf.as_bytes_mut(), &self.block_device),
-                    DIRENT_SZ,
-                );
-                let (this_inode_block_id, this_inode_block_offset) = fs.get_disk_inode_pos(buf.inode_id());
-                if this_inode_block_id as usize == block_id && this_inode_block_offset == block_offset {
-                    count += 1;
+    /// Remove the empty directory `name` under current inode, freeing its
+    /// inode and data blocks
+    pub fn rmdir(&self, name: &str) -> Result<(), RemoveError> {
+        let mut fs = self.fs.write();
+        if name == "." || name == ".." {
+            return Err(RemoveError::Invalid);
+        }
+        let dir_id = self
+            .read_disk_inode(|root_inode| self.find_inode_id(name, root_inode))
+            .ok_or(RemoveError::NotFound)?;
+        let dir = self.inode_of(dir_id, &fs);
+        let (is_dir, count) =
+            dir.read_disk_inode(|disk_inode| (disk_inode.is_dir(), dir.dirent_count(disk_inode)));
+        if !is_dir {
+            return Err(RemoveError::NotDir);
+        }
+        if count != 0 {
+            return Err(RemoveError::NotEmpty);
+        }
+        self.modify_disk_inode(|root_inode| self.remove_dirent(root_inode, name));
+        dir.free_locked(dir_id, &mut fs);
+        block_cache_sync_all();
+        Ok(())
+    }
+    /// Remove the entry `name` under current inode together with everything
+    /// below it, freeing their inodes and data blocks
+    pub fn remove_all(&self, name: &str) -> Result<(), RemoveError> {
+        let mut fs = self.fs.write();
+        if name == "." || name == ".." {
+            return Err(RemoveError::Invalid);
+        }
+        let inode_id = self
+            .read_disk_inode(|root_inode| self.find_inode_id(name, root_inode))
+            .ok_or(RemoveError::NotFound)?;
+        let inode = self.inode_of(inode_id, &fs);
+        self.modify_disk_inode(|root_inode| self.remove_dirent(root_inode, name));
+        // 树外还有硬链接指向的文件只删除目录项
+        let linked = self.linked_ids(&fs);
+        inode.free_tree_locked(inode_id, &linked, &mut BTreeSet::new(), &mut fs);
+        block_cache_sync_all();
+        Ok(())
+    }
+    /// Ids of the inodes some directory reachable from the root still holds
+    /// a dirent other than `.` and `..` for
+    fn linked_ids(&self, fs: &EasyFileSystem) -> BTreeSet<u32> {
+        let mut linked = BTreeSet::new();
+        let mut stack = vec![0u32];
+        while let Some(dir_id) = stack.pop() {
+            let dir = self.inode_of(dir_id, fs);
+            dir.read_disk_inode(|disk_inode| {
+                if !disk_inode.is_dir() {
+                    return;
+                }
+                let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+                let mut dirent = DirEntry::empty();
+                for i in 0..file_count {
+                    disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                    if dirent.name() == "." || dirent.name() == ".." {
+                        continue;
+                    }
+                    // 类型未知的目录项出栈时再判断是否为目录
+                    if linked.insert(dirent.inode_id()) && dirent.entry_type() != DirEntryType::File {
+                        stack.push(dirent.inode_id());
+                    }
                 }
+            });
+        }
+        linked
+    }
+    /// Free current inode `inode_id` and, if it is a directory, everything
+    /// below it. Inodes in `linked` are still linked from outside of the tree
+    /// and only lose a link, those reached twice through hard links are freed once
+    fn free_tree_locked(
+        &self,
+        inode_id: u32,
+        linked: &BTreeSet<u32>,
+        freed: &mut BTreeSet<u32>,
+        fs: &mut RwLockWriteGuard<EasyFileSystem>,
+    ) {
+        if linked.contains(&inode_id) {
+            self.modify_disk_inode(|disk_inode| disk_inode.nlink = disk_inode.nlink.saturating_sub(1));
+            return;
+        }
+        if !freed.insert(inode_id) {
+            return;
+        }
+        let children: Vec<u32> = self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return Vec::new();
             }
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut dirent = DirEntry::empty();
+            (0..file_count)
+                .filter_map(|i| {
+                    disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+                    (dirent.name() != "." && dirent.name() != "..").then(|| dirent.inode_id())
+                })
+                .collect()
+        });
+        for child_id in children {
+            self.inode_of(child_id, fs).free_tree_locked(child_id, linked, freed, fs);
+        }
+        self.free_locked(inode_id, fs);
+    }
+    /// Drop one link to current inode `inode_id` after the caller removed a
+    /// dirent referring to it under the fs lock, freeing it with the last one
+    fn unlink_locked(&self, inode_id: u32, fs: &mut RwLockWriteGuard<EasyFileSystem>) {
+        let nlink = self.modify_disk_inode(|disk_inode| {
+            disk_inode.nlink = disk_inode.nlink.saturating_sub(1);
+            disk_inode.nlink
         });
-        count
+        if nlink == 0 {
+            self.free_locked(inode_id, fs);
+        }
+    }
+    /// Free the data blocks and the inode `inode_id` of current inode,
+    /// the caller holds the fs lock and has removed every dirent referring to it
+    fn free_locked(&self, inode_id: u32, fs: &mut RwLockWriteGuard<EasyFileSystem>) {
+        let data_blocks =
+            self.modify_disk_inode(|disk_inode| disk_inode.clear_size(&self.block_device));
+        for data_block in data_blocks {
+            fs.dealloc_data(data_block);
+        }
+        dir_index::forget(self.dir_key());
+        fs.dealloc_inode(inode_id);
     }
+}
 
+impl Drop for Inode {
+    fn drop(&mut self) {
+        // 关闭时写出写合并缓冲中的追加数据
+        self.flush_appends();
+    }
 }
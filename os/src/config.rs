@@ -23,6 +23,26 @@ pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
 pub const CLOCK_FREQ: usize = 12500000;
 /// the physical memory end
 pub const MEMORY_END: usize = 0x88000000;
+/// default max number of open files per task
+pub const DEFAULT_FD_LIMIT: usize = 64;
+/// hard max number of open files per task, setrlimit cannot exceed it
+pub const MAX_FD_LIMIT: usize = 1024;
+/// number of CPUs the scheduler runs tasks on
+pub const CPU_NUM: usize = 1;
+/// affinity mask covering every CPU
+pub const ALL_CPUS_MASK: usize = (1 << CPU_NUM) - 1;
+/// whether stdout of the initial process batches its output line by line
+pub const STDOUT_LINE_BUFFERED: bool = false;
+/// bytes a line buffered stdout holds before flushing without a newline
+pub const STDOUT_BUFFER_SIZE: usize = 256;
+/// cached disk blocks untouched for this many ms are dropped by the timer
+pub const BLOCK_CACHE_TTL_MS: usize = 1000;
+/// capacity of the ring buffer of a pipe in bytes
+pub const PIPE_BUFFER_SIZE: usize = 32;
+/// bytes of a task name, the terminating NUL included
+pub const TASK_COMM_LEN: usize = 16;
+/// max number of components of a path, `.` and `..` included
+pub const MAX_PATH_COMPONENTS: usize = 64;
 /// The base address of control registers in Virtio_Block device
 /// MMIO 地址区间（起始地址，长度）
 pub const MMIO: &[(usize, usize)] = &[(0x10001000, 0x1000)];
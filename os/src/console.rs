@@ -1,14 +1,54 @@
-//! SBI console driver, for text output
+//! Console output, sent to a swappable sink which is the SBI console by default
 use crate::sbi::console_putchar;
+use crate::sync::UPSafeCell;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::fmt::{self, Write};
+use lazy_static::*;
+
+/// Destination of everything written to the console
+pub trait ConsoleSink: Send {
+    /// Output `bytes` in order
+    fn write_bytes(&mut self, bytes: &[u8]);
+}
+
+/// The SBI console of the machine
+pub struct SbiConsole;
+
+impl ConsoleSink for SbiConsole {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            console_putchar(byte as usize);
+        }
+    }
+}
+
+lazy_static! {
+    /// 安装的输出目标，None 表示 SBI 控制台，这样堆初始化之前也能打印
+    static ref CONSOLE_SINK: UPSafeCell<Option<Box<dyn ConsoleSink>>> =
+        unsafe { UPSafeCell::new(None) };
+}
+
+/// Send console output to `sink` from now on, return the sink it replaces
+/// (None for the default SBI console)
+pub fn set_console_sink(sink: Option<Box<dyn ConsoleSink>>) -> Option<Box<dyn ConsoleSink>> {
+    core::mem::replace(&mut *CONSOLE_SINK.exclusive_access(), sink)
+}
+
+/// Write raw `bytes` to the console
+pub fn console_write(bytes: &[u8]) {
+    match CONSOLE_SINK.exclusive_access().as_mut() {
+        Some(sink) => sink.write_bytes(bytes),
+        None => SbiConsole.write_bytes(bytes),
+    }
+}
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
-        }
+        console_write(s.as_bytes());
         Ok(())
     }
 }
@@ -32,3 +72,25 @@ macro_rules! println {
         $crate::console::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?))
     }
 }
+
+/// A sink keeping everything written to it
+struct CaptureSink(Arc<UPSafeCell<Vec<u8>>>);
+
+impl ConsoleSink for CaptureSink {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.0.exclusive_access().extend_from_slice(bytes);
+    }
+}
+
+/// Check that console output reaches the installed sink, the heap must be
+/// ready
+pub fn console_test() {
+    let captured = Arc::new(unsafe { UPSafeCell::new(Vec::new()) });
+    let previous = set_console_sink(Some(Box::new(CaptureSink(Arc::clone(&captured)))));
+    print!("captured {}", 42);
+    println!("!");
+    // 恢复原来的输出目标之后才能把结果打印出来
+    set_console_sink(previous);
+    assert_eq!(captured.exclusive_access().as_slice(), b"captured 42!\n");
+    println!("console_test passed!");
+}
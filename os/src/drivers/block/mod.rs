@@ -4,7 +4,10 @@ mod virtio_blk;
 
 pub use virtio_blk::VirtIOBlock;
 
+use crate::sync::UPSafeCell;
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 use easy_fs::BlockDevice;
 use lazy_static::*;
 
@@ -16,6 +19,24 @@ lazy_static! {
     pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
 }
 
+// 可挂载的块设备表，下标即设备号，0 号为存放根文件系统的 BLOCK_DEVICE
+lazy_static! {
+    static ref BLOCK_DEVICES: UPSafeCell<Vec<Arc<dyn BlockDevice>>> =
+        unsafe { UPSafeCell::new(vec![BLOCK_DEVICE.clone()]) };
+}
+
+/// Register a block device so that it can be mounted, return its device id
+pub fn register_block_device(block_device: Arc<dyn BlockDevice>) -> usize {
+    let mut devices = BLOCK_DEVICES.exclusive_access();
+    devices.push(block_device);
+    devices.len() - 1
+}
+
+/// Get the block device registered with `device_id`
+pub fn get_block_device(device_id: usize) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.exclusive_access().get(device_id).cloned()
+}
+
 #[allow(unused)]
 /// Test the block device
 pub fn block_device_test() {
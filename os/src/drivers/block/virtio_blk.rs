@@ -1,4 +1,5 @@
 use super::BlockDevice;
+use easy_fs::BlockError;
 use crate::mm::{
     frame_alloc, frame_dealloc, kernel_token, FrameTracker, PageTable, PhysAddr, PhysPageNum,
     StepByOne, VirtAddr,
@@ -19,17 +20,17 @@ lazy_static! {
 }
 
 impl BlockDevice for VirtIOBlock {
-    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
         self.0
             .exclusive_access()
             .read_block(block_id, buf)
-            .expect("Error when reading VirtIOBlk");
+            .map_err(|_| BlockError::ReadFailed(block_id))
     }
-    fn write_block(&self, block_id: usize, buf: &[u8]) {
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
         self.0
             .exclusive_access()
             .write_block(block_id, buf)
-            .expect("Error when writing VirtIOBlk");
+            .map_err(|_| BlockError::WriteFailed(block_id))
     }
 }
 
@@ -2,4 +2,4 @@
 
 pub mod block;
 
-pub use block::BLOCK_DEVICE;
+pub use block::{get_block_device, register_block_device, BLOCK_DEVICE};
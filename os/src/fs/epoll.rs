@@ -0,0 +1,127 @@
+//! Epoll instance, a cached set of fds watched for readiness
+use super::File;
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+/// epoll event: there is data to read
+pub const EPOLLIN: u32 = 0x1;
+/// epoll event: writing now will not block
+pub const EPOLLOUT: u32 = 0x4;
+/// epoll_ctl() op: register a new fd
+pub const EPOLL_CTL_ADD: usize = 1;
+/// epoll_ctl() op: stop watching an fd
+pub const EPOLL_CTL_DEL: usize = 2;
+/// epoll_ctl() op: change the events watched on a registered fd
+pub const EPOLL_CTL_MOD: usize = 3;
+
+/// One event reported by epoll_wait(), see epoll_ctl(2)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct EpollEvent {
+    /// Watched events on input, ready events on output
+    pub events: u32,
+    /// User data returned untouched with the events
+    pub data: u64,
+}
+
+/// A registered fd
+struct Interest {
+    /// 不持有文件的引用，文件关闭后自动失效而不会被 epoll 延长生命周期
+    file: Weak<dyn File + Send + Sync>,
+    event: EpollEvent,
+}
+
+/// Fds registered once through epoll_ctl() and checked by each epoll_wait(),
+/// so callers do not pass their whole fd set on every wait as with poll()
+pub struct Epoll {
+    interests: UPSafeCell<BTreeMap<usize, Interest>>,
+}
+
+impl Epoll {
+    /// Create an epoll instance watching nothing
+    pub fn new() -> Self {
+        Self {
+            interests: unsafe { UPSafeCell::new(BTreeMap::new()) },
+        }
+    }
+
+    /// Apply epoll_ctl() `op` to `fd` open as `file`, return 0 or -1 if `op`
+    /// is unknown, `fd` is already registered on add or not registered
+    /// on modify and delete
+    pub fn ctl(&self, op: usize, fd: usize, file: &Arc<dyn File + Send + Sync>, event: EpollEvent) -> isize {
+        let mut interests = self.interests.exclusive_access();
+        // fd 被关闭并复用后，旧的登记不再有效
+        if interests
+            .get(&fd)
+            .is_some_and(|interest| !interest.file.ptr_eq(&Arc::downgrade(file)))
+        {
+            interests.remove(&fd);
+        }
+        match (op, interests.contains_key(&fd)) {
+            (EPOLL_CTL_ADD, false) => {
+                let file = Arc::downgrade(file);
+                interests.insert(fd, Interest { file, event });
+            }
+            (EPOLL_CTL_MOD, true) => interests.get_mut(&fd).unwrap().event = event,
+            (EPOLL_CTL_DEL, true) => {
+                interests.remove(&fd);
+            }
+            _ => return -1,
+        }
+        0
+    }
+
+    /// The ready events of up to `max` registered fds, forgetting fds whose
+    /// file has been closed
+    pub fn ready(&self, max: usize) -> Vec<EpollEvent> {
+        let mut interests = self.interests.exclusive_access();
+        interests.retain(|_, interest| interest.file.strong_count() > 0);
+        let mut ready = Vec::new();
+        for interest in interests.values() {
+            if ready.len() == max {
+                break;
+            }
+            let Some(file) = interest.file.upgrade() else {
+                continue;
+            };
+            let mut events = 0;
+            if interest.event.events & EPOLLIN != 0 && file.readable() && file.read_ready() {
+                events |= EPOLLIN;
+            }
+            if interest.event.events & EPOLLOUT != 0 && file.writable() && file.write_ready() {
+                events |= EPOLLOUT;
+            }
+            if events != 0 {
+                ready.push(EpollEvent {
+                    events,
+                    data: interest.event.data,
+                });
+            }
+        }
+        ready
+    }
+}
+
+impl Default for Epoll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl File for Epoll {
+    fn readable(&self) -> bool {
+        false
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn read(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+}
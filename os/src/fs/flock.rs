@@ -0,0 +1,87 @@
+//! Advisory whole-file locks taken by flock()
+use crate::sync::UPSafeCell;
+use crate::task::WaitQueue;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// flock() operation: take a shared lock
+pub const LOCK_SH: usize = 1;
+/// flock() operation: take an exclusive lock
+pub const LOCK_EX: usize = 2;
+/// flock() flag: fail instead of waiting for a conflicting lock
+pub const LOCK_NB: usize = 4;
+/// flock() operation: release the lock
+pub const LOCK_UN: usize = 8;
+
+/// 以 (文件系统标识, DiskInode 所在块编号, 块内偏移) 标识文件，
+/// 同一文件各自打开得到的 Inode 对象共用一份锁状态
+pub type FlockKey = (usize, usize, usize);
+
+/// Locks held on one file and the tasks waiting for them
+struct FileLocks {
+    /// 持有锁的打开文件（以其地址标识）及其是否为排他锁
+    holders: Vec<(usize, bool)>,
+    waiters: Arc<WaitQueue>,
+}
+
+lazy_static! {
+    /// Lock state of every file someone holds a lock on
+    static ref FLOCKS: UPSafeCell<BTreeMap<FlockKey, FileLocks>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Take a shared or `exclusive` lock on the file `key` for the open file
+/// `owner`, converting the lock `owner` already holds. Wait while another
+/// owner holds a conflicting lock, or return false at once if `nonblock`
+pub fn lock(key: FlockKey, owner: usize, exclusive: bool, nonblock: bool) -> bool {
+    loop {
+        let mut flocks = FLOCKS.exclusive_access();
+        let locks = flocks.entry(key).or_insert_with(|| FileLocks {
+            holders: Vec::new(),
+            waiters: Arc::new(WaitQueue::new()),
+        });
+        let conflict = locks
+            .holders
+            .iter()
+            .any(|&(holder, held_exclusive)| holder != owner && (exclusive || held_exclusive));
+        if !conflict {
+            locks.holders.retain(|&(holder, _)| holder != owner);
+            locks.holders.push((owner, exclusive));
+            return true;
+        }
+        if nonblock {
+            return false;
+        }
+        let waiters = Arc::clone(&locks.waiters);
+        // 转换锁时先放开原来的锁再等待，两个共享锁同时升级才不会互相等死
+        let converting = locks.holders.iter().any(|&(holder, _)| holder == owner);
+        locks.holders.retain(|&(holder, _)| holder != owner);
+        drop(flocks);
+        if converting {
+            waiters.notify_all();
+        }
+        waiters.wait();
+    }
+}
+
+/// Release the lock the open file `owner` holds on the file `key`, if any,
+/// and wake the tasks waiting for a lock on it
+pub fn unlock(key: FlockKey, owner: usize) {
+    let mut flocks = FLOCKS.exclusive_access();
+    let Some(locks) = flocks.get_mut(&key) else {
+        return;
+    };
+    if !locks.holders.iter().any(|&(holder, _)| holder == owner) {
+        return;
+    }
+    locks.holders.retain(|&(holder, _)| holder != owner);
+    let waiters = Arc::clone(&locks.waiters);
+    // 被唤醒的任务重新查找，不存在时另建一份锁状态
+    if locks.holders.is_empty() {
+        flocks.remove(&key);
+    }
+    drop(flocks);
+    waiters.notify_all();
+}
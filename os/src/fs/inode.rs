@@ -1,17 +1,20 @@
 //! `Arc<Inode>` -> `OSInodeInner`: In order to open files concurrently
-//! we need to wrap `Inode` into `Arc`,but `Mutex` in `Inode` prevents
-//! file systems from being accessed simultaneously
+//! we need to wrap `Inode` into `Arc`,but `RwLock` in `Inode` prevents
+//! file systems from being modified simultaneously
 //!
 //! `UPSafeCell<OSInodeInner>` -> `OSInode`: for static `ROOT_INODE`,we
 //! need to wrap `OSInodeInner` into `UPSafeCell`
-use super::File;
+use super::flock::{self, FlockKey};
+use super::overlay::{copy_up, find_or_create_path, resolve, Lookup, WHITEOUT_PREFIX};
+use super::{File, IoStats};
 use crate::drivers::BLOCK_DEVICE;
 use crate::mm::UserBuffer;
 use crate::sync::UPSafeCell;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::*;
-use easy_fs::{EasyFileSystem, Inode};
+use easy_fs::{pin_block, DirEntryType, EasyFileSystem, Inode};
 use lazy_static::*;
 
 /// inode in memory
@@ -22,11 +25,14 @@ pub struct OSInode {
     readable: bool,
     writable: bool,
     inner: UPSafeCell<OSInodeInner>,
+    stats: IoStats,
 }
 /// The OS inode inner in 'UPSafeCell'
 pub struct OSInodeInner {
     offset: usize,
     inode: Arc<Inode>,
+    /// 从头 getdents 时目录项的快照，之后按快照中的位置继续读取
+    dirents: Option<Vec<(String, u32, DirEntryType)>>,
 }
 
 impl OSInode {
@@ -35,7 +41,14 @@ impl OSInode {
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            inner: unsafe {
+                UPSafeCell::new(OSInodeInner {
+                    offset: 0,
+                    inode,
+                    dirents: None,
+                })
+            },
+            stats: IoStats::new(),
         }
     }
     /// read all data from the inode
@@ -54,6 +67,10 @@ impl OSInode {
         v
     }
 
+    /// get the underlying vfs inode
+    pub fn inode(&self) -> Arc<Inode> {
+        self.inner.exclusive_access().inode.clone()
+    }
     /// get current node id
     pub fn get_inode_id(&self) -> u64 {
         let inner = self.inner.exclusive_access();
@@ -64,6 +81,70 @@ impl OSInode {
         let inner = self.inner.exclusive_access();
         (inner.inode.block_id, inner.inode.block_offset)
     }
+    /// read from `offset` without moving the file offset
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.inner.exclusive_access().inode.read_at(offset, buf)
+    }
+    /// read into `buf` from `offset`, or from the file offset and move it if
+    /// `offset` is None, return the number of bytes read
+    pub fn read_from(&self, offset: Option<usize>, buf: &mut [u8]) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let read_size = inner.inode.read_at(offset.unwrap_or(inner.offset), buf);
+        if offset.is_none() {
+            inner.offset += read_size;
+        }
+        self.stats.record_read(read_size);
+        read_size
+    }
+    /// write `buf` at `offset`, or at the file offset and move it if
+    /// `offset` is None, return the number of bytes written
+    pub fn write_to(&self, offset: Option<usize>, buf: &[u8]) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let write_size = inner.inode.write_at(offset.unwrap_or(inner.offset), buf);
+        if offset.is_none() {
+            inner.offset += write_size;
+        }
+        self.stats.record_write(write_size);
+        write_size
+    }
+    /// get the size of the file in bytes
+    pub fn size(&self) -> usize {
+        self.inner.exclusive_access().inode.size() as usize
+    }
+    /// get the permission bits and whether it is a directory
+    pub fn mode(&self) -> (u16, bool) {
+        let inner = self.inner.exclusive_access();
+        (inner.inode.mode(), inner.inode.is_dir())
+    }
+    /// whether the inode has been freed and reused for another file since it was opened
+    pub fn is_stale(&self) -> bool {
+        self.inner.exclusive_access().inode.is_stale()
+    }
+    /// resize the file to `len` bytes, false if it cannot grow that far
+    pub fn truncate(&self, len: usize) -> bool {
+        self.inner.exclusive_access().inode.truncate(len as u32)
+    }
+    /// identify the file on disk for flock(), equal for every open of it
+    fn flock_key(&self) -> FlockKey {
+        let inner = self.inner.exclusive_access();
+        (inner.inode.fs_id(), inner.inode.block_id, inner.inode.block_offset)
+    }
+    /// take a shared or `exclusive` flock() lock for this open file, waiting
+    /// for conflicting locks unless `nonblock`, false if it would wait
+    pub fn lock(&self, exclusive: bool, nonblock: bool) -> bool {
+        flock::lock(self.flock_key(), self as *const Self as usize, exclusive, nonblock)
+    }
+    /// release the flock() lock of this open file, if any
+    pub fn unlock(&self) {
+        flock::unlock(self.flock_key(), self as *const Self as usize);
+    }
+}
+
+impl Drop for OSInode {
+    // 锁属于打开的文件，最后一个引用它的 fd 关闭（包括进程退出）时释放
+    fn drop(&mut self) {
+        self.unlock();
+    }
 }
 
 // 需要从块设备 BLOCK_DEVICE 上打开文件系统，并从文件系统中获取根目录的 inode
@@ -71,7 +152,10 @@ lazy_static! {
     /// The root inode of the filesystem
     pub static ref ROOT_INODE: Arc<Inode> = {
         let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
-        Arc::new(EasyFileSystem::root_inode(&efs))
+        let root_inode = EasyFileSystem::root_inode(&efs);
+        // 每次路径解析都从根目录开始，让它所在的块常驻缓存
+        pin_block(root_inode.block_id, &BLOCK_DEVICE);
+        Arc::new(root_inode)
     };
 }
 
@@ -93,10 +177,26 @@ bitflags! {
         const WRONLY = 1 << 0;
         /// read and write
         const RDWR = 1 << 1;
+        /// fail if the file already exists, used with CREATE
+        const EXCL = 1 << 7;
         /// create new file
         const CREATE = 1 << 9;
         /// truncate file size to 0
         const TRUNC = 1 << 10;
+        /// close the fd on exec
+        const CLOEXEC = 1 << 19;
+    }
+}
+
+bitflags! {
+    /// The mode argument of access(), F_OK is the empty set
+    pub struct AccessFlags: u32 {
+        /// execute or search permission
+        const X_OK = 1 << 0;
+        /// write permission
+        const W_OK = 1 << 1;
+        /// read permission
+        const R_OK = 1 << 2;
     }
 }
 
@@ -114,29 +214,63 @@ impl OpenFlags {
     }
 }
 
-/// Open a file
+/// List the apps in the root directory whose names start with `prefix`
+pub fn list_apps_filtered(prefix: &str) -> Vec<String> {
+    ROOT_INODE.ls_filter(|name| name.starts_with(prefix))
+}
+
+/// Find the inode of `path` by walking its components from the root,
+/// crossing into the filesystems mounted on the way
+pub fn find_path(path: &str) -> Option<Arc<Inode>> {
+    resolve(path).map(Lookup::inode)
+}
+
+/// Whether the owner permission bits of `inode` allow the requested access
+pub fn permits(inode: &Inode, readable: bool, writable: bool) -> bool {
+    let mode = inode.mode();
+    (!readable || mode & 0o400 != 0) && (!writable || mode & 0o200 != 0)
+}
+
+/// Open a file, None if it does not exist or its permission bits
+/// forbid the requested access (EACCES)
 pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
     let (readable, writable) = flags.read_write();
     if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
+        // 查找与创建在同一把文件系统锁下完成，避免两个调用者都认为自己创建了文件
+        let (inode, created) = find_or_create_path(name)?;
+        if !created {
+            if flags.contains(OpenFlags::EXCL) || !permits(&inode, readable, writable) {
+                return None;
+            }
             // clear size
             inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            // create file
-            ROOT_INODE
-                .create(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
         }
+        Some(Arc::new(OSInode::new(readable, writable, inode)))
     } else {
-        ROOT_INODE.find(name).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
-                inode.clear();
-            }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
+        let inode = find_path(name)?;
+        // 先检查权限，避免截断一个只读文件
+        if !permits(&inode, readable, writable) {
+            return None;
+        }
+        // 写打开 overlay 下层的文件前先将其复制到上层
+        let inode = if writable { copy_up(name)? } else { inode };
+        if flags.contains(OpenFlags::TRUNC) {
+            inode.clear();
+        }
+        Some(Arc::new(OSInode::new(readable, writable, inode)))
     }
 }
+/// `d_type` of a dirent whose type is not recorded
+pub const DT_UNKNOWN: u8 = 0;
+/// `d_type` of a directory
+pub const DT_DIR: u8 = 4;
+/// `d_type` of a regular file
+pub const DT_REG: u8 = 8;
+
+/// Size of the fixed part of a `linux_dirent64` record:
+/// d_ino: u64, d_off: i64, d_reclen: u16, d_type: u8
+const DIRENT64_HEADER_SZ: usize = 19;
+
 /// OSInode 也是要一种要放到进程文件描述符表中，通过 sys_read/write 进行读写的文件
 impl File for OSInode {
     fn readable(&self) -> bool {
@@ -157,17 +291,103 @@ impl File for OSInode {
             inner.offset += read_size;
             total_read_size += read_size;
         }
+        self.stats.record_read(total_read_size);
         total_read_size
     }
     fn write(&self, buf: UserBuffer) -> usize {
         let mut inner = self.inner.exclusive_access();
-        let mut total_write_size = 0usize;
-        for slice in buf.buffers.iter() {
-            let write_size = inner.inode.write_at(inner.offset, *slice);
-            assert_eq!(write_size, slice.len());
-            inner.offset += write_size;
-            total_write_size += write_size;
-        }
+        // 直接写入各个页面碎片，不经过中间缓冲区，磁盘满时只写入一部分
+        let total_write_size = inner.inode.write_vectored_at(inner.offset, &buf.buffers);
+        inner.offset += total_write_size;
+        self.stats.record_write(total_write_size);
         total_write_size
     }
+    fn read_until(&self, mut buf: UserBuffer, delim: u8) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            // 整片读入后在其中查找分隔符，offset 只前进到分隔符之后
+            let read_size = inner.inode.read_at(inner.offset, slice);
+            let (read_size, found) = match slice[..read_size].iter().position(|b| *b == delim) {
+                Some(pos) => (pos + 1, true),
+                None => (read_size, false),
+            };
+            inner.offset += read_size;
+            total_read_size += read_size;
+            if found || read_size < slice.len() {
+                break;
+            }
+        }
+        self.stats.record_read(total_read_size);
+        total_read_size
+    }
+    fn pread(&self, mut buf: UserBuffer, offset: usize) -> isize {
+        let inner = self.inner.exclusive_access();
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = inner.inode.read_at(offset + total_read_size, slice);
+            total_read_size += read_size;
+            if read_size < slice.len() {
+                break;
+            }
+        }
+        self.stats.record_read(total_read_size);
+        total_read_size as isize
+    }
+    fn pwrite(&self, buf: UserBuffer, offset: usize) -> isize {
+        let inner = self.inner.exclusive_access();
+        let write_size = inner.inode.write_vectored_at(offset, &buf.buffers);
+        self.stats.record_write(write_size);
+        write_size as isize
+    }
+    fn getdents(&self, mut buf: UserBuffer) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        if !inner.inode.is_dir() {
+            return -1;
+        }
+        // unlink 会把最后一个目录项移到空位，按位置逐项读取可能漏读或重读，
+        // 因此从头读取时记下全部目录项，offset 是快照中下一项的序号
+        if inner.offset == 0 || inner.dirents.is_none() {
+            inner.dirents = Some(inner.inode.dirents());
+        }
+        let inner = &mut *inner;
+        let dirents = inner.dirents.as_ref().unwrap();
+        let mut records: Vec<u8> = Vec::new();
+        while let Some((name, inode_id, entry_type)) = dirents.get(inner.offset) {
+            if name.starts_with(WHITEOUT_PREFIX) {
+                inner.offset += 1;
+                continue;
+            }
+            // 名字以 \0 结尾，整条记录按 8 字节对齐
+            let reclen = (DIRENT64_HEADER_SZ + name.len() + 1 + 7) & !7;
+            if records.len() + reclen > buf.len() {
+                // 记录不会被截断，放不下的留给下一次调用
+                break;
+            }
+            let d_type = match entry_type {
+                DirEntryType::File => DT_REG,
+                DirEntryType::Directory => DT_DIR,
+                DirEntryType::Unknown => DT_UNKNOWN,
+            };
+            let start = records.len();
+            records.extend_from_slice(&(*inode_id as u64).to_le_bytes());
+            records.extend_from_slice(&(inner.offset as i64 + 1).to_le_bytes());
+            records.extend_from_slice(&(reclen as u16).to_le_bytes());
+            records.push(d_type);
+            records.extend_from_slice(name.as_bytes());
+            records.resize(start + reclen, 0);
+            inner.offset += 1;
+        }
+        if records.is_empty() && inner.offset < dirents.len() {
+            // 缓冲区连一条记录都放不下
+            return -1;
+        }
+        buf.copy_from_slice(&records) as isize
+    }
+    fn flush(&self) {
+        self.inner.exclusive_access().inode.flush_appends();
+    }
+    fn io_stats(&self) -> (u64, u64) {
+        self.stats.get()
+    }
 }
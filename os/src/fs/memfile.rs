@@ -0,0 +1,113 @@
+//! In-memory file which never touches the disk
+use super::{File, IoStats, Stat, StatMode, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+
+/// A readable and writable file whose data lives in a growable buffer
+pub struct MemFile {
+    inner: UPSafeCell<MemFileInner>,
+    stats: IoStats,
+}
+
+/// The mem file inner in 'UPSafeCell'
+pub struct MemFileInner {
+    offset: usize,
+    data: Vec<u8>,
+}
+
+impl MemFile {
+    /// Create an empty mem file
+    pub fn new() -> Self {
+        Self {
+            inner: unsafe {
+                UPSafeCell::new(MemFileInner {
+                    offset: 0,
+                    data: Vec::new(),
+                })
+            },
+            stats: IoStats::new(),
+        }
+    }
+    /// Current size of the file in bytes
+    pub fn size(&self) -> usize {
+        self.inner.exclusive_access().data.len()
+    }
+}
+
+impl Default for MemFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl File for MemFile {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let start = inner.offset.min(inner.data.len());
+        let read_size = buf.copy_from_slice(&inner.data[start..]);
+        inner.offset = start + read_size;
+        self.stats.record_read(read_size);
+        read_size
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let len = buf.len();
+        let start = inner.offset;
+        // offset 越过文件末尾时，中间部分补零
+        if inner.data.len() < start + len {
+            inner.data.resize(start + len, 0);
+        }
+        let write_size = buf.copy_into_slice(&mut inner.data[start..start + len]);
+        inner.offset += write_size;
+        self.stats.record_write(write_size);
+        write_size
+    }
+    fn pread(&self, mut buf: UserBuffer, offset: usize) -> isize {
+        let inner = self.inner.exclusive_access();
+        let start = offset.min(inner.data.len());
+        let read_size = buf.copy_from_slice(&inner.data[start..]);
+        self.stats.record_read(read_size);
+        read_size as isize
+    }
+    fn pwrite(&self, buf: UserBuffer, offset: usize) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        let len = buf.len();
+        if inner.data.len() < offset + len {
+            inner.data.resize(offset + len, 0);
+        }
+        let write_size = buf.copy_into_slice(&mut inner.data[offset..offset + len]);
+        self.stats.record_write(write_size);
+        write_size as isize
+    }
+    fn seek(&self, offset: isize, whence: usize) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => inner.offset as isize,
+            SEEK_END => inner.data.len() as isize,
+            _ => return -1,
+        };
+        let new_offset = base + offset;
+        if new_offset < 0 {
+            return -1;
+        }
+        inner.offset = new_offset as usize;
+        new_offset
+    }
+    fn fstat(&self, stat: &mut Stat) -> isize {
+        stat.mode = StatMode::FILE;
+        stat.nlink = 1;
+        stat.size = self.size() as u64;
+        0
+    }
+    fn io_stats(&self) -> (u64, u64) {
+        self.stats.get()
+    }
+}
@@ -1,8 +1,20 @@
 //! File trait & inode(dir, file, pipe, stdin, stdout)
 
+mod epoll;
+mod flock;
 mod inode;
+mod memfile;
+mod mount;
+mod overlay;
+mod pipe;
+mod procfs;
 mod stdio;
 use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use crate::config::BLOCK_CACHE_TTL_MS;
+use crate::task::{current_task, suspend_current_and_run_next};
+use crate::timer::get_time_ms;
+use alloc::vec;
 
 /// trait File for all file types
 /// 接口在内存和I/O资源之间建立了数据交换的通道
@@ -24,8 +36,126 @@ pub trait File: Send + Sync + AnyConvertor{
     fn fstat(&self, stat: &mut Stat) -> isize {
         -1
     }
+
+    #[allow(unused_variables)]
+    /// move the offset of the file according to `whence`, return the new offset
+    fn seek(&self, offset: isize, whence: usize) -> isize {
+        -1
+    }
+
+    #[allow(unused_variables)]
+    /// read from `offset` to buf without moving the file offset,
+    /// return the number of bytes read, -1 if the file is not seekable
+    fn pread(&self, buf: UserBuffer, offset: usize) -> isize {
+        -1
+    }
+
+    #[allow(unused_variables)]
+    /// write buf at `offset` without moving the file offset,
+    /// return the number of bytes written, -1 if the file is not seekable
+    fn pwrite(&self, buf: UserBuffer, offset: usize) -> isize {
+        -1
+    }
+
+    /// read from the file to buf until `delim` has been read or buf is full,
+    /// return the number of bytes read, `delim` included
+    fn read_until(&self, buf: UserBuffer, delim: u8) -> usize {
+        let mut read_size = 0usize;
+        for byte_ref in buf {
+            // 逐字节读取，不会越过分隔符多读
+            let byte = unsafe { core::slice::from_raw_parts_mut(byte_ref, 1) };
+            if self.read(UserBuffer::new(vec![byte])) == 0 {
+                break;
+            }
+            read_size += 1;
+            if unsafe { *byte_ref } == delim {
+                break;
+            }
+        }
+        read_size
+    }
+
+    #[allow(unused_variables)]
+    /// read dirents from the file offset into buf as `linux_dirent64` records,
+    /// return the number of bytes filled, -1 if the file is not a directory
+    fn getdents(&self, buf: UserBuffer) -> isize {
+        -1
+    }
+
+    /// send out data the file still holds in memory, e.g. a buffered line
+    fn flush(&self) {}
+
+    /// total bytes (read, written) through this file so far
+    fn io_stats(&self) -> (u64, u64) {
+        (0, 0)
+    }
+
+    /// whether a read would return without blocking
+    fn read_ready(&self) -> bool {
+        true
+    }
+
+    /// whether a write would return without blocking
+    fn write_ready(&self) -> bool {
+        true
+    }
+}
+
+/// Bytes read and written through one file, for I/O accounting
+pub struct IoStats(UPSafeCell<(u64, u64)>);
+
+impl IoStats {
+    /// Create zeroed counters
+    pub fn new() -> Self {
+        Self(unsafe { UPSafeCell::new((0, 0)) })
+    }
+    /// Count `len` bytes read
+    pub fn record_read(&self, len: usize) {
+        self.0.exclusive_access().0 += len as u64;
+    }
+    /// Count `len` bytes written
+    pub fn record_write(&self, len: usize) {
+        self.0.exclusive_access().1 += len as u64;
+    }
+    /// Bytes (read, written) so far
+    pub fn get(&self) -> (u64, u64) {
+        *self.0.exclusive_access()
+    }
+}
+
+impl Default for IoStats {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+/// Initialize the filesystem: waiters of the fs lock switch to another task
+/// instead of spinning while the holder is descheduled
+pub fn init() {
+    easy_fs::set_lock_yield(yield_fs_lock);
+}
+
+/// Drop the disk blocks cached but not accessed for
+/// [`BLOCK_CACHE_TTL_MS`], called on timer interrupts. Skipped when the
+/// block cache is locked by the interrupted code
+pub fn sweep_cold_blocks() {
+    easy_fs::try_sweep_block_cache(get_time_ms(), BLOCK_CACHE_TTL_MS);
+}
+
+fn yield_fs_lock() {
+    // 启动阶段还没有当前任务，只能继续自旋
+    if current_task().is_some() {
+        suspend_current_and_run_next();
+    }
+}
+
+/// seek relative to the start of the file
+pub const SEEK_SET: usize = 0;
+/// seek relative to the current offset
+pub const SEEK_CUR: usize = 1;
+/// seek relative to the end of the file
+pub const SEEK_END: usize = 2;
+
 use core::any::Any;
 
 /// convert current type to &dyn Any
@@ -52,8 +182,36 @@ pub struct Stat {
     pub mode: StatMode,
     /// number of hard links
     pub nlink: u32,
+    /// total size in bytes
+    pub size: u64,
+    /// user id of the owner
+    pub uid: u32,
+    /// group id of the owner
+    pub gid: u32,
+    /// last access time in seconds
+    pub atime: u64,
+    /// last modification time in seconds
+    pub mtime: u64,
     /// unused pad
-    pub pad: [u64; 7],
+    pub pad: [u64; 3],
+}
+
+/// The size and usage of a filesystem
+#[repr(C)]
+#[derive(Debug)]
+pub struct StatFs {
+    /// size of a block in bytes
+    pub bsize: u64,
+    /// total data blocks
+    pub blocks: u64,
+    /// free data blocks
+    pub bfree: u64,
+    /// total inodes
+    pub files: u64,
+    /// free inodes
+    pub ffree: u64,
+    /// max size of a file in bytes
+    pub maxfilesize: u64,
 }
 
 bitflags! {
@@ -66,8 +224,17 @@ bitflags! {
         const DIR   = 0o040000;
         /// ordinary regular file
         const FILE  = 0o100000;
+        /// permission bits rwxrwxrwx
+        const PERM  = 0o777;
     }
 }
 
-pub use inode::{list_apps, open_file, OSInode, OpenFlags, ROOT_INODE};
+pub use epoll::{Epoll, EpollEvent, EPOLLIN, EPOLLOUT, EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD};
+pub use flock::{LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN};
+pub use inode::{find_path, DT_DIR, DT_REG, DT_UNKNOWN, list_apps, list_apps_filtered, open_file, permits, AccessFlags, OSInode, OpenFlags, ROOT_INODE};
+pub use memfile::MemFile;
+pub use mount::{mount, mounted_root, sync_all, umount, MountFlags, MountPoint};
+pub use overlay::{copy_up, find_or_create_path, link_path, path_too_deep, remove, remove_dir, rename_path, split_path, resolve, with_start_dir, Lookup, WHITEOUT_PREFIX};
+pub use procfs::{open_proc, ProcStatus, PROC_ROOT};
+pub use pipe::{make_pipe, make_pipe_with_capacity, Pipe, PipeRingBuffer};
 pub use stdio::{Stdin, Stdout};
@@ -0,0 +1,134 @@
+//! Mount table grafting the root of another easy-fs onto a directory
+use crate::drivers::{get_block_device, BLOCK_DEVICE};
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use bitflags::*;
+use easy_fs::{try_block_cache_sync_all, EasyFileSystem, Inode};
+use lazy_static::*;
+
+bitflags! {
+    /// The flags argument of mount()
+    pub struct MountFlags: u32 {
+        /// mount as a read-only lower layer under the target directory
+        const OVERLAY = 1 << 0;
+    }
+}
+
+/// A filesystem mounted on a directory
+pub struct MountPoint {
+    /// id of the block device holding the filesystem
+    pub device_id: usize,
+    /// root inode of the mounted filesystem
+    pub root: Arc<Inode>,
+    /// whether the filesystem is a read-only lower layer under the mount
+    /// point directory instead of replacing it
+    pub overlay: bool,
+}
+
+/// 以挂载点目录在其所属文件系统中的位置为键
+type MountKey = (usize, usize, usize);
+
+fn mount_key(inode: &Inode) -> MountKey {
+    (inode.fs_id(), inode.block_id, inode.block_offset)
+}
+
+lazy_static! {
+    /// Mounted filesystems indexed by their mount point directory
+    static ref MOUNT_TABLE: UPSafeCell<BTreeMap<MountKey, MountPoint>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// The root of the filesystem mounted on `inode` and whether it is an overlay, if any
+pub fn mounted_root(inode: &Inode) -> Option<(Arc<Inode>, bool)> {
+    MOUNT_TABLE
+        .exclusive_access()
+        .get(&mount_key(inode))
+        .map(|mount_point| (mount_point.root.clone(), mount_point.overlay))
+}
+
+/// Mount the easy-fs on block device `device_id` at directory `target`,
+/// as a lower layer under `target` if `overlay`
+pub fn mount(device_id: usize, target: Arc<Inode>, overlay: bool) -> isize {
+    // 0 号设备是根文件系统；同一设备打开两次会各自维护位图，必须拒绝
+    if device_id == 0 || !target.is_dir() {
+        return -1;
+    }
+    let Some(block_device) = get_block_device(device_id) else {
+        return -1;
+    };
+    let mut table = MOUNT_TABLE.exclusive_access();
+    let key = mount_key(&target);
+    if table.contains_key(&key) || table.values().any(|mp| mp.device_id == device_id) {
+        return -1;
+    }
+    // 镜像损坏或与设备不符时挂载失败，而不是让内核 panic
+    let Ok(efs) = EasyFileSystem::try_open(block_device) else {
+        return -1;
+    };
+    let root = Arc::new(EasyFileSystem::root_inode(&efs));
+    table.insert(
+        key,
+        MountPoint {
+            device_id,
+            root,
+            overlay,
+        },
+    );
+    0
+}
+
+/// Unmount the filesystem whose root is `target`, or which is overlaid under
+/// the directory `target`. Fails if there is no such mount or some of its
+/// inodes are still in use (e.g. open files)
+pub fn umount(target: Arc<Inode>) -> isize {
+    let mut table = MOUNT_TABLE.exclusive_access();
+    let target_key = mount_key(&target);
+    let Some(key) = table
+        .iter()
+        .find(|(key, mp)| {
+            mount_key(&mp.root) == target_key || (mp.overlay && **key == target_key)
+        })
+        .map(|(key, _)| *key)
+    else {
+        return -1;
+    };
+    drop(target);
+    // 只剩挂载表里的根 inode 引用该文件系统时才允许卸载
+    let mounted = &table[&key].root;
+    if Arc::strong_count(mounted) != 1 || mounted.fs_users() != 1 {
+        return -1;
+    }
+    if try_block_cache_sync_all().is_err() {
+        return -1;
+    }
+    let device = get_block_device(table[&key].device_id).unwrap();
+    if device.flush().is_err() {
+        return -1;
+    }
+    table.remove(&key);
+    0
+}
+
+/// Write every dirty cached block back and flush the root device and the
+/// devices of all mounted filesystems, -1 if some device fails
+pub fn sync_all() -> isize {
+    // 块缓存由所有文件系统共享，一次写回即覆盖全部设备
+    if try_block_cache_sync_all().is_err() {
+        return -1;
+    }
+    let table = MOUNT_TABLE.exclusive_access();
+    let mounted = table
+        .values()
+        .filter_map(|mount_point| get_block_device(mount_point.device_id));
+    let mut failed = false;
+    for device in core::iter::once(BLOCK_DEVICE.clone()).chain(mounted) {
+        // 一个设备失败时仍然刷新其余设备
+        failed |= device.flush().is_err();
+    }
+    if failed {
+        -1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,337 @@
+//! Path resolution across mounts, with overlay mounts merging an upper
+//! writable directory and a lower read-only filesystem
+use super::inode::ROOT_INODE;
+use super::mount::mounted_root;
+use crate::config::MAX_PATH_COMPONENTS;
+use crate::task::current_task;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use easy_fs::{Inode, RemoveError, BLOCK_SZ};
+
+/// Prefix of the upper layer entry recording that a lower layer entry was deleted
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// Where a path was found
+pub enum Lookup {
+    /// in the writable upper layer, or outside of any overlay
+    Upper(Arc<Inode>),
+    /// only in the read-only lower layer of an overlay
+    Lower(Arc<Inode>),
+}
+
+impl Lookup {
+    /// The inode found, whichever layer it is in
+    pub fn inode(self) -> Arc<Inode> {
+        match self {
+            Self::Upper(inode) | Self::Lower(inode) => inode,
+        }
+    }
+}
+
+fn whiteout_name(name: &str) -> String {
+    format!("{}{}", WHITEOUT_PREFIX, name)
+}
+
+/// Split `path` into its parent path and last component. The parent of a
+/// top-level absolute path is "/", that of a single relative name is "",
+/// which resolves to the cwd
+pub fn split_path(path: &str) -> Option<(&str, &str)> {
+    let path = path.trim_end_matches('/');
+    let (parent, name) = match path.rsplit_once('/') {
+        // "/foo" 的父目录是根目录，不能变成相对路径 ""
+        Some(("", name)) => ("/", name),
+        Some(pair) => pair,
+        None => ("", path),
+    };
+    if name.is_empty() {
+        None
+    } else {
+        Some((parent, name))
+    }
+}
+
+/// Whether `path` has more components than path resolution accepts
+pub fn path_too_deep(path: &str) -> bool {
+    path.split('/').filter(|name| !name.is_empty()).count() > MAX_PATH_COMPONENTS
+}
+
+/// Enter the filesystem mounted on the directory `upper`, return the new (upper, lower)
+fn cross_mount(
+    upper: Arc<Inode>,
+    lower: Option<Arc<Inode>>,
+) -> (Arc<Inode>, Option<Arc<Inode>>) {
+    match mounted_root(&upper) {
+        // overlay 挂载：挂载点目录作为 upper，被挂载的根作为 lower
+        Some((root, true)) => (upper, Some(root)),
+        Some((root, false)) => (root, None),
+        None => (upper, lower),
+    }
+}
+
+/// The root directory of the current task set by chroot(), the filesystem
+/// root before any task runs
+fn root_dir() -> Arc<Inode> {
+    current_task()
+        .map(|task| task.inner_exclusive_access().root.clone())
+        .unwrap_or_else(|| ROOT_INODE.clone())
+}
+
+/// The directory a path starts from: the root of the current task for
+/// absolute paths, otherwise its cwd (the root before any task runs)
+fn start_dir(path: &str) -> Arc<Inode> {
+    if path.starts_with('/') {
+        return root_dir();
+    }
+    current_task()
+        .map(|task| task.inner_exclusive_access().cwd.clone())
+        .unwrap_or_else(|| ROOT_INODE.clone())
+}
+
+/// Whether `a` and `b` are the same file on disk
+fn same_inode(a: &Inode, b: &Inode) -> bool {
+    a.fs_id() == b.fs_id() && a.block_id == b.block_id && a.block_offset == b.block_offset
+}
+
+/// Run `f` with relative paths resolved from `dir` instead of the cwd of
+/// the current task, as the *at() syscalls do for their directory fd
+pub fn with_start_dir<T>(dir: Arc<Inode>, f: impl FnOnce() -> T) -> T {
+    let task = current_task().unwrap();
+    // 临时替换当前任务的 cwd，任务在 f 中让出 CPU 时也不影响其它任务
+    let cwd = core::mem::replace(&mut task.inner_exclusive_access().cwd, dir);
+    let ret = f();
+    task.inner_exclusive_access().cwd = cwd;
+    ret
+}
+
+/// Resolve `path` from the root or the cwd, crossing mounts. Inside an overlay
+/// the upper layer shadows the lower one and whiteouts hide lower entries
+pub fn resolve(path: &str) -> Option<Lookup> {
+    match resolve_layers(path)? {
+        (Some(inode), _) => Some(Lookup::Upper(inode)),
+        (None, Some(inode)) => Some(Lookup::Lower(inode)),
+        (None, None) => None,
+    }
+}
+
+/// 路径在上层的 inode 与合并在其下的下层 inode
+type Layers = (Option<Arc<Inode>>, Option<Arc<Inode>>);
+
+/// Resolve `path` into its upper inode and the lower directory merged under it
+fn resolve_layers(path: &str) -> Option<Layers> {
+    // 每个分量（包括 . 和 ..）都计数，来回跳转的路径也会在上限处失败
+    if path_too_deep(path) {
+        return None;
+    }
+    // cwd 只记录 inode，位于 overlay 子目录中时相对路径只能看到上层
+    let (start, start_lower) = cross_mount(start_dir(path), None);
+    // 根目录上挂载了文件系统时，进入后的根同样视为任务的根
+    let root = root_dir();
+    let (mounted_root, _) = cross_mount(root.clone(), None);
+    let mut upper = Some(start);
+    let mut lower = start_lower;
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        // 在任务的根目录处 .. 停留在根目录，chroot 后无法向上逃出
+        if name == ".."
+            && upper
+                .as_ref()
+                .is_some_and(|dir| same_inode(dir, &root) || same_inode(dir, &mounted_root))
+        {
+            continue;
+        }
+        let hidden = upper
+            .as_ref()
+            .is_some_and(|dir| dir.find(&whiteout_name(name)).is_some());
+        // 中间分量是普通文件时 find 返回 None
+        let next_upper = upper.as_ref().and_then(|dir| dir.find(name));
+        let next_lower = match &lower {
+            Some(dir) if !hidden => dir.find(name),
+            _ => None,
+        };
+        (upper, lower) = match next_upper {
+            // 两层的同名目录合并，upper 中的普通文件则遮蔽 lower
+            Some(inode) => {
+                let next_lower = next_lower.filter(|dir| dir.is_dir() && inode.is_dir());
+                let (inode, next_lower) = cross_mount(inode, next_lower);
+                (Some(inode), next_lower)
+            }
+            None => (None, next_lower),
+        };
+        if upper.is_none() && lower.is_none() {
+            return None;
+        }
+    }
+    Some((upper, lower))
+}
+
+/// Resolve `path` for writing: a file or directory found only in a lower
+/// layer is first copied up, together with its missing parent directories
+pub fn copy_up(path: &str) -> Option<Arc<Inode>> {
+    match resolve(path)? {
+        Lookup::Upper(inode) => Some(inode),
+        Lookup::Lower(lower) => {
+            let (parent, name) = split_path(path)?;
+            let dir = copy_up(parent)?;
+            let inode = if lower.is_dir() {
+                dir.create_dir(name)?
+            } else {
+                let inode = dir.create(name)?;
+                let mut buf = [0u8; BLOCK_SZ];
+                let mut offset = 0;
+                loop {
+                    let len = lower.read_at(offset, &mut buf);
+                    if len == 0 {
+                        break;
+                    }
+                    if inode.write_at(offset, &buf[..len]) < len {
+                        // 上层放不下整个文件，撤销复制
+                        dir.unlink(name);
+                        return None;
+                    }
+                    offset += len;
+                }
+                inode
+            };
+            inode.set_mode(lower.mode());
+            Some(inode)
+        }
+    }
+}
+
+/// Find `path` in the upper layer, creating it there if not found,
+/// return (inode, created)
+pub fn find_or_create_path(path: &str) -> Option<(Arc<Inode>, bool)> {
+    let (parent, name) = split_path(path)?;
+    // 仅存在于下层的文件先复制到上层，保留其权限位
+    if let Some(Lookup::Lower(_)) = resolve(path) {
+        return copy_up(path).map(|inode| (inode, false));
+    }
+    copy_up(parent)?.find_or_create(name)
+}
+
+/// Remove the file `path`, recording a whiteout in the upper layer if a lower
+/// layer still provides the same name. Directories are left to `remove_dir`
+pub fn remove(path: &str) -> isize {
+    let Some((parent, name)) = split_path(path) else {
+        return -1;
+    };
+    let (inode, in_upper) = match resolve(path) {
+        Some(Lookup::Upper(inode)) => (inode, true),
+        Some(Lookup::Lower(inode)) => (inode, false),
+        None => return -1,
+    };
+    if inode.is_dir() {
+        return -1;
+    }
+    if in_upper {
+        let Some(Lookup::Upper(dir)) = resolve(parent) else {
+            return -1;
+        };
+        // 删除最后一个链接时 unlink 会回收 inode 和数据块
+        if dir.unlink(name) != 0 {
+            return -1;
+        }
+    }
+    hide_lower(path, parent, name)
+}
+
+/// Move `old` to `new` in the upper layer, a file found only in a lower
+/// layer is copied up first and then hidden there by a whiteout
+pub fn rename_path(old: &str, new: &str) -> isize {
+    let (Some((old_parent, old_name)), Some((new_parent, new_name))) =
+        (split_path(old), split_path(new))
+    else {
+        return -1;
+    };
+    match resolve(old) {
+        // 下层目录无法整体复制到上层 (EXDEV)
+        Some(Lookup::Lower(inode)) if inode.is_dir() => return -1,
+        Some(_) => {}
+        None => return -1,
+    }
+    let (Some(_), Some(from), Some(to)) = (copy_up(old), copy_up(old_parent), copy_up(new_parent))
+    else {
+        return -1;
+    };
+    if from.rename(old_name, &to, new_name) != 0 {
+        return -1;
+    }
+    hide_lower(old, old_parent, old_name)
+}
+
+/// Add the hard link `new` to the file `old` in the upper layer, a file
+/// found only in a lower layer is copied up first
+pub fn link_path(old: &str, new: &str) -> isize {
+    let Some((new_parent, new_name)) = split_path(new) else {
+        return -1;
+    };
+    if !resolve(old).is_some_and(|lookup| !lookup.inode().is_dir()) {
+        return -1;
+    }
+    // 新名字在任意一层已存在都不能创建
+    if resolve(new).is_some() {
+        return -1;
+    }
+    let (Some(target), Some(dir)) = (copy_up(old), copy_up(new_parent)) else {
+        return -1;
+    };
+    dir.link_inode(&target, new_name)
+}
+
+/// Remove the empty directory `path`. A directory merged with a lower layer
+/// must be empty in both layers, and is hidden by a whiteout afterwards
+pub fn remove_dir(path: &str) -> Result<(), RemoveError> {
+    let (parent, name) = split_path(path).ok_or(RemoveError::Invalid)?;
+    if name == "." || name == ".." {
+        return Err(RemoveError::Invalid);
+    }
+    let (upper, lower) = resolve_layers(path).ok_or(RemoveError::NotFound)?;
+    if upper.iter().chain(lower.iter()).any(|dir| !dir.is_dir()) {
+        return Err(RemoveError::NotDir);
+    }
+    let is_entry = |name: &String| name != "." && name != "..";
+    // upper 中只剩 whiteout 时视为空；lower 中被 whiteout 隐藏的项也不算
+    let upper_entries = upper.as_ref().map_or(0, |dir| {
+        dir.ls_filter(|name| !name.starts_with(WHITEOUT_PREFIX))
+            .iter()
+            .filter(|name| is_entry(name))
+            .count()
+    });
+    let lower_entries = lower.as_ref().map_or(0, |dir| {
+        dir.ls()
+            .iter()
+            .filter(|name| is_entry(name))
+            .filter(|name| {
+                let hidden = upper.as_ref().and_then(|up| up.find(&whiteout_name(name)));
+                hidden.is_none()
+            })
+            .count()
+    });
+    if upper_entries + lower_entries != 0 {
+        return Err(RemoveError::NotEmpty);
+    }
+    if upper.is_some() {
+        let Some(Lookup::Upper(dir)) = resolve(parent) else {
+            return Err(RemoveError::NotFound);
+        };
+        // 连同其中的 whiteout 一起删除
+        dir.remove_all(name)?;
+    }
+    if hide_lower(path, parent, name) != 0 {
+        return Err(RemoveError::NotFound);
+    }
+    Ok(())
+}
+
+/// Record a whiteout for `name` under `parent` if a lower layer still provides `path`
+fn hide_lower(path: &str, parent: &str, name: &str) -> isize {
+    if let Some(Lookup::Lower(_)) = resolve(path) {
+        let Some(dir) = copy_up(parent) else {
+            return -1;
+        };
+        if dir.create(&whiteout_name(name)).is_none() {
+            return -1;
+        }
+    }
+    0
+}
@@ -0,0 +1,474 @@
+//! Anonymous pipe backed by a bounded ring buffer
+use super::{File, IoStats};
+use crate::config::PIPE_BUFFER_SIZE;
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::task::{suspend_current_and_run_next, TICK_QUEUE};
+#[cfg(feature = "deadlock_detect")]
+use crate::task::{block_on_pipe, current_task, unblock};
+
+/// One end of a pipe, either readable or writable
+pub struct Pipe {
+    readable: bool,
+    writable: bool,
+    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    stats: IoStats,
+}
+
+impl Pipe {
+    /// Create the read end of a pipe from a ring buffer
+    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+        Self {
+            readable: true,
+            writable: false,
+            buffer,
+            stats: IoStats::new(),
+        }
+    }
+    /// Create the write end of a pipe from a ring buffer
+    pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+        Self {
+            readable: false,
+            writable: true,
+            buffer,
+            stats: IoStats::new(),
+        }
+    }
+    /// Whether current pipe is the read end (if `read`) or the write end of `buffer`
+    #[cfg(feature = "deadlock_detect")]
+    pub fn is_end_of(&self, buffer: &Arc<UPSafeCell<PipeRingBuffer>>, read: bool) -> bool {
+        Arc::ptr_eq(&self.buffer, buffer) && self.readable == read
+    }
+    /// Record that the current task blocks on this pipe for the deadlock detector
+    #[cfg(feature = "deadlock_detect")]
+    fn block(&self, blocked: &mut bool) {
+        if !*blocked {
+            *blocked = true;
+            block_on_pipe(current_task().unwrap().getpid(), &self.buffer, self.readable);
+        }
+    }
+    /// The current task goes on after blocking on this pipe
+    #[cfg(feature = "deadlock_detect")]
+    fn unblock(&self, blocked: bool) {
+        if blocked {
+            unblock(current_task().unwrap().getpid());
+        }
+    }
+    /// Whether `self` and `other` are ends of the same pipe
+    pub fn same_pipe(&self, other: &Pipe) -> bool {
+        Arc::ptr_eq(&self.buffer, &other.buffer)
+    }
+    /// Copy up to `len` bytes buffered in the pipe of read end `self` into
+    /// the pipe of write end `out`, another pipe, leaving them readable from
+    /// `self`. Wait until there is data to copy and room for it unless
+    /// `nonblock`. Return the number of bytes copied, 0 once `self` is empty
+    /// with all write ends closed, or None if `nonblock` and it would wait
+    pub fn tee(&self, out: &Pipe, len: usize, nonblock: bool) -> Option<usize> {
+        assert!(self.readable() && out.writable() && !self.same_pipe(out));
+        if len == 0 {
+            return Some(0);
+        }
+        loop {
+            let input = self.buffer.exclusive_access();
+            let available = input.available_read();
+            if available == 0 && input.all_write_ends_closed() {
+                return Some(0);
+            }
+            let mut output = out.buffer.exclusive_access();
+            let room = output.available_write();
+            if available > 0 && room > 0 {
+                let copied = len.min(available).min(room);
+                // 只查看输入缓冲区中的数据，不移动其读指针
+                for i in 0..copied {
+                    output.write_byte(input.peek_byte(i));
+                }
+                out.stats.record_write(copied);
+                return Some(copied);
+            }
+            drop(output);
+            drop(input);
+            if nonblock {
+                return None;
+            }
+            suspend_current_and_run_next();
+        }
+    }
+    /// Copy the bytes buffered in the pipe into `buf` without consuming
+    /// them, so the next read returns the same bytes. Wait until there is
+    /// something buffered, return the number of bytes copied, 0 once the
+    /// pipe is empty with all write ends closed
+    pub fn peek(&self, buf: UserBuffer) -> usize {
+        assert!(self.readable());
+        loop {
+            let ring_buffer = self.buffer.exclusive_access();
+            let available = ring_buffer.available_read();
+            if available == 0 {
+                if ring_buffer.all_write_ends_closed() {
+                    return 0;
+                }
+                drop(ring_buffer);
+                suspend_current_and_run_next();
+                continue;
+            }
+            // 读指针保持不动，随后的 read 仍从同一位置开始
+            let mut peeked = 0usize;
+            for byte_ref in buf.into_iter().take(available) {
+                unsafe {
+                    *byte_ref = ring_buffer.peek_byte(peeked);
+                }
+                peeked += 1;
+            }
+            return peeked;
+        }
+    }
+    /// Hand up to `len` bytes buffered in the pipe of read end `self` to
+    /// `sink` straight from the ring buffer, consuming as many as `sink`
+    /// reports taken and stopping once it takes fewer than offered. Wait
+    /// until there is data unless `nonblock`. Return the number of bytes
+    /// consumed, 0 once the pipe is empty with all write ends closed, or
+    /// None if `nonblock` and it would wait
+    pub fn splice_to(
+        &self,
+        len: usize,
+        nonblock: bool,
+        mut sink: impl FnMut(&[u8]) -> usize,
+    ) -> Option<usize> {
+        assert!(self.readable());
+        loop {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            if ring_buffer.available_read() == 0 {
+                if ring_buffer.all_write_ends_closed() {
+                    return Some(0);
+                }
+                drop(ring_buffer);
+                if nonblock {
+                    return None;
+                }
+                suspend_current_and_run_next();
+                continue;
+            }
+            let mut moved = 0usize;
+            // 环形缓冲区中的数据最多分成两段连续的字节
+            while moved < len && ring_buffer.available_read() > 0 {
+                let chunk = ring_buffer.head_slice();
+                let offered = chunk.len().min(len - moved);
+                let taken = sink(&chunk[..offered]);
+                ring_buffer.consume(taken);
+                moved += taken;
+                if taken < offered {
+                    break;
+                }
+            }
+            self.stats.record_read(moved);
+            return Some(moved);
+        }
+    }
+    /// Fill the pipe of write end `self` with up to `len` bytes produced by
+    /// `source` straight into the ring buffer, stopping once it produces
+    /// fewer than asked, e.g. at the end of a file. Wait until there is room
+    /// unless `nonblock`. Return the number of bytes added, or None if
+    /// `nonblock` and it would wait
+    pub fn splice_from(
+        &self,
+        len: usize,
+        nonblock: bool,
+        mut source: impl FnMut(&mut [u8]) -> usize,
+    ) -> Option<usize> {
+        assert!(self.writable());
+        if len == 0 {
+            return Some(0);
+        }
+        loop {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            if ring_buffer.available_write() == 0 {
+                drop(ring_buffer);
+                if nonblock {
+                    return None;
+                }
+                suspend_current_and_run_next();
+                continue;
+            }
+            let mut moved = 0usize;
+            while moved < len && ring_buffer.available_write() > 0 {
+                let chunk = ring_buffer.tail_slice();
+                let wanted = chunk.len().min(len - moved);
+                let produced = source(&mut chunk[..wanted]);
+                ring_buffer.produce(produced);
+                moved += produced;
+                if produced < wanted {
+                    break;
+                }
+            }
+            self.stats.record_write(moved);
+            return Some(moved);
+        }
+    }
+    /// Read into buf until it is full, all write ends are closed or `delim`
+    /// (if any) has been read
+    fn read_bytes(&self, buf: UserBuffer, delim: Option<u8>) -> usize {
+        assert!(self.readable());
+        let want_to_read = buf.len();
+        let mut buf_iter = buf.into_iter();
+        let mut already_read = 0usize;
+        #[cfg(feature = "deadlock_detect")]
+        let mut blocked = false;
+        loop {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            let loop_read = ring_buffer.available_read();
+            if loop_read == 0 {
+                if ring_buffer.all_write_ends_closed() {
+                    #[cfg(feature = "deadlock_detect")]
+                    self.unblock(blocked);
+                    self.stats.record_read(already_read);
+                    return already_read;
+                }
+                // 缓冲区为空，让出 CPU 等待写端写入
+                drop(ring_buffer);
+                #[cfg(feature = "deadlock_detect")]
+                self.block(&mut blocked);
+                suspend_current_and_run_next();
+                continue;
+            }
+            #[cfg(feature = "deadlock_detect")]
+            self.unblock(core::mem::take(&mut blocked));
+            for _ in 0..loop_read {
+                if let Some(byte_ref) = buf_iter.next() {
+                    let byte = ring_buffer.read_byte();
+                    unsafe {
+                        *byte_ref = byte;
+                    }
+                    already_read += 1;
+                    if already_read == want_to_read || delim == Some(byte) {
+                        self.stats.record_read(already_read);
+                        return already_read;
+                    }
+                } else {
+                    self.stats.record_read(already_read);
+                    return already_read;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        // 最后一个写端关闭后读端变为就绪（读到 0）
+        if self.writable {
+            wake_pollers();
+        }
+    }
+}
+
+/// Wake the tasks polling fds, a pipe has just changed state
+fn wake_pollers() {
+    TICK_QUEUE.notify_all();
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum RingBufferStatus {
+    Full,
+    Empty,
+    Normal,
+}
+
+/// The ring buffer shared by both ends of a pipe
+pub struct PipeRingBuffer {
+    arr: Vec<u8>,
+    head: usize,
+    tail: usize,
+    status: RingBufferStatus,
+    /// 只保存写端的弱引用，用于判断所有写端是否都已关闭
+    write_end: Option<Weak<Pipe>>,
+}
+
+impl PipeRingBuffer {
+    /// Create an empty ring buffer holding at most `capacity` bytes
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self {
+            arr: vec![0; capacity],
+            head: 0,
+            tail: 0,
+            status: RingBufferStatus::Empty,
+            write_end: None,
+        }
+    }
+    /// Record the write end of the pipe
+    pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
+        self.write_end = Some(Arc::downgrade(write_end));
+    }
+    /// Push a byte at the tail, the buffer must not be full
+    pub fn write_byte(&mut self, byte: u8) {
+        self.status = RingBufferStatus::Normal;
+        self.arr[self.tail] = byte;
+        self.tail = (self.tail + 1) % self.capacity();
+        if self.tail == self.head {
+            self.status = RingBufferStatus::Full;
+        }
+        wake_pollers();
+    }
+    /// Pop a byte from the head, the buffer must not be empty
+    pub fn read_byte(&mut self) -> u8 {
+        self.status = RingBufferStatus::Normal;
+        let c = self.arr[self.head];
+        self.head = (self.head + 1) % self.capacity();
+        if self.head == self.tail {
+            self.status = RingBufferStatus::Empty;
+        }
+        wake_pollers();
+        c
+    }
+    /// The byte `i` bytes past the head without consuming it,
+    /// `i` must be less than [`PipeRingBuffer::available_read`]
+    pub fn peek_byte(&self, i: usize) -> u8 {
+        assert!(i < self.available_read());
+        self.arr[(self.head + i) % self.capacity()]
+    }
+    /// The buffered bytes from the head up to the tail or the end of the
+    /// array, whichever comes first
+    fn head_slice(&self) -> &[u8] {
+        let len = self.available_read().min(self.capacity() - self.head);
+        &self.arr[self.head..self.head + len]
+    }
+    /// Drop `len` bytes from the head, at most [`PipeRingBuffer::available_read`]
+    fn consume(&mut self, len: usize) {
+        assert!(len <= self.available_read());
+        if len == 0 {
+            return;
+        }
+        self.head = (self.head + len) % self.capacity();
+        self.status = if self.head == self.tail {
+            RingBufferStatus::Empty
+        } else {
+            RingBufferStatus::Normal
+        };
+        wake_pollers();
+    }
+    /// The free bytes from the tail up to the head or the end of the array,
+    /// whichever comes first
+    fn tail_slice(&mut self) -> &mut [u8] {
+        let len = self.available_write().min(self.capacity() - self.tail);
+        &mut self.arr[self.tail..self.tail + len]
+    }
+    /// Take `len` bytes written at the tail into the buffer,
+    /// at most [`PipeRingBuffer::available_write`]
+    fn produce(&mut self, len: usize) {
+        assert!(len <= self.available_write());
+        if len == 0 {
+            return;
+        }
+        self.tail = (self.tail + len) % self.capacity();
+        self.status = if self.tail == self.head {
+            RingBufferStatus::Full
+        } else {
+            RingBufferStatus::Normal
+        };
+        wake_pollers();
+    }
+    /// Number of bytes that can be read now
+    pub fn available_read(&self) -> usize {
+        if self.status == RingBufferStatus::Empty {
+            0
+        } else if self.tail > self.head {
+            self.tail - self.head
+        } else {
+            self.tail + self.capacity() - self.head
+        }
+    }
+    /// Number of bytes that can be written now
+    pub fn available_write(&self) -> usize {
+        if self.status == RingBufferStatus::Full {
+            0
+        } else {
+            self.capacity() - self.available_read()
+        }
+    }
+    /// The max number of bytes the buffer holds
+    pub fn capacity(&self) -> usize {
+        self.arr.len()
+    }
+    /// Whether all write ends have been dropped
+    pub fn all_write_ends_closed(&self) -> bool {
+        self.write_end.as_ref().unwrap().upgrade().is_none()
+    }
+}
+
+/// Create a pipe with the default capacity, return (read_end, write_end)
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    make_pipe_with_capacity(PIPE_BUFFER_SIZE)
+}
+
+/// Create a pipe whose ring buffer holds `capacity` bytes, return (read_end, write_end)
+pub fn make_pipe_with_capacity(capacity: usize) -> (Arc<Pipe>, Arc<Pipe>) {
+    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new(capacity)) });
+    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
+    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
+    buffer.exclusive_access().set_write_end(&write_end);
+    (read_end, write_end)
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+    fn writable(&self) -> bool {
+        self.writable
+    }
+    fn read(&self, buf: UserBuffer) -> usize {
+        self.read_bytes(buf, None)
+    }
+    fn read_until(&self, buf: UserBuffer, delim: u8) -> usize {
+        self.read_bytes(buf, Some(delim))
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        assert!(self.writable());
+        let want_to_write = buf.len();
+        let mut buf_iter = buf.into_iter();
+        let mut already_write = 0usize;
+        #[cfg(feature = "deadlock_detect")]
+        let mut blocked = false;
+        loop {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            let loop_write = ring_buffer.available_write();
+            if loop_write == 0 {
+                // 缓冲区已满，让出 CPU 等待读端取走数据
+                drop(ring_buffer);
+                #[cfg(feature = "deadlock_detect")]
+                self.block(&mut blocked);
+                suspend_current_and_run_next();
+                continue;
+            }
+            #[cfg(feature = "deadlock_detect")]
+            self.unblock(core::mem::take(&mut blocked));
+            for _ in 0..loop_write {
+                if let Some(byte_ref) = buf_iter.next() {
+                    ring_buffer.write_byte(unsafe { *byte_ref });
+                    already_write += 1;
+                    if already_write == want_to_write {
+                        self.stats.record_write(want_to_write);
+                        return want_to_write;
+                    }
+                } else {
+                    self.stats.record_write(already_write);
+                    return already_write;
+                }
+            }
+        }
+    }
+    fn io_stats(&self) -> (u64, u64) {
+        self.stats.get()
+    }
+    fn read_ready(&self) -> bool {
+        // 写端全部关闭后读会立即返回 0
+        let ring_buffer = self.buffer.exclusive_access();
+        ring_buffer.available_read() > 0 || ring_buffer.all_write_ends_closed()
+    }
+    fn write_ready(&self) -> bool {
+        self.buffer.exclusive_access().available_write() > 0
+    }
+}
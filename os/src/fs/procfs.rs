@@ -0,0 +1,98 @@
+//! Synthetic `/proc` files whose contents are generated from kernel state
+use super::{File, IoStats};
+use crate::config::PAGE_SIZE;
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use crate::task::{current_task, find_task, TaskControlBlock, TaskStatus};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+
+/// The directory `/proc` is served from
+pub const PROC_ROOT: &str = "/proc";
+
+/// Read-only `/proc/<pid>/status` of a task, regenerated on every read
+pub struct ProcStatus {
+    task: Weak<TaskControlBlock>,
+    offset: UPSafeCell<usize>,
+    stats: IoStats,
+}
+
+impl ProcStatus {
+    /// Create the status file of `task`
+    pub fn new(task: &Arc<TaskControlBlock>) -> Self {
+        Self {
+            task: Arc::downgrade(task),
+            offset: unsafe { UPSafeCell::new(0) },
+            stats: IoStats::new(),
+        }
+    }
+    /// Render the status text, None if the task has been released
+    fn render(&self) -> Option<String> {
+        let task = self.task.upgrade()?;
+        let ppid = task.getppid();
+        let inner = task.inner_exclusive_access();
+        let state = match inner.task_status {
+            TaskStatus::UnInit => "U (uninit)",
+            TaskStatus::Ready => "R (ready)",
+            TaskStatus::Running => "R (running)",
+            TaskStatus::Blocked => "S (sleeping)",
+            TaskStatus::Zombie => "Z (zombie)",
+        };
+        let kb = PAGE_SIZE / 1024;
+        let (rchar, wchar) = inner.io_stats();
+        let memory_set = inner.memory_set();
+        Some(format!(
+            "Name:\t{}\nPid:\t{}\nPPid:\t{}\nState:\t{}\nVmRSS:\t{} kB\nVmHWM:\t{} kB\nRChar:\t{}\nWChar:\t{}\n",
+            inner.name,
+            task.getpid(),
+            ppid,
+            state,
+            memory_set.resident_pages() * kb,
+            inner.max_rss.max(memory_set.peak_pages()) * kb,
+            rchar,
+            wchar,
+        ))
+    }
+}
+
+impl File for ProcStatus {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let Some(text) = self.render() else {
+            return 0;
+        };
+        let mut offset = self.offset.exclusive_access();
+        let start = (*offset).min(text.len());
+        let read_size = buf.copy_from_slice(&text.as_bytes()[start..]);
+        *offset = start + read_size;
+        self.stats.record_read(read_size);
+        read_size
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn io_stats(&self) -> (u64, u64) {
+        self.stats.get()
+    }
+}
+
+/// Open `path` if it names a file under `/proc`: `/proc/<pid>/status`,
+/// where `<pid>` may be `self` for the calling task
+pub fn open_proc(path: &str) -> Option<Arc<dyn File>> {
+    let rest = path.strip_prefix(PROC_ROOT)?.strip_prefix('/')?;
+    let (pid, name) = rest.split_once('/')?;
+    let task = match pid {
+        "self" => current_task()?,
+        pid => find_task(pid.parse().ok()?)?,
+    };
+    match name {
+        "status" => Some(Arc::new(ProcStatus::new(&task))),
+        _ => None,
+    }
+}
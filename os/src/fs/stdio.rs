@@ -1,14 +1,64 @@
 //!Stdin & Stdout
-use super::File;
+use super::{File, IoStats};
+use crate::config::STDOUT_BUFFER_SIZE;
+use crate::console::console_write;
 use crate::mm::UserBuffer;
 use crate::sbi::console_getchar;
-use crate::task::suspend_current_and_run_next;
+use crate::sync::UPSafeCell;
+use crate::task::TICK_QUEUE;
+use alloc::vec;
+use alloc::vec::Vec;
 
 /// stdin file for getting chars from console
-pub struct Stdin;
+#[derive(Default)]
+pub struct Stdin {
+    stats: IoStats,
+}
 
 /// stdout file for putting chars to console
-pub struct Stdout;
+#[derive(Default)]
+pub struct Stdout {
+    stats: IoStats,
+    /// 行缓冲模式下尚未输出的字节，None 表示不缓冲
+    buffer: Option<UPSafeCell<Vec<u8>>>,
+}
+
+impl Stdin {
+    /// Create a stdin file with its own I/O counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Discard the input typed but not read yet so that it does not reach
+    /// the next program reading the console. Stdin has no line discipline,
+    /// so there is no mode to restore. Return the number of bytes discarded
+    pub fn reset(&self) -> usize {
+        let mut discarded = 0usize;
+        loop {
+            // 没有可用字符时返回 0，部分 SBI 实现返回 -1
+            match console_getchar() {
+                0 | usize::MAX => return discarded,
+                _ => discarded += 1,
+            }
+        }
+    }
+}
+
+impl Stdout {
+    /// Create an unbuffered stdout file with its own I/O counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a stdout file sending its output to the console a line at a
+    /// time, or once `STDOUT_BUFFER_SIZE` bytes are pending
+    pub fn line_buffered() -> Self {
+        Self {
+            stats: IoStats::new(),
+            buffer: Some(unsafe { UPSafeCell::new(Vec::with_capacity(STDOUT_BUFFER_SIZE)) }),
+        }
+    }
+}
 
 impl File for Stdin {
     /// 标准输入文件 Stdin 是只读文件，只允许进程通过 read 从里面读入数据
@@ -21,28 +71,49 @@ impl File for Stdin {
     fn read(&self, mut user_buf: UserBuffer) -> usize {
         // 目前每次仅支持读入一个字符
         assert_eq!(user_buf.len(), 1);
-        // busy loop
         let mut c: usize;
         loop {
             c = console_getchar();
-            //表示没有可用字符，让出 CPU 给其他进程
+            //表示没有可用字符，睡到下一个时钟中断再查看
             if c == 0 {
-                suspend_current_and_run_next();
+                TICK_QUEUE.wait();
                 continue;
             } else {
                 break;
             }
         }
         // 将读入的字符写入到用户缓冲区中
-        let ch = c as u8;
-        unsafe {
-            user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
+        let len = user_buf.copy_from_slice(&[c as u8]);
+        self.stats.record_read(len);
+        len
+    }
+    fn read_until(&self, user_buf: UserBuffer, delim: u8) -> usize {
+        let mut read_size = 0usize;
+        for byte_ref in user_buf {
+            // 逐个字符等待输入，直到读到分隔符或缓冲区填满
+            let c = loop {
+                match console_getchar() {
+                    0 => TICK_QUEUE.wait(),
+                    c => break c as u8,
+                }
+            };
+            unsafe {
+                *byte_ref = c;
+            }
+            read_size += 1;
+            if c == delim {
+                break;
+            }
         }
-        1
+        self.stats.record_read(read_size);
+        read_size
     }
     fn write(&self, _user_buf: UserBuffer) -> usize {
         panic!("Cannot write to stdin!");
     }
+    fn io_stats(&self) -> (u64, u64) {
+        self.stats.get()
+    }
 }
 
 impl File for Stdout {
@@ -58,9 +129,33 @@ impl File for Stdout {
     }
     fn write(&self, user_buf: UserBuffer) -> usize {
         // 将用户缓冲区中的数据写入到控制台
-        for buffer in user_buf.buffers.iter() {
-            print!("{}", core::str::from_utf8(*buffer).unwrap());
+        // 先拼接成连续的字节，避免跨页的多字节字符被拆开
+        let mut bytes = vec![0u8; user_buf.len()];
+        let len = user_buf.copy_into_slice(&mut bytes);
+        let bytes = &bytes[..len];
+        match &self.buffer {
+            Some(buffer) => {
+                let mut buffer = buffer.exclusive_access();
+                buffer.extend_from_slice(bytes);
+                // 遇到换行或缓冲区满时才真正输出，减少 SBI 调用次数
+                if bytes.contains(&b'\n') || buffer.len() >= STDOUT_BUFFER_SIZE {
+                    console_write(&buffer);
+                    buffer.clear();
+                }
+            }
+            None => console_write(bytes),
         }
-        user_buf.len()
+        self.stats.record_write(len);
+        len
+    }
+    fn flush(&self) {
+        if let Some(buffer) = &self.buffer {
+            let mut buffer = buffer.exclusive_access();
+            console_write(&buffer);
+            buffer.clear();
+        }
+    }
+    fn io_stats(&self) -> (u64, u64) {
+        self.stats.get()
     }
 }
@@ -63,16 +63,20 @@ fn clear_bss() {
 }
 
 #[no_mangle]
-/// the rust entry-point of os
-pub fn rust_main() -> ! {
+/// the rust entry-point of os, SBI passes the id of the boot hart in a0
+pub extern "C" fn rust_main(hart_id: usize) -> ! {
     clear_bss();
+    task::set_current_hart_id(hart_id);
     println!("[kernel] Hello, world!");
     logging::init();
     mm::init();
     mm::remap_test();
+    mm::discard_test();
+    console::console_test();
     trap::init();
     trap::enable_timer_interrupt();
     timer::set_next_trigger();
+    fs::init();
     fs::list_apps();
     task::add_initproc();
     task::run_tasks();
@@ -14,7 +14,7 @@ pub struct FrameTracker {
 }
 
 impl FrameTracker {
-    /// Create a new FrameTracker
+    /// Create a new FrameTracker, the frame is always zeroed before being handed out
     pub fn new(ppn: PhysPageNum) -> Self {
         // page cleaning
         let bytes_array = ppn.get_bytes_array();
@@ -103,7 +103,7 @@ pub fn init_frame_allocator() {
     );
 }
 
-/// Allocate a physical page frame in FrameTracker style
+/// Allocate a physical page frame in FrameTracker style, filled with zeros
 pub fn frame_alloc() -> Option<FrameTracker> {
     FRAME_ALLOCATOR
         .exclusive_access()
@@ -111,8 +111,15 @@ pub fn frame_alloc() -> Option<FrameTracker> {
         .map(FrameTracker::new)
 }
 
+/// Byte pattern written over freed frames when the `frame_poison` feature is on
+#[cfg(feature = "frame_poison")]
+pub const FRAME_POISON: u8 = 0xa5;
+
 /// Deallocate a physical page frame with a given ppn
 pub fn frame_dealloc(ppn: PhysPageNum) {
+    // 释放后仍被访问的页帧会读到 FRAME_POISON 而不是看似合法的旧数据
+    #[cfg(feature = "frame_poison")]
+    ppn.get_bytes_array().fill(FRAME_POISON);
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
 }
 
@@ -9,6 +9,7 @@ use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::arch::asm;
+use easy_fs::Inode;
 use lazy_static::*;
 use riscv::register::satp;
 
@@ -40,6 +41,8 @@ pub fn kernel_token() -> usize {
 pub struct MemorySet {
     page_table: PageTable,
     areas: Vec<MapArea>,
+    /// 同时驻留过的最多页数，解除映射时不会减小
+    peak_pages: usize,
 }
 
 impl MemorySet {
@@ -48,6 +51,7 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            peak_pages: 0,
         }
     }
     /// Get the page table token
@@ -67,6 +71,77 @@ impl MemorySet {
         );
     }
 
+    /// Map `file` at [start_va, end_va) without loading anything: each page
+    /// is read in from the file by [`Self::load_page`] on first access,
+    /// pages past the end of the file read as zeros. Assume no conflicts
+    pub fn insert_file_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        file: MappedFile,
+    ) {
+        let mut area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        area.file = Some(file);
+        self.areas.push(area);
+    }
+
+    /// Load the page at `va` of a user mapping if it has no frame: a page
+    /// of a file mapping is read in from the file on first access, a page
+    /// dropped by [`Self::discard_pages`] comes back filled with zeros.
+    /// Return false if `va` is not inside such a page, e.g. the page is
+    /// loaded and the access violates its permission
+    pub fn load_page(&mut self, va: VirtAddr) -> bool {
+        let vpn = va.floor();
+        let Some(area) = self.areas.iter_mut().find(|area| {
+            area.is_user() && area.vpn_range.get_start() <= vpn && vpn < area.vpn_range.get_end()
+        }) else {
+            return false;
+        };
+        if area.data_frames.contains_key(&vpn) {
+            return false;
+        }
+        area.map_one(&mut self.page_table, vpn);
+        if let Some(file) = area.file.as_ref() {
+            let offset = file.offset + (vpn.0 - area.vpn_range.get_start().0) * PAGE_SIZE;
+            file.inode.read_at(offset, area.data_frames[&vpn].ppn.get_bytes_array());
+        }
+        self.note_resident();
+        // 刷新 TLB 中可能缓存的无效页表项
+        unsafe {
+            asm!("sfence.vma");
+        }
+        true
+    }
+
+    /// Map a trap context page for one more task sharing the address space,
+    /// at the highest free page below TRAP_CONTEXT_BASE. Return its address
+    pub fn alloc_trap_cx(&mut self) -> usize {
+        let mut va = TRAP_CONTEXT_BASE;
+        loop {
+            va -= PAGE_SIZE;
+            if !self.check_conflict(va.into(), (va + PAGE_SIZE).into()) {
+                break;
+            }
+        }
+        self.insert_framed_area(va.into(), (va + PAGE_SIZE).into(), MapPermission::R | MapPermission::W);
+        va
+    }
+
+    /// Write the modified pages of [start, end), which must lie inside one
+    /// framed area, back to the file the area maps if it is a shared mapping
+    pub fn sync_area(&mut self, start: VirtAddr, end: VirtAddr) -> bool {
+        let Some(index) = self.framed_area_covering(start, end) else {
+            return false;
+        };
+        self.areas[index].write_back(&self.page_table, VPNRange::new(start.floor(), end.ceil()));
+        // 清除 D 位后刷新 TLB，否则之后的写入不会再置位
+        unsafe {
+            asm!("sfence.vma");
+        }
+        true
+    }
+
     /// 在内存集中清空映射区域
     pub fn remove_framed_area(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> isize {
         // 遍历所有映射区域
@@ -74,6 +149,8 @@ impl MemorySet {
         while index < self.areas.len() {
             let area = &mut self.areas[index];
             if area.vpn_range.get_start() == start_va.floor() && area.vpn_range.get_end() == end_va.ceil() {
+                // 共享的文件映射在解除前写回文件
+                area.write_back(&self.page_table, area.vpn_range);
                 area.unmap(&mut self.page_table); // 解除映射
                 self.areas.remove(index); // 移除映射区域
                 return 0;
@@ -104,6 +181,7 @@ impl MemorySet {
             map_area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(map_area);
+        self.note_resident();
     }
     /// Mention that trampoline is not collected by areas.
     fn map_trampoline(&mut self) {
@@ -194,14 +272,18 @@ impl MemorySet {
     /// Include sections in elf and trampoline and TrapContext and user stack,
     /// also returns user_sp_base and entry point.
     pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+        Self::try_from_elf(elf_data).expect("invalid elf!")
+    }
+    /// Like [`MemorySet::from_elf`], but return None without allocating any
+    /// frame when `elf_data` is not an executable this kernel can load
+    pub fn try_from_elf(elf_data: &[u8]) -> Option<(Self, usize, usize)> {
+        let elf = xmas_elf::ElfFile::new(elf_data).ok()?;
+        Self::check_elf(&elf)?;
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
         let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
         let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
         for i in 0..ph_count {
@@ -263,30 +345,73 @@ impl MemorySet {
             ),
             None,
         );
-        (
+        Some((
             memory_set,
             user_stack_top,
             elf.header.pt2.entry_point() as usize,
-        )
+        ))
+    }
+    /// Check everything `try_from_elf` relies on: the load segments lie in
+    /// the file, ascend without sharing a page, and leave room for the user
+    /// stack below the trap context
+    fn check_elf(elf: &xmas_elf::ElfFile) -> Option<()> {
+        if elf.header.pt1.magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return None;
+        }
+        let mut max_end_vpn = VirtPageNum(0);
+        for i in 0..elf.header.pt2.ph_count() {
+            let ph = elf.program_header(i).ok()?;
+            if ph.get_type().ok()? != xmas_elf::program::Type::Load {
+                continue;
+            }
+            let file_end = ph.offset().checked_add(ph.file_size())?;
+            let mem_end = ph.virtual_addr().checked_add(ph.mem_size())?;
+            if file_end > elf.input.len() as u64
+                || ph.file_size() > ph.mem_size()
+                || mem_end > TRAP_CONTEXT_BASE as u64
+            {
+                return None;
+            }
+            let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
+            let end_va: VirtAddr = (mem_end as usize).into();
+            if start_va.floor() < max_end_vpn {
+                return None;
+            }
+            max_end_vpn = end_va.ceil();
+        }
+        // 用户栈紧跟在最后一个段之后，中间隔一个保护页
+        let max_end_va: VirtAddr = max_end_vpn.into();
+        let user_stack_top = usize::from(max_end_va) + PAGE_SIZE + USER_STACK_SIZE;
+        (user_stack_top <= TRAP_CONTEXT_BASE).then_some(())
     }
     /// Create a new address space by copy code&data from a exited process's address space.
+    /// Read-only areas such as the text share their frames with `user_space` instead
     pub fn from_existed_user(user_space: &Self) -> Self {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
         // copy data sections/trap_context/user_stack
         for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
-            // copy data from another space
-            for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
-                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
-                dst_ppn
+            let mut new_area = MapArea::from_another(area);
+            let shared = area.file.as_ref().is_some_and(|file| file.shared);
+            if area.map_type == MapType::Framed && (!area.map_perm.contains(MapPermission::W) || shared) {
+                // 只读的段内容不会变化，共享的文件映射本就要互相可见，直接共享页帧
+                new_area.share_frames(&mut memory_set.page_table, area);
+                memory_set.areas.push(new_area);
+                continue;
+            }
+            // 可写的私有映射只复制已有页帧的页面，其余页面在子进程中首次访问时
+            // 再从文件读入或分配全零页帧
+            for (vpn, frame) in area.data_frames.iter() {
+                new_area.map_one(&mut memory_set.page_table, *vpn);
+                new_area.data_frames[vpn]
+                    .ppn
                     .get_bytes_array()
-                    .copy_from_slice(src_ppn.get_bytes_array());
+                    .copy_from_slice(frame.ppn.get_bytes_array());
             }
+            memory_set.areas.push(new_area);
         }
+        memory_set.note_resident();
         memory_set
     }
     /// Change page table by writing satp CSR Register.
@@ -302,8 +427,26 @@ impl MemorySet {
         self.page_table.translate(vpn)
     }
 
-    ///Remove all `MapArea`
+    /// Number of pages backed by allocated frames
+    pub fn resident_pages(&self) -> usize {
+        self.areas.iter().map(|area| area.data_frames.len()).sum()
+    }
+
+    /// The most pages backed by allocated frames at any one time so far
+    pub fn peak_pages(&self) -> usize {
+        self.peak_pages
+    }
+
+    /// Raise the peak to the pages resident now, after mapping more of them
+    fn note_resident(&mut self) {
+        self.peak_pages = self.peak_pages.max(self.resident_pages());
+    }
+
+    ///Remove all `MapArea`, writing shared file mappings back first
     pub fn recycle_data_pages(&mut self) {
+        for area in self.areas.iter() {
+            area.write_back(&self.page_table, area.vpn_range);
+        }
         self.areas.clear();
     }
 
@@ -331,12 +474,61 @@ impl MemorySet {
             .find(|area| area.vpn_range.get_start() == start.floor())
         {
             area.append_to(&mut self.page_table, new_end.ceil());
+            self.note_resident();
             true
         } else {
             false
         }
     }
 
+    /// Load the pages of [start, end), which must lie inside one user
+    /// mapping, ahead of their first access if the mapping is backed by a
+    /// file. Anonymous pages are left to be allocated on demand
+    pub fn prefetch_pages(&mut self, start: VirtAddr, end: VirtAddr) -> bool {
+        let Some(index) = self.framed_area_covering(start, end) else {
+            return false;
+        };
+        if self.areas[index].file.is_some() {
+            // 已经载入的页面 load_page 什么也不做
+            for vpn in VPNRange::new(start.floor(), end.ceil()) {
+                self.load_page(vpn.into());
+            }
+        }
+        true
+    }
+
+    /// Throw away the contents of the pages in [start, end), which must lie
+    /// inside one user mapping, and free their frames. Later accesses load
+    /// the pages again by [`Self::load_page`], they read as zeros, or the
+    /// file contents in a file mapping
+    pub fn discard_pages(&mut self, start: VirtAddr, end: VirtAddr) -> bool {
+        let Some(index) = self.framed_area_covering(start, end) else {
+            return false;
+        };
+        let area = &mut self.areas[index];
+        let range = VPNRange::new(start.floor(), end.ceil());
+        // 共享映射先写回，再解除映射；与其他进程共享的页帧在它们解除映射后才释放
+        area.write_back(&self.page_table, range);
+        for vpn in range {
+            if area.data_frames.contains_key(&vpn) {
+                area.unmap_one(&mut self.page_table, vpn);
+            }
+        }
+        unsafe {
+            asm!("sfence.vma");
+        }
+        true
+    }
+
+    /// 只接受用户映射，内核的陷入上下文等区域不允许用户丢弃或写回
+    fn framed_area_covering(&self, start: VirtAddr, end: VirtAddr) -> Option<usize> {
+        self.areas.iter().position(|area| {
+            area.is_user()
+                && area.vpn_range.get_start() <= start.floor()
+                && end.ceil() <= area.vpn_range.get_end()
+        })
+    }
+
     /// 检测新的映射区域是否与已有的映射区域冲突
     pub fn check_conflict(&self, start: VirtAddr, end: VirtAddr) -> bool {
         // any: 如果任意一个元素满足条件，则返回true
@@ -347,12 +539,36 @@ impl MemorySet {
         })
     }
 }
+impl Drop for MemorySet {
+    fn drop(&mut self) {
+        // exec 替换地址空间时，旧地址空间中共享的文件映射也要写回
+        for area in self.areas.iter() {
+            area.write_back(&self.page_table, area.vpn_range);
+        }
+    }
+}
+
+/// The file behind a file-backed mapping
+#[derive(Clone)]
+pub struct MappedFile {
+    /// The mapped file
+    pub inode: Arc<Inode>,
+    /// Offset in the file of the first page of the mapping
+    pub offset: usize,
+    /// MAP_SHARED: writes to the mapping reach the file, and the frames
+    /// stay shared with the children forked afterwards
+    pub shared: bool,
+}
+
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// 只读段的页帧可能被 fork 出的多个地址空间共享
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// 文件映射的后备文件，匿名映射为 None
+    file: Option<MappedFile>,
 }
 
 impl MapArea {
@@ -369,6 +585,7 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            file: None,
         }
     }
     pub fn from_another(another: &Self) -> Self {
@@ -377,8 +594,13 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            file: another.file.clone(),
         }
     }
+    /// Whether the area is a framed mapping accessible in U mode
+    fn is_user(&self) -> bool {
+        self.map_type == MapType::Framed && self.map_perm.contains(MapPermission::U)
+    }
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
         match self.map_type {
@@ -388,12 +610,22 @@ impl MapArea {
             MapType::Framed => {
                 let frame = frame_alloc().unwrap();
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
             }
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         page_table.map(vpn, ppn, pte_flags);
     }
+    /// Map every page of current area to the frame `another` maps it to,
+    /// both areas keep the frames alive
+    pub fn share_frames(&mut self, page_table: &mut PageTable, another: &Self) {
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        // 文件映射中尚未载入的页面不共享，各自在首次访问时载入
+        for (vpn, frame) in another.data_frames.iter() {
+            page_table.map(*vpn, frame.ppn, pte_flags);
+            self.data_frames.insert(*vpn, Arc::clone(frame));
+        }
+    }
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         if self.map_type == MapType::Framed {
             self.data_frames.remove(&vpn);
@@ -407,12 +639,19 @@ impl MapArea {
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
+            // 文件映射中从未访问过的页面和丢弃的页面没有映射
+            if self.map_type == MapType::Framed && !self.data_frames.contains_key(&vpn) {
+                continue;
+            }
             self.unmap_one(page_table, vpn);
         }
     }
     #[allow(unused)]
     pub fn shrink_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
         for vpn in VPNRange::new(new_end, self.vpn_range.get_end()) {
+            if self.map_type == MapType::Framed && !self.data_frames.contains_key(&vpn) {
+                continue;
+            }
             self.unmap_one(page_table, vpn)
         }
         self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
@@ -424,6 +663,26 @@ impl MapArea {
         }
         self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
     }
+    /// Write the pages in `range` that were written since the last write back
+    /// to the file, if the area is a shared file mapping. The part of a page
+    /// past the end of the file is not written
+    pub fn write_back(&self, page_table: &PageTable, range: VPNRange) {
+        let Some(file) = self.file.as_ref().filter(|file| file.shared) else {
+            return;
+        };
+        let size = file.inode.size() as usize;
+        for vpn in range {
+            if !self.data_frames.contains_key(&vpn) || !page_table.translate(vpn).is_some_and(|pte| pte.dirty()) {
+                continue;
+            }
+            let offset = file.offset + (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            if offset < size {
+                let len = PAGE_SIZE.min(size - offset);
+                file.inode.write_at(offset, &self.data_frames[&vpn].ppn.get_bytes_array()[..len]);
+            }
+            page_table.set_dirty(vpn, false);
+        }
+    }
     /// data: start-aligned but maybe with shorter length
     /// assume that all frames were cleared before
     pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
@@ -493,3 +752,37 @@ pub fn remap_test() {
         .executable(),);
     println!("remap_test passed!");
 }
+
+/// discard test: MADV_DONTNEED frees the frames of user pages, which come
+/// back filled with zeros on the next access, and kernel areas are refused
+#[allow(unused)]
+pub fn discard_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let start = VirtAddr::from(0x1000_0000);
+    let end = VirtAddr::from(0x1000_0000 + 2 * PAGE_SIZE);
+    memory_set.insert_framed_area(start, end, MapPermission::R | MapPermission::W | MapPermission::U);
+    let vpn = VirtAddr::from(0x1000_0000 + PAGE_SIZE).floor();
+    let ppn = memory_set.translate(vpn).unwrap().ppn();
+    ppn.get_bytes_array().fill(0x5a);
+    assert!(memory_set.discard_pages(vpn.into(), end));
+    assert_eq!(memory_set.resident_pages(), 1);
+    assert!(!memory_set.translate(vpn).unwrap().is_valid());
+    // 释放的页帧回到分配器，下一次分配就拿到它
+    assert_eq!(frame_alloc().unwrap().ppn, ppn);
+    assert!(memory_set.load_page(vpn.into()));
+    let pte = memory_set.translate(vpn).unwrap();
+    assert!(pte.is_valid());
+    assert!(pte.ppn().get_bytes_array().iter().all(|b| *b == 0));
+    // 陷入上下文没有 U 权限，用户不能丢弃或写回
+    memory_set.insert_framed_area(
+        TRAP_CONTEXT_BASE.into(),
+        TRAMPOLINE.into(),
+        MapPermission::R | MapPermission::W,
+    );
+    let trap_cx = VirtAddr::from(TRAP_CONTEXT_BASE);
+    let trap_cx_end = VirtAddr::from(TRAP_CONTEXT_BASE + PAGE_SIZE);
+    assert!(!memory_set.discard_pages(trap_cx, trap_cx_end));
+    assert!(!memory_set.sync_area(trap_cx, trap_cx_end));
+    assert!(!memory_set.load_page(trap_cx));
+    println!("discard_test passed!");
+}
@@ -15,11 +15,12 @@ mod page_table;
 use address::VPNRange;
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
 pub use frame_allocator::{frame_alloc, frame_dealloc, FrameTracker};
-pub use memory_set::remap_test;
-pub use memory_set::{kernel_token, MapPermission, MemorySet, KERNEL_SPACE};
+pub use memory_set::{discard_test, remap_test};
+pub use memory_set::{kernel_token, MapPermission, MappedFile, MemorySet, KERNEL_SPACE};
 use page_table::PTEFlags;
 pub use page_table::{
-    translated_byte_buffer, translated_ref, translated_refmut, translated_str, PageTable,
+    translated_byte_buffer, translated_byte_buffer_mut, translated_ref, translated_refmut, translated_str,
+    translated_user_buffer, PageTable,
     PageTableEntry, UserBuffer, UserBufferIterator,
 };
 
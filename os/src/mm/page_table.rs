@@ -1,5 +1,6 @@
 //! Implementation of [`PageTableEntry`] and [`PageTable`].
 use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use crate::task::load_page;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -62,6 +63,10 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    /// The page pointered by page table entry has been written?
+    pub fn dirty(&self) -> bool {
+        (self.flags() & PTEFlags::D) != PTEFlags::empty()
+    }
 }
 
 /// page table structure
@@ -139,6 +144,17 @@ impl PageTable {
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
     }
+    /// Set (or clear) the dirty bit of the mapped page `vpn`. After clearing
+    /// it the TLB must be flushed, or the hardware never sets it again
+    pub fn set_dirty(&self, vpn: VirtPageNum, dirty: bool) {
+        if let Some(pte) = self.find_pte(vpn).filter(|pte| pte.is_valid()) {
+            if dirty {
+                pte.bits |= PTEFlags::D.bits as usize;
+            } else {
+                pte.bits &= !(PTEFlags::D.bits as usize);
+            }
+        }
+    }
     /// get the page table entry from the virtual page number
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).map(|pte| *pte)
@@ -158,8 +174,29 @@ impl PageTable {
     }
 }
 
-/// Translate&Copy a ptr[u8] array with LENGTH len to a mutable u8 Vec through page table
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+/// Find the page table entry of user page `vpn`, first loading the page if
+/// it lies in a mapping of current task and has no frame yet: the
+/// kernel reaches user pages through their physical addresses, so its
+/// accesses never fault. If `write`, mark the page dirty for the same reason
+fn user_pte(page_table: &PageTable, vpn: VirtPageNum, write: bool) -> PageTableEntry {
+    let mut pte = page_table.translate(vpn).unwrap();
+    if !pte.is_valid() && load_page(page_table.token(), vpn.into()) {
+        pte = page_table.translate(vpn).unwrap();
+    }
+    // 内核写入不会置位 D，这里代为标记，以免共享的文件映射漏写回
+    if write && pte.writable() {
+        page_table.set_dirty(vpn, true);
+    }
+    pte
+}
+
+/// Physical address of user address `va`, see [`user_pte`]
+fn user_pa(page_table: &PageTable, va: VirtAddr, write: bool) -> PhysAddr {
+    let aligned_pa: PhysAddr = user_pte(page_table, va.floor(), write).ppn().into();
+    (usize::from(aligned_pa) + va.page_offset()).into()
+}
+
+fn translated_pages(token: usize, ptr: *const u8, len: usize, write: bool) -> Vec<&'static mut [u8]> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
     let end = start + len;
@@ -167,7 +204,7 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
+        let ppn = user_pte(&page_table, vpn, write).ppn();
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
@@ -181,16 +218,50 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
     v
 }
 
+/// Translate&Copy a ptr[u8] array with LENGTH len to a mutable u8 Vec through page table
+pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+    translated_pages(token, ptr, len, false)
+}
+
+/// Like [`translated_byte_buffer`], for a buffer the kernel writes into:
+/// its pages are marked dirty
+pub fn translated_byte_buffer_mut(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+    translated_pages(token, ptr, len, true)
+}
+
+/// Like [`translated_byte_buffer`], but return None instead of panicking
+/// if any page of the array is unmapped or not readable from user mode
+pub fn translated_user_buffer(token: usize, ptr: *const u8, len: usize) -> Option<Vec<&'static mut [u8]>> {
+    let page_table = PageTable::from_token(token);
+    let mut start = ptr as usize;
+    let end = start.checked_add(len)?;
+    let mut v = Vec::new();
+    while start < end {
+        let start_va = VirtAddr::from(start);
+        let mut vpn = start_va.floor();
+        let pte = page_table.translate(vpn).filter(|pte| {
+            pte.is_valid() && pte.readable() && pte.flags().contains(PTEFlags::U)
+        })?;
+        vpn.step();
+        let mut end_va: VirtAddr = vpn.into();
+        end_va = end_va.min(VirtAddr::from(end));
+        if end_va.page_offset() == 0 {
+            v.push(&mut pte.ppn().get_bytes_array()[start_va.page_offset()..]);
+        } else {
+            v.push(&mut pte.ppn().get_bytes_array()[start_va.page_offset()..end_va.page_offset()]);
+        }
+        start = end_va.into();
+    }
+    Some(v)
+}
+
 /// Translate&Copy a ptr[u8] array end with `\0` to a `String` Vec through page table
 pub fn translated_str(token: usize, ptr: *const u8) -> String {
     let page_table = PageTable::from_token(token);
     let mut string = String::new();
     let mut va = ptr as usize;
     loop {
-        let ch: u8 = *(page_table
-            .translate_va(VirtAddr::from(va))
-            .unwrap()
-            .get_mut());
+        let ch: u8 = *user_pa(&page_table, VirtAddr::from(va), false).get_ref();
         if ch == 0 {
             break;
         }
@@ -204,19 +275,12 @@ pub fn translated_str(token: usize, ptr: *const u8) -> String {
 /// Translate a ptr[u8] array through page table and return a reference of T
 pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
     let page_table = PageTable::from_token(token);
-    page_table
-        .translate_va(VirtAddr::from(ptr as usize))
-        .unwrap()
-        .get_ref()
+    user_pa(&page_table, VirtAddr::from(ptr as usize), false).get_ref()
 }
 /// Translate a ptr[u8] array through page table and return a mutable reference of T
 pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
     let page_table = PageTable::from_token(token);
-    let va = ptr as usize;
-    page_table
-        .translate_va(VirtAddr::from(va))
-        .unwrap()
-        .get_mut()
+    user_pa(&page_table, VirtAddr::from(ptr as usize), true).get_mut()
 }
 
 /// An abstraction over a buffer passed from user space to kernel space
@@ -230,6 +294,14 @@ impl UserBuffer {
     pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
         Self { buffers }
     }
+    /// Wrap a kernel buffer so that it can be passed to `File::read/write`
+    ///
+    /// # Safety
+    ///
+    /// `buf` must outlive the returned UserBuffer and not be accessed meanwhile
+    pub unsafe fn from_kernel(buf: &mut [u8]) -> Self {
+        Self::new(vec![core::slice::from_raw_parts_mut(buf.as_mut_ptr(), buf.len())])
+    }
     /// Get the length of the buffer
     pub fn len(&self) -> usize {
         let mut total: usize = 0;
@@ -238,6 +310,34 @@ impl UserBuffer {
         }
         total
     }
+    /// Copy `src` into the buffer across its fragments,
+    /// return the number of bytes copied (capped at the buffer length)
+    pub fn copy_from_slice(&mut self, src: &[u8]) -> usize {
+        let mut copied = 0usize;
+        for buffer in self.buffers.iter_mut() {
+            if copied == src.len() {
+                break;
+            }
+            let len = buffer.len().min(src.len() - copied);
+            buffer[..len].copy_from_slice(&src[copied..copied + len]);
+            copied += len;
+        }
+        copied
+    }
+    /// Copy the buffer into `dst` across its fragments,
+    /// return the number of bytes copied (capped at the length of `dst`)
+    pub fn copy_into_slice(&self, dst: &mut [u8]) -> usize {
+        let mut copied = 0usize;
+        for buffer in self.buffers.iter() {
+            if copied == dst.len() {
+                break;
+            }
+            let len = buffer.len().min(dst.len() - copied);
+            dst[copied..copied + len].copy_from_slice(&buffer[..len]);
+            copied += len;
+        }
+        copied
+    }
 }
 
 impl IntoIterator for UserBuffer {
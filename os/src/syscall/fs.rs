@@ -1,24 +1,79 @@
 //! File and filesystem-related syscalls
-use crate::fs::{open_file, OpenFlags, Stat, OSInode, ROOT_INODE, StatMode};
-use crate::mm::{translated_byte_buffer, translated_str, UserBuffer};
-use crate::task::{current_task, current_user_token};
+use crate::fs::{copy_up, Epoll, EpollEvent, EPOLL_CTL_DEL, LOCK_NB, LOCK_SH, LOCK_EX, LOCK_UN, find_path, link_path, make_pipe, mount, open_file, open_proc, path_too_deep, File, remove, remove_dir, rename_path, split_path, sync_all, umount, with_start_dir, MountFlags, permits, AccessFlags, MemFile, OpenFlags, Pipe, Stat, OSInode, StatFs, StatMode};
+use super::process::TimeVal;
+use crate::config::PAGE_SIZE;
+use crate::mm::{translated_byte_buffer, translated_byte_buffer_mut, translated_ref, translated_refmut, translated_str, UserBuffer};
+use crate::task::{current_task, current_user_token, FdEntry, TICK_QUEUE};
+use crate::timer::{get_time_ms, get_time_us};
+use alloc::sync::Arc;
+use alloc::vec;
 use core::any::Any;
+use easy_fs::{Inode, RemoveError, NAME_LENGTH_LIMIT};
+
+/// *at() dirfd: resolve relative paths from the cwd
+pub const AT_FDCWD: isize = -100;
+/// unlinkat() flag: remove a directory instead of a file
+pub const AT_REMOVEDIR: usize = 0x200;
+
+/// poll() event: there is data to read
+pub const POLLIN: i16 = 0x1;
+/// poll() event: writing now will not block
+pub const POLLOUT: i16 = 0x4;
+/// poll() event: the fd is not open
+pub const POLLNVAL: i16 = 0x20;
+
+/// One fd watched by poll(), see poll(2)
+#[repr(C)]
+#[derive(Debug)]
+pub struct PollFd {
+    /// File descriptor, negative ones are ignored
+    pub fd: i32,
+    /// Requested events
+    pub events: i16,
+    /// Returned events
+    pub revents: i16,
+}
+
+/// No such file or directory
+const ENOENT: isize = 2;
+/// Resource temporarily unavailable
+const EWOULDBLOCK: isize = 11;
+/// File exists
+const EEXIST: isize = 17;
+/// Not a directory
+const ENOTDIR: isize = 20;
+/// Invalid argument
+const EINVAL: isize = 22;
+/// No space left on device
+const ENOSPC: isize = 28;
+/// File name too long
+const ENAMETOOLONG: isize = 36;
+/// Directory not empty
+const ENOTEMPTY: isize = 39;
+/// Stale file handle
+const ESTALE: isize = 116;
+
+/// Whether `file` is open on an inode that has been freed and reused since
+fn is_stale(file: &Arc<dyn File + Send + Sync>) -> bool {
+    file.as_any()
+        .downcast_ref::<OSInode>()
+        .is_some_and(OSInode::is_stale)
+}
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     trace!("kernel:pid[{}] sys_write", current_task().unwrap().pid.0);
     let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table[fd] {
+    if let Some(file) = inner.file(fd) {
         if !file.writable() {
             return -1;
         }
-        let file = file.clone();
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
+        if is_stale(&file) {
+            return -ESTALE;
+        }
         file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
     } else {
         -1
@@ -30,133 +85,1053 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
+    if let Some(file) = inner.file(fd) {
         if !file.readable() {
             return -1;
         }
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
+        if is_stale(&file) {
+            return -ESTALE;
+        }
         trace!("kernel: sys_read .. file.read");
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        file.read(UserBuffer::new(translated_byte_buffer_mut(token, buf, len))) as isize
     } else {
         -1
     }
 }
 
-pub fn sys_open(path: *const u8, flags: u32) -> isize {
-    trace!("kernel:pid[{}] sys_open", current_task().unwrap().pid.0);
+/// Read from `offset` of `fd` without moving its file offset
+pub fn sys_pread(fd: usize, buf: *const u8, len: usize, offset: isize) -> isize {
+    trace!("kernel:pid[{}] sys_pread", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let Some(file) = inner.file(fd) else {
+        return -1;
+    };
+    drop(inner);
+    if !file.readable() || offset < 0 {
+        return -1;
+    }
+    file.pread(UserBuffer::new(translated_byte_buffer_mut(token, buf, len)), offset as usize)
+}
+
+/// Write at `offset` of `fd` without moving its file offset
+pub fn sys_pwrite(fd: usize, buf: *const u8, len: usize, offset: isize) -> isize {
+    trace!("kernel:pid[{}] sys_pwrite", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let Some(file) = inner.file(fd) else {
+        return -1;
+    };
+    drop(inner);
+    if !file.writable() || offset < 0 {
+        return -1;
+    }
+    file.pwrite(UserBuffer::new(translated_byte_buffer(token, buf, len)), offset as usize)
+}
+
+pub fn sys_openat(dirfd: isize, path: *const u8, flags: u32, _mode: u32) -> isize {
+    trace!("kernel:pid[{}] sys_openat", current_task().unwrap().pid.0);
     let task = current_task().unwrap();
     let token = current_user_token();
     let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
-        let mut inner = task.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
+    let flags = OpenFlags::from_bits(flags).unwrap();
+    // 绝对路径忽略 dirfd，否则从 dirfd 指向的目录开始解析
+    let start = if path.starts_with('/') || dirfd == AT_FDCWD {
+        None
     } else {
-        -1
-    }
+        let Some(file) = task.inner_exclusive_access().file(dirfd as usize) else {
+            return -1;
+        };
+        match file.as_any().downcast_ref::<OSInode>().map(OSInode::inode) {
+            Some(inode) if inode.is_dir() => Some(inode),
+            _ => return -1,
+        }
+    };
+    let file: Arc<dyn File> = if let Some(file) = open_proc(path.as_str()) {
+        // /proc 下的文件由内核状态生成，只读
+        if flags.read_write().1 {
+            return -1;
+        }
+        file
+    } else if let Some(inode) = match start {
+        Some(dir) => with_start_dir(dir, || open_file(path.as_str(), flags)),
+        None => open_file(path.as_str(), flags),
+    } {
+        inode
+    } else {
+        return -1;
+    };
+    let mut inner = task.inner_exclusive_access();
+    // 打开的文件数已达上限 (EMFILE)
+    let Some(fd) = inner.alloc_fd() else {
+        return -1;
+    };
+    inner.fd_table()[fd] = Some(FdEntry::new(file));
+    fd as isize
 }
 
 pub fn sys_close(fd: usize) -> isize {
     trace!("kernel:pid[{}] sys_close", current_task().unwrap().pid.0);
     let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let mut fd_table = inner.fd_table();
+    if fd >= fd_table.len() {
+        return -1;
+    }
+    if fd_table[fd].is_none() {
+        return -1;
+    }
+    fd_table[fd].take();
+    // fd 表按需增长，关闭末尾的 fd 时把空槽一并收回
+    while let Some(None) = fd_table.last() {
+        fd_table.pop();
+    }
+    0
+}
+
+/// Duplicate `oldfd` to exactly `newfd`, closing `newfd` first if it is open.
+/// Only O_CLOEXEC is accepted in `flags`
+pub fn sys_dup3(oldfd: usize, newfd: usize, flags: u32) -> isize {
+    trace!("kernel:pid[{}] sys_dup3", current_task().unwrap().pid.0);
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -1;
+    };
+    if !OpenFlags::CLOEXEC.contains(flags) || oldfd == newfd {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if newfd >= inner.fd_limit {
+        return -1;
+    }
+    let Some(file) = inner.file(oldfd) else {
+        return -1;
+    };
+    let mut fd_table = inner.fd_table();
+    if newfd >= fd_table.len() {
+        fd_table.resize(newfd + 1, None);
+    }
+    // 旧文件在此被替换并释放
+    fd_table[newfd] = Some(FdEntry {
+        file,
+        cloexec: flags.contains(OpenFlags::CLOEXEC),
+    });
+    newfd as isize
+}
+
+/// Create a pipe, write the fds of (read_end, write_end) into `pipe`
+pub fn sys_pipe(pipe: *mut usize) -> isize {
+    trace!("kernel:pid[{}] sys_pipe", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let token = current_user_token();
+    let mut inner = task.inner_exclusive_access();
+    let (pipe_read, pipe_write) = make_pipe();
+    let Some(read_fd) = inner.alloc_fd() else {
+        return -1;
+    };
+    inner.fd_table()[read_fd] = Some(FdEntry::new(pipe_read));
+    let Some(write_fd) = inner.alloc_fd() else {
+        // 写端分配失败时归还读端
+        inner.fd_table()[read_fd] = None;
+        return -1;
+    };
+    inner.fd_table()[write_fd] = Some(FdEntry::new(pipe_write));
+    *translated_refmut(token, pipe) = read_fd;
+    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
+    0
+}
+
+/// Create an anonymous in-memory file, return its fd
+pub fn sys_memfd_create(_name: *const u8, _flags: u32) -> isize {
+    trace!("kernel:pid[{}] sys_memfd_create", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
     let mut inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
+    let Some(fd) = inner.alloc_fd() else {
+        return -1;
+    };
+    inner.fd_table()[fd] = Some(FdEntry::new(Arc::new(MemFile::new())));
+    fd as isize
+}
+
+/// Read the dirents of directory `fd` into `buf` as `linux_dirent64` records,
+/// return the number of bytes filled, 0 at the end of the directory
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    trace!("kernel:pid[{}] sys_getdents64", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(fd) else {
+        return -1;
+    };
+    file.getdents(UserBuffer::new(translated_byte_buffer_mut(current_user_token(), buf, len)))
+}
+
+/// Reposition the offset of the file `fd`, return the new offset
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    trace!("kernel:pid[{}] sys_lseek", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if let Some(file) = inner.file(fd) {
+        drop(inner);
+        file.seek(offset, whence)
+    } else {
+        -1
+    }
+}
+
+/// Resize the regular file `fd` to `length` bytes, the fd must be writable
+pub fn sys_ftruncate(fd: usize, length: isize) -> isize {
+    trace!("kernel:pid[{}] sys_ftruncate", current_task().unwrap().pid.0);
+    if length < 0 || length as usize > u32::MAX as usize {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if let Some(file) = inner.file(fd) {
+        if !file.writable() {
+            return -1;
+        }
+        drop(inner);
+        if let Some(os_node) = file.as_any().downcast_ref::<OSInode>() {
+            if os_node.truncate(length as usize) {
+                0
+            } else {
+                -1
+            }
+        } else {
+            // 只有磁盘上的普通文件可以截断
+            -1
+        }
+    } else {
+        -1
+    }
+}
+
+/// Back [offset, offset + len) of the regular file `fd` with zeroed data
+/// blocks, growing the file if it ends before, all or nothing. Only mode 0
+/// is supported
+pub fn sys_fallocate(fd: usize, mode: usize, offset: isize, len: isize) -> isize {
+    trace!("kernel:pid[{}] sys_fallocate", current_task().unwrap().pid.0);
+    if mode != 0 || offset < 0 || len <= 0 {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(fd) else {
+        return -1;
+    };
+    if !file.writable() {
+        return -1;
+    }
+    match file.as_any().downcast_ref::<OSInode>().map(OSInode::inode) {
+        // 空间不足时不分配任何块 (ENOSPC)
+        Some(inode) if !inode.is_dir() && inode.allocate(offset as usize, len as usize) => 0,
+        _ => -1,
+    }
+}
+
+/// Apply flock() `operation`, one of LOCK_SH, LOCK_EX and LOCK_UN optionally
+/// with LOCK_NB, to the advisory lock of the open file `fd`. The lock is
+/// shared by fds dup()ed from the same open and released when the last of
+/// them is closed. Return -EWOULDBLOCK if LOCK_NB is given and another open
+/// of the file holds a conflicting lock
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    trace!("kernel:pid[{}] sys_flock", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(fd) else {
+        return -1;
+    };
+    // 只有磁盘上的文件可以加锁
+    let Some(os_node) = file.as_any().downcast_ref::<OSInode>() else {
+        return -EINVAL;
+    };
+    let nonblock = operation & LOCK_NB != 0;
+    let locked = match operation & !LOCK_NB {
+        LOCK_SH => os_node.lock(false, nonblock),
+        LOCK_EX => os_node.lock(true, nonblock),
+        LOCK_UN => {
+            os_node.unlock();
+            true
+        }
+        _ => return -EINVAL,
+    };
+    if locked {
+        0
+    } else {
+        -EWOULDBLOCK
+    }
+}
+
+/// Resize the regular file at `path` to `length` bytes
+pub fn sys_truncate(path: *const u8, length: isize) -> isize {
+    trace!("kernel:pid[{}] sys_truncate", current_task().unwrap().pid.0);
+    if length < 0 || length as usize > u32::MAX as usize {
+        return -1;
+    }
+    let path = translated_str(current_user_token(), path);
+    match find_path(path.as_str()) {
+        Some(inode) if !inode.is_dir() && inode.truncate(length as u32) => 0,
+        // 路径不存在、是目录或磁盘空间不足
+        _ => -1,
+    }
+}
+
+/// Check whether `path` exists and allows the access in `mode`, without opening it
+pub fn sys_access(path: *const u8, mode: u32) -> isize {
+    trace!("kernel:pid[{}] sys_access", current_task().unwrap().pid.0);
+    let Some(mode) = AccessFlags::from_bits(mode) else {
+        return -1;
+    };
+    let path = translated_str(current_user_token(), path);
+    let readable = mode.contains(AccessFlags::R_OK);
+    let writable = mode.contains(AccessFlags::W_OK);
+    match find_path(path.as_str()) {
+        // 目录不可按文件写入；exec 不检查 x 位，X_OK 总是满足
+        Some(inode) if !(writable && inode.is_dir()) && permits(&inode, readable, writable) => 0,
+        _ => -1,
+    }
+}
+
+/// Change the permission bits of the file at `path`
+pub fn sys_chmod(path: *const u8, mode: u32) -> isize {
+    trace!("kernel:pid[{}] sys_chmod", current_task().unwrap().pid.0);
+    if mode & !0o777 != 0 {
         return -1;
     }
-    if inner.fd_table[fd].is_none() {
+    let path = translated_str(current_user_token(), path);
+    if let Some(inode) = find_path(path.as_str()) {
+        inode.set_mode(mode as u16);
+        0
+    } else {
+        -1
+    }
+}
+
+/// Change the owner of the file at `path`, -1 if an id does not fit the
+/// 16 bits kept on disk. There are no users yet, so anyone may chown
+pub fn sys_chown(path: *const u8, uid: u32, gid: u32) -> isize {
+    trace!("kernel:pid[{}] sys_chown", current_task().unwrap().pid.0);
+    let (Ok(uid), Ok(gid)) = (u16::try_from(uid), u16::try_from(gid)) else {
         return -1;
+    };
+    let path = translated_str(current_user_token(), path);
+    if let Some(inode) = find_path(path.as_str()) {
+        inode.set_owner(uid, gid);
+        0
+    } else {
+        -1
+    }
+}
+
+/// Change the cwd of the current task to the directory at `path`
+pub fn sys_chdir(path: *const u8) -> isize {
+    trace!("kernel:pid[{}] sys_chdir", current_task().unwrap().pid.0);
+    let path = translated_str(current_user_token(), path);
+    match find_path(path.as_str()) {
+        Some(inode) if inode.is_dir() => {
+            current_task().unwrap().inner_exclusive_access().cwd = inode;
+            0
+        }
+        _ => -1,
     }
-    inner.fd_table[fd].take();
+}
+
+/// Change the cwd of the current task to the directory opened as `fd`
+pub fn sys_fchdir(fd: usize) -> isize {
+    trace!("kernel:pid[{}] sys_fchdir", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let Some(file) = inner.file(fd) else {
+        return -1;
+    };
+    // 直接使用 fd 背后的 inode，不再重新解析路径
+    let Some(inode) = file.as_any().downcast_ref::<OSInode>().map(OSInode::inode) else {
+        return -1;
+    };
+    if !inode.is_dir() {
+        return -1;
+    }
+    inner.cwd = inode;
     0
 }
 
-/// YOUR JOB: Implement fstat.
-pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
-    trace!(
-        "kernel:pid[{}] sys_fstat NOT IMPLEMENTED",
-        current_task().unwrap().pid.0
-    );
+/// Make the directory at `path` the root of the current task: absolute
+/// paths are resolved from it and `..` in it stays there. The cwd moves
+/// into it as well so relative paths cannot start outside. Children
+/// inherit the root
+pub fn sys_chroot(path: *const u8) -> isize {
+    trace!("kernel:pid[{}] sys_chroot", current_task().unwrap().pid.0);
+    let path = translated_str(current_user_token(), path);
+    match find_path(path.as_str()) {
+        Some(inode) if inode.is_dir() => {
+            let task = current_task().unwrap();
+            let mut inner = task.inner_exclusive_access();
+            inner.cwd = inode.clone();
+            inner.root = inode;
+            0
+        }
+        Some(_) => -ENOTDIR,
+        None => -ENOENT,
+    }
+}
+
+/// Mount the easy-fs on block device `device_id` at the directory `target`,
+/// with `MountFlags::OVERLAY` as a read-only layer under the directory's own entries
+pub fn sys_mount(device_id: usize, target: *const u8, flags: u32) -> isize {
+    trace!("kernel:pid[{}] sys_mount", current_task().unwrap().pid.0);
+    let Some(flags) = MountFlags::from_bits(flags) else {
+        return -1;
+    };
+    let target = translated_str(current_user_token(), target);
+    match find_path(target.as_str()) {
+        Some(inode) => mount(device_id, inode, flags.contains(MountFlags::OVERLAY)),
+        None => -1,
+    }
+}
+
+/// Unmount the filesystem mounted at `target`, fails while its files are open
+pub fn sys_umount(target: *const u8) -> isize {
+    trace!("kernel:pid[{}] sys_umount", current_task().unwrap().pid.0);
+    let target = translated_str(current_user_token(), target);
+    // 路径解析会越过挂载点，得到被挂载文件系统的根，overlay 挂载则得到挂载点目录本身
+    match find_path(target.as_str()) {
+        Some(root) => umount(root),
+        None => -1,
+    }
+}
+
+/// utimensat() time: set the time to the current time
+pub const UTIME_NOW: usize = (1 << 30) - 1;
+/// utimensat() time: leave the time unchanged
+pub const UTIME_OMIT: usize = (1 << 30) - 2;
+
+/// Set the access and modification time of `path` from `times[0]` and
+/// `times[1]`, both to the current time if `times` is null. A `usec` of
+/// UTIME_NOW or UTIME_OMIT sets that time to now or leaves it unchanged
+pub fn sys_utimensat(path: *const u8, times: *const TimeVal) -> isize {
+    trace!("kernel:pid[{}] sys_utimensat", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let Some(inode) = find_path(path.as_str()) else {
+        return -1;
+    };
+    // 没有 RTC，时间从开机开始计
+    let now = (get_time_us() / 1_000_000) as u32;
+    let (atime, mtime) = if times.is_null() {
+        (Some(now), Some(now))
+    } else {
+        // 逐字段读取，两个 TimeVal 可能跨页
+        let field = |i: usize| *translated_ref(token, (times as *const usize).wrapping_add(i));
+        let time = |i: usize| match field(i * 2 + 1) {
+            UTIME_NOW => Ok(Some(now)),
+            UTIME_OMIT => Ok(None),
+            usec if usec >= 1_000_000 => Err(()),
+            _ => u32::try_from(field(i * 2)).map(Some).map_err(|_| ()),
+        };
+        let (Ok(atime), Ok(mtime)) = (time(0), time(1)) else {
+            return -1;
+        };
+        (atime, mtime)
+    };
+    inode.set_times(atime, mtime);
+    0
+}
+
+/// fadvise() advice: no special treatment
+pub const POSIX_FADV_NORMAL: usize = 0;
+/// fadvise() advice: the data will be accessed randomly
+pub const POSIX_FADV_RANDOM: usize = 1;
+/// fadvise() advice: the data will be accessed sequentially
+pub const POSIX_FADV_SEQUENTIAL: usize = 2;
+/// fadvise() advice: the data will be accessed soon
+pub const POSIX_FADV_WILLNEED: usize = 3;
+/// fadvise() advice: the data will not be accessed soon
+pub const POSIX_FADV_DONTNEED: usize = 4;
+/// fadvise() advice: the data will be accessed only once
+pub const POSIX_FADV_NOREUSE: usize = 5;
+
+/// Give the kernel a hint about the use of [offset, offset + len) of the file
+/// `fd`, up to its end if `len` is 0. POSIX_FADV_DONTNEED drops the cached
+/// blocks of the range, the other advice is accepted and ignored
+pub fn sys_fadvise(fd: usize, offset: usize, len: usize, advice: usize) -> isize {
+    trace!("kernel:pid[{}] sys_fadvise", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(fd) else {
+        return -1;
+    };
+    // 管道等没有磁盘块的文件 (ESPIPE)
+    let Some(os_node) = file.as_any().downcast_ref::<OSInode>() else {
+        return -1;
+    };
+    match advice {
+        POSIX_FADV_DONTNEED => {
+            let len = if len == 0 { usize::MAX } else { len };
+            match os_node.inode().drop_cache(offset, len) {
+                Ok(()) => 0,
+                Err(_) => -1,
+            }
+        }
+        POSIX_FADV_NORMAL | POSIX_FADV_RANDOM | POSIX_FADV_SEQUENTIAL | POSIX_FADV_WILLNEED
+        | POSIX_FADV_NOREUSE => 0,
+        _ => -1,
+    }
+}
+
+/// Load [offset, offset + count) of the file `fd` into the block cache ahead
+/// of reads, as far as the end of the file and the room of the cache allow
+pub fn sys_readahead(fd: usize, offset: usize, count: usize) -> isize {
+    trace!("kernel:pid[{}] sys_readahead", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(fd) else {
+        return -1;
+    };
+    // 只有磁盘文件有块可以预读 (EINVAL)
+    let Some(os_node) = file.as_any().downcast_ref::<OSInode>() else {
+        return -1;
+    };
+    os_node.inode().readahead(offset, count);
+    0
+}
+
+/// Write all cached data of every mounted filesystem to its device, durable
+/// once this returns 0
+pub fn sys_sync() -> isize {
+    trace!("kernel:pid[{}] sys_sync", current_task().unwrap().pid.0);
+    sync_all()
+}
+
+/// Send out what the file `fd` still buffers in memory, and for a file on
+/// disk write the cached blocks back to the device
+pub fn sys_fsync(fd: usize) -> isize {
+    trace!("kernel:pid[{}] sys_fsync", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(fd) else {
+        return -1;
+    };
+    file.flush();
+    // 磁盘文件还要把块缓存写回设备
+    if file.as_any().is::<OSInode>() {
+        return sync_all();
+    }
+    0
+}
+
+/// Copy up to `count` bytes from `in_fd` to `out_fd` inside the kernel.
+/// If `offset` is not null, read the regular file `in_fd` from `*offset`
+/// without moving its file offset, and store the offset after the last byte
+/// read back to `*offset`. Return the number of bytes transferred
+pub fn sys_sendfile(out_fd: usize, in_fd: usize, offset: *mut isize, count: usize) -> isize {
+    trace!("kernel:pid[{}] sys_sendfile", current_task().unwrap().pid.0);
+    let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
+    let (Some(out_file), Some(in_file)) = (inner.file(out_fd), inner.file(in_fd)) else {
+        return -1;
+    };
+    drop(inner);
+    if !out_file.writable() || !in_file.readable() {
+        return -1;
+    }
+    let mut pos = if offset.is_null() {
+        None
+    } else {
+        let start = *translated_ref(token, offset);
+        // 指定偏移量时只支持磁盘上的普通文件
+        if start < 0 || in_file.as_any().downcast_ref::<OSInode>().is_none() {
+            return -1;
+        }
+        Some(start as usize)
+    };
+    let mut buffer = vec![0u8; PAGE_SIZE];
+    let mut total = 0usize;
+    while total < count {
+        let len = (count - total).min(PAGE_SIZE);
+        let read_size = match pos {
+            Some(start) => {
+                let os_node = in_file.as_any().downcast_ref::<OSInode>().unwrap();
+                os_node.read_at(start, &mut buffer[..len])
+            }
+            None => in_file.read(unsafe { UserBuffer::from_kernel(&mut buffer[..len]) }),
+        };
+        // 读到文件末尾
+        if read_size == 0 {
+            break;
+        }
+        let write_size =
+            out_file.write(unsafe { UserBuffer::from_kernel(&mut buffer[..read_size]) });
+        total += write_size;
+        if let Some(start) = pos.as_mut() {
+            *start += write_size;
+        }
+        if write_size < read_size {
+            break;
+        }
+    }
+    if let Some(end) = pos {
+        *translated_refmut(token, offset) = end as isize;
+    }
+    total as isize
+}
 
-    // check legality
-    if fd >= inner.fd_table.len() {
+/// recvfrom() flag: return the buffered bytes without consuming them
+pub const MSG_PEEK: u32 = 2;
+
+/// Read from the pipe `fd` like read(), or with MSG_PEEK copy what it
+/// buffers without consuming it, so the next read returns the same bytes.
+/// Pipes stand in for sockets, other files and flags are refused
+pub fn sys_recvfrom(fd: usize, buf: *const u8, len: usize, flags: u32) -> isize {
+    trace!("kernel:pid[{}] sys_recvfrom", current_task().unwrap().pid.0);
+    if flags & !MSG_PEEK != 0 {
         return -1;
     }
-    if inner.fd_table[fd].is_none() {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(fd) else {
+        return -1;
+    };
+    // 不是套接字 (ENOTSOCK)
+    let Some(pipe) = file.as_any().downcast_ref::<Pipe>() else {
+        return -1;
+    };
+    if !pipe.readable() {
         return -1;
     }
+    let buf = UserBuffer::new(translated_byte_buffer_mut(token, buf, len));
+    if flags & MSG_PEEK != 0 {
+        pipe.peek(buf) as isize
+    } else {
+        pipe.read(buf) as isize
+    }
+}
+
+/// tee() flag: fail instead of waiting for data or room
+pub const SPLICE_F_NONBLOCK: u32 = 2;
+
+/// Duplicate up to `len` bytes buffered in the pipe `fd_in` into the pipe
+/// `fd_out` without consuming them, so they can still be read from `fd_in`.
+/// Flags other than SPLICE_F_NONBLOCK are accepted and ignored.
+/// Return the number of bytes duplicated, 0 if `fd_in` is empty and has
+/// no writer left
+pub fn sys_tee(fd_in: usize, fd_out: usize, len: usize, flags: u32) -> isize {
+    trace!("kernel:pid[{}] sys_tee", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let (Some(in_file), Some(out_file)) = (inner.file(fd_in), inner.file(fd_out)) else {
+        return -1;
+    };
+    drop(inner);
+    // 两端都必须是管道，且不能是同一个管道 (EINVAL)
+    let (Some(in_pipe), Some(out_pipe)) = (
+        in_file.as_any().downcast_ref::<Pipe>(),
+        out_file.as_any().downcast_ref::<Pipe>(),
+    ) else {
+        return -1;
+    };
+    if !in_pipe.readable() || !out_pipe.writable() || in_pipe.same_pipe(out_pipe) {
+        return -1;
+    }
+    match in_pipe.tee(out_pipe, len, flags & SPLICE_F_NONBLOCK != 0) {
+        Some(copied) => copied as isize,
+        // 非阻塞时没有数据或空间 (EAGAIN)
+        None => -1,
+    }
+}
 
-    let ino: u64;
-    let nlink: u32;
-    if let Some(file_node) = &inner.fd_table[fd] {
-        let any: &dyn Any = file_node.as_any();
-        let os_node = any.downcast_ref::<OSInode>().unwrap();
-        ino = os_node.get_inode_id();
-        let (block_id, block_offset) = os_node.get_inode_pos();
-        nlink = ROOT_INODE.get_link_num(block_id, block_offset);
+/// Move up to `len` bytes between a pipe and a file inside the kernel, from
+/// the pipe `fd_in` to the file `fd_out` or from the file `fd_in` to the
+/// pipe `fd_out`, copying straight between the pipe buffer and the file.
+/// The offset pointer of the pipe must be null. The one of the file, if
+/// not null, gives the file position to use without moving the file
+/// offset and receives the position after the last byte moved.
+/// Flags other than SPLICE_F_NONBLOCK are accepted and ignored.
+/// Return the number of bytes moved
+pub fn sys_splice(
+    fd_in: usize,
+    off_in: *mut isize,
+    fd_out: usize,
+    off_out: *mut isize,
+    len: usize,
+    flags: u32,
+) -> isize {
+    trace!("kernel:pid[{}] sys_splice", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let (Some(in_file), Some(out_file)) = (inner.file(fd_in), inner.file(fd_out)) else {
+        return -1;
+    };
+    drop(inner);
+    if !in_file.readable() || !out_file.writable() {
+        return -1;
+    }
+    if is_stale(&in_file) || is_stale(&out_file) {
+        return -ESTALE;
+    }
+    let nonblock = flags & SPLICE_F_NONBLOCK != 0;
+    // 一端是管道，另一端是磁盘文件
+    let (pipe, os_node, off, to_file) = match (
+        in_file.as_any().downcast_ref::<Pipe>(),
+        out_file.as_any().downcast_ref::<OSInode>(),
+        in_file.as_any().downcast_ref::<OSInode>(),
+        out_file.as_any().downcast_ref::<Pipe>(),
+    ) {
+        (Some(pipe), Some(os_node), _, _) if off_in.is_null() => (pipe, os_node, off_out, true),
+        (_, _, Some(os_node), Some(pipe)) if off_out.is_null() => (pipe, os_node, off_in, false),
+        _ => return -1,
+    };
+    let mut pos = if off.is_null() {
+        None
     } else {
+        let start = *translated_ref(token, off);
+        if start < 0 {
+            return -1;
+        }
+        Some(start as usize)
+    };
+    let moved = if to_file {
+        pipe.splice_to(len, nonblock, |bytes| {
+            let write_size = os_node.write_to(pos, bytes);
+            if let Some(start) = pos.as_mut() {
+                *start += write_size;
+            }
+            write_size
+        })
+    } else {
+        pipe.splice_from(len, nonblock, |bytes| {
+            let read_size = os_node.read_from(pos, bytes);
+            if let Some(start) = pos.as_mut() {
+                *start += read_size;
+            }
+            read_size
+        })
+    };
+    // 非阻塞时管道中没有数据或空间 (EAGAIN)
+    let Some(moved) = moved else {
         return -1;
+    };
+    if let Some(end) = pos {
+        *translated_refmut(token, off) = end as isize;
     }
+    moved as isize
+}
 
-    let stat = &Stat {
+/// Wait until one of the `nfds` fds at `fds` is ready or `timeout` ms pass,
+/// 0 returns at once and a negative timeout waits forever.
+/// Return the number of fds with non-zero `revents`, 0 on timeout
+pub fn sys_poll(fds: *mut PollFd, nfds: usize, timeout: isize) -> isize {
+    trace!("kernel:pid[{}] sys_poll", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    let deadline = (timeout > 0).then(|| get_time_ms() + timeout as usize);
+    loop {
+        let mut ready = 0;
+        for i in 0..nfds {
+            let pollfd = translated_refmut(token, unsafe { fds.add(i) });
+            pollfd.revents = 0;
+            if pollfd.fd < 0 {
+                continue;
+            }
+            let task = current_task().unwrap();
+            let file = task.inner_exclusive_access().file(pollfd.fd as usize);
+            pollfd.revents = match file {
+                Some(file) => {
+                    let mut revents = 0;
+                    if pollfd.events & POLLIN != 0 && file.readable() && file.read_ready() {
+                        revents |= POLLIN;
+                    }
+                    if pollfd.events & POLLOUT != 0 && file.writable() && file.write_ready() {
+                        revents |= POLLOUT;
+                    }
+                    revents
+                }
+                _ => POLLNVAL,
+            };
+            if pollfd.revents != 0 {
+                ready += 1;
+            }
+        }
+        if ready > 0 || timeout == 0 {
+            return ready;
+        }
+        if deadline.is_some_and(|deadline| get_time_ms() >= deadline) {
+            return 0;
+        }
+        // 没有 fd 就绪：睡到下一个时钟中断或某个管道状态改变，再重新检查 fd 和截止时间
+        TICK_QUEUE.wait();
+    }
+}
+
+/// Create an epoll instance, return its fd
+pub fn sys_epoll_create() -> isize {
+    trace!("kernel:pid[{}] sys_epoll_create", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let Some(fd) = inner.alloc_fd() else {
+        return -1;
+    };
+    inner.fd_table()[fd] = Some(FdEntry::new(Arc::new(Epoll::new())));
+    fd as isize
+}
+
+/// Register, modify or remove (per `op`) the fd `fd` on the epoll
+/// instance `epfd`, watching the events in `*event`
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: *const EpollEvent) -> isize {
+    trace!("kernel:pid[{}] sys_epoll_ctl", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let (Some(epoll), Some(file)) = (inner.file(epfd), inner.file(fd)) else {
+        return -1;
+    };
+    drop(inner);
+    let Some(epoll) = epoll.as_any().downcast_ref::<Epoll>() else {
+        return -1;
+    };
+    // 不允许 epoll 监视自身
+    if epfd == fd {
+        return -1;
+    }
+    // 删除时不读取 event，允许传入空指针
+    let event = if op == EPOLL_CTL_DEL {
+        EpollEvent { events: 0, data: 0 }
+    } else {
+        *translated_ref(current_user_token(), event)
+    };
+    epoll.ctl(op, fd, &file, event)
+}
+
+/// Wait until one of the fds registered on `epfd` is ready or `timeout` ms
+/// pass, with the same timeout rules as poll(). Store the events of up to
+/// `maxevents` ready fds at `events`, return their number, 0 on timeout
+pub fn sys_epoll_wait(epfd: usize, events: *mut EpollEvent, maxevents: usize, timeout: isize) -> isize {
+    trace!("kernel:pid[{}] sys_epoll_wait", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(epfd) else {
+        return -1;
+    };
+    let Some(epoll) = file.as_any().downcast_ref::<Epoll>() else {
+        return -1;
+    };
+    if maxevents == 0 {
+        return -1;
+    }
+    let token = current_user_token();
+    let deadline = (timeout > 0).then(|| get_time_ms() + timeout as usize);
+    loop {
+        let ready = epoll.ready(maxevents);
+        if !ready.is_empty() || timeout == 0 {
+            for (i, event) in ready.iter().enumerate() {
+                *translated_refmut(token, unsafe { events.add(i) }) = *event;
+            }
+            return ready.len() as isize;
+        }
+        if deadline.is_some_and(|deadline| get_time_ms() >= deadline) {
+            return 0;
+        }
+        // 没有 fd 就绪：与 poll 一样睡到下一个时钟中断或某个管道状态改变
+        TICK_QUEUE.wait();
+    }
+}
+
+/// YOUR JOB: Implement fstat.
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_fstat NOT IMPLEMENTED",
+        current_task().unwrap().pid.0
+    );
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+
+    // check legality
+    let Some(file_node) = inner.file(fd) else {
+        return -1;
+    };
+
+    let stat = &mut Stat {
         dev: 0,
-        ino: ino,
+        ino: 0,
         mode: StatMode::FILE,
-        nlink: nlink,
-        pad: [0;7],
+        nlink: 0,
+        size: 0,
+        uid: 0,
+        gid: 0,
+        atime: 0,
+        mtime: 0,
+        pad: [0; 3],
     };
+    let any: &dyn Any = file_node.as_any();
+    if let Some(os_node) = any.downcast_ref::<OSInode>() {
+        stat.ino = os_node.get_inode_id();
+        stat.nlink = os_node.inode().nlink();
+        stat.size = os_node.size() as u64;
+        let (uid, gid) = os_node.inode().owner();
+        stat.uid = uid as u32;
+        stat.gid = gid as u32;
+        let (atime, mtime) = os_node.inode().times();
+        stat.atime = atime as u64;
+        stat.mtime = mtime as u64;
+        let (mode, is_dir) = os_node.mode();
+        let file_type = if is_dir { StatMode::DIR } else { StatMode::FILE };
+        stat.mode = file_type | StatMode::from_bits_truncate(mode as u32);
+    } else if file_node.fstat(stat) != 0 {
+        // 非磁盘文件交给各自的 fstat 实现
+        return -1;
+    }
 
     // copy data from kernel space to user space
     let token = inner.get_user_token();
-    let st = translated_byte_buffer(token, st as *const u8, core::mem::size_of::<Stat>());
-    let stat_ptr = stat as *const _ as *const u8;
-    for (idx, byte) in st.into_iter().enumerate() {
-        unsafe {
-            byte.copy_from_slice(core::slice::from_raw_parts(stat_ptr.wrapping_byte_add(idx), byte.len()));
-        }
+    drop(inner);
+    let mut st = UserBuffer::new(translated_byte_buffer_mut(
+        token,
+        st as *const u8,
+        core::mem::size_of::<Stat>(),
+    ));
+    let stat_bytes = unsafe {
+        core::slice::from_raw_parts(stat as *const _ as *const u8, core::mem::size_of::<Stat>())
+    };
+    st.copy_from_slice(stat_bytes);
+    0
+}
+
+/// Report the usage of the filesystem holding `path`
+pub fn sys_statfs(path: *const u8, buf: *mut StatFs) -> isize {
+    trace!("kernel:pid[{}] sys_statfs", current_task().unwrap().pid.0);
+    let path = translated_str(current_user_token(), path);
+    match find_path(path.as_str()) {
+        Some(inode) => copy_statfs(&inode, buf),
+        None => -1,
     }
+}
+
+/// Report the usage of the filesystem holding the file `fd`
+pub fn sys_fstatfs(fd: usize, buf: *mut StatFs) -> isize {
+    trace!("kernel:pid[{}] sys_fstatfs", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(fd) else {
+        return -1;
+    };
+    // 只有磁盘上的文件属于某个文件系统
+    match file.as_any().downcast_ref::<OSInode>() {
+        Some(os_node) => copy_statfs(&os_node.inode(), buf),
+        None => -1,
+    }
+}
+
+/// Fill the user `StatFs` at `buf` from the filesystem of `inode`,
+/// the struct may straddle two pages
+fn copy_statfs(inode: &Inode, buf: *mut StatFs) -> isize {
+    let fs_stat = inode.stat_fs();
+    let statfs = StatFs {
+        bsize: fs_stat.block_size as u64,
+        blocks: fs_stat.total_blocks as u64,
+        bfree: fs_stat.free_blocks as u64,
+        files: fs_stat.total_inodes as u64,
+        ffree: fs_stat.free_inodes as u64,
+        maxfilesize: fs_stat.max_file_size as u64,
+    };
+    let mut user_buf = UserBuffer::new(translated_byte_buffer_mut(
+        current_user_token(),
+        buf as *const u8,
+        core::mem::size_of::<StatFs>(),
+    ));
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            &statfs as *const _ as *const u8,
+            core::mem::size_of::<StatFs>(),
+        )
+    };
+    user_buf.copy_from_slice(bytes);
     0
 }
 
-/// YOUR JOB: Implement linkat.
+/// Reset the terminal of the current task as vhangup(2) does: input typed
+/// but not read yet is discarded
+pub fn sys_vhangup() -> isize {
+    trace!("kernel:pid[{}] sys_vhangup", current_task().unwrap().pid.0);
+    current_task().unwrap().inner_exclusive_access().reset_terminal();
+    0
+}
+
+/// Add the hard link `new_name` to the file `old_name`.
+/// Return -1 if `old_name` is missing or a directory, or `new_name` exists
 pub fn sys_linkat(old_name: *const u8, new_name: *const u8) -> isize {
-    trace!(
-        "kernel:pid[{}] sys_linkat NOT IMPLEMENTED",
-        current_task().unwrap().pid.0
-    );
+    trace!("kernel:pid[{}] sys_linkat", current_task().unwrap().pid.0);
     let token = current_user_token();
     let old = translated_str(token, old_name);
     let new = translated_str(token, new_name);
-    println!("link {} to {}", new , old);
-    if old.as_str() != new.as_str() {
-        if let Some(_) = ROOT_INODE.link(old.as_str(), new.as_str()) {
-            return 0;
-        }
-    }
-    -1
+    link_path(old.as_str(), new.as_str())
 }
 
-/// YOUR JOB: Implement unlinkat.
+/// Remove the link `name` to a file, freeing the file with its last link.
+/// Return -1 if `name` is missing or a directory
 pub fn sys_unlinkat(name: *const u8) -> isize {
-    trace!(
-        "kernel:pid[{}] sys_unlinkat NOT IMPLEMENTED",
-        current_task().unwrap().pid.0
-    );
+    trace!("kernel:pid[{}] sys_unlinkat", current_task().unwrap().pid.0);
     let token = current_user_token();
     let name = translated_str(token, name);
-    if let Some(inode) = ROOT_INODE.find(name.as_str()) {
-        if ROOT_INODE.get_link_num(inode.block_id, inode.block_offset) == 1 {
-            // clear data if only one link exists
-            inode.clear();
+    remove(name.as_str())
+}
+
+/// Move the file or directory `old_path` to `new_path`, replacing an existing
+/// file or empty directory there
+pub fn sys_rename(old_path: *const u8, new_path: *const u8) -> isize {
+    trace!("kernel:pid[{}] sys_rename", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    let old_path = translated_str(token, old_path);
+    let new_path = translated_str(token, new_path);
+    rename_path(old_path.as_str(), new_path.as_str())
+}
+
+/// Create the directory `path` with permission bits `mode`.
+/// Return -EEXIST if it exists, -ENOENT/-ENOTDIR if its parent is missing
+/// or not a directory, -ENAMETOOLONG if the last component does not fit or
+/// the path has too many components, -ENOSPC if the disk is full
+pub fn sys_mkdir(path: *const u8, mode: u32) -> isize {
+    trace!("kernel:pid[{}] sys_mkdir", current_task().unwrap().pid.0);
+    let path = translated_str(current_user_token(), path);
+    if path_too_deep(path.as_str()) {
+        return -ENAMETOOLONG;
+    }
+    let Some((parent, name)) = split_path(path.as_str()) else {
+        // 根目录总是存在
+        return -EEXIST;
+    };
+    if name.len() > NAME_LENGTH_LIMIT {
+        return -ENAMETOOLONG;
+    }
+    if find_path(path.as_str()).is_some() {
+        return -EEXIST;
+    }
+    match find_path(parent) {
+        None => return -ENOENT,
+        Some(dir) if !dir.is_dir() => return -ENOTDIR,
+        Some(_) => {}
+    }
+    let Some(dir) = copy_up(parent) else {
+        return -ENOENT;
+    };
+    match dir.create_dir(name) {
+        Some(inode) => {
+            inode.set_mode((mode & 0o777) as u16);
+            0
         }
-        return ROOT_INODE.unlink(name.as_str());
+        // 名字已检查过，只剩 inode 或数据块分配失败
+        None => -ENOSPC,
+    }
+}
+
+/// Remove the empty directory `path`.
+/// Return -ENOENT, -ENOTDIR, -ENOTEMPTY, -EINVAL (for `.` and `..`) or
+/// -ENAMETOOLONG
+pub fn sys_rmdir(path: *const u8) -> isize {
+    trace!("kernel:pid[{}] sys_rmdir", current_task().unwrap().pid.0);
+    let path = translated_str(current_user_token(), path);
+    if path_too_deep(path.as_str()) {
+        return -ENAMETOOLONG;
+    }
+    match remove_dir(path.as_str()) {
+        Ok(()) => 0,
+        Err(RemoveError::NotFound) => -ENOENT,
+        Err(RemoveError::NotDir) => -ENOTDIR,
+        Err(RemoveError::NotEmpty) => -ENOTEMPTY,
+        Err(RemoveError::Invalid) => -EINVAL,
     }
-    -1
 }
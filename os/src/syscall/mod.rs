@@ -10,46 +10,150 @@
 //! `sys_` then the name of the syscall. You can find functions like this in
 //! submodules, and you should also implement syscalls this way.
 
+/// epoll_create1 syscall
+const SYSCALL_EPOLL_CREATE1: usize = 20;
+/// epoll_ctl syscall
+const SYSCALL_EPOLL_CTL: usize = 21;
+/// epoll_pwait syscall
+const SYSCALL_EPOLL_PWAIT: usize = 22;
+/// dup3 syscall
+const SYSCALL_DUP3: usize = 24;
+/// flock syscall
+const SYSCALL_FLOCK: usize = 32;
+/// mkdirat syscall
+const SYSCALL_MKDIRAT: usize = 34;
 /// unlinkat syscall
 const SYSCALL_UNLINKAT: usize = 35;
 /// linkat syscall
 const SYSCALL_LINKAT: usize = 37;
+/// renameat syscall
+const SYSCALL_RENAMEAT: usize = 38;
+/// umount2 syscall
+const SYSCALL_UMOUNT2: usize = 39;
+/// mount syscall
+const SYSCALL_MOUNT: usize = 40;
+/// statfs syscall
+const SYSCALL_STATFS: usize = 43;
+/// fstatfs syscall
+const SYSCALL_FSTATFS: usize = 44;
+/// truncate syscall
+const SYSCALL_TRUNCATE: usize = 45;
+/// ftruncate syscall
+const SYSCALL_FTRUNCATE: usize = 46;
+/// fallocate syscall
+const SYSCALL_FALLOCATE: usize = 47;
+/// faccessat syscall
+const SYSCALL_FACCESSAT: usize = 48;
+/// chdir syscall
+const SYSCALL_CHDIR: usize = 49;
+/// fchdir syscall
+const SYSCALL_FCHDIR: usize = 50;
+/// chroot syscall
+const SYSCALL_CHROOT: usize = 51;
+/// fchmodat syscall
+const SYSCALL_FCHMODAT: usize = 53;
+/// fchownat syscall
+const SYSCALL_FCHOWNAT: usize = 54;
 /// open syscall
 const SYSCALL_OPEN: usize = 56;
 /// close syscall
 const SYSCALL_CLOSE: usize = 57;
+/// vhangup syscall
+const SYSCALL_VHANGUP: usize = 58;
+/// pipe syscall
+const SYSCALL_PIPE: usize = 59;
+/// getdents64 syscall
+const SYSCALL_GETDENTS64: usize = 61;
+/// lseek syscall
+const SYSCALL_LSEEK: usize = 62;
 /// read syscall
 const SYSCALL_READ: usize = 63;
 /// write syscall
 const SYSCALL_WRITE: usize = 64;
+/// pread64 syscall
+const SYSCALL_PREAD: usize = 67;
+/// pwrite64 syscall
+const SYSCALL_PWRITE: usize = 68;
+/// sendfile syscall
+const SYSCALL_SENDFILE: usize = 71;
+/// poll syscall
+const SYSCALL_POLL: usize = 73;
+/// splice syscall
+const SYSCALL_SPLICE: usize = 76;
+/// tee syscall
+const SYSCALL_TEE: usize = 77;
 /// fstat syscall
 const SYSCALL_FSTAT: usize = 80;
+/// sync syscall
+const SYSCALL_SYNC: usize = 81;
+/// fsync syscall
+const SYSCALL_FSYNC: usize = 82;
+/// utimensat syscall
+const SYSCALL_UTIMENSAT: usize = 88;
 /// exit syscall
 const SYSCALL_EXIT: usize = 93;
+/// clock_gettime syscall
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+/// sched_setaffinity syscall
+const SYSCALL_SCHED_SETAFFINITY: usize = 122;
+/// sched_getaffinity syscall
+const SYSCALL_SCHED_GETAFFINITY: usize = 123;
 /// yield syscall
 const SYSCALL_YIELD: usize = 124;
 /// setpriority syscall
 const SYSCALL_SET_PRIORITY: usize = 140;
+/// getrlimit syscall
+const SYSCALL_GETRLIMIT: usize = 163;
+/// setrlimit syscall
+const SYSCALL_SETRLIMIT: usize = 164;
+/// prctl syscall
+const SYSCALL_PRCTL: usize = 167;
+/// getcpu syscall
+const SYSCALL_GETCPU: usize = 168;
 /// gettime syscall
 const SYSCALL_GET_TIME: usize = 169;
 /// getpid syscall
 const SYSCALL_GETPID: usize = 172;
+/// getppid syscall
+const SYSCALL_GETPPID: usize = 173;
+/// gettid syscall
+const SYSCALL_GETTID: usize = 178;
+/// recvfrom syscall
+const SYSCALL_RECVFROM: usize = 207;
+/// readahead syscall
+const SYSCALL_READAHEAD: usize = 213;
 /// sbrk syscall
 const SYSCALL_SBRK: usize = 214;
 /// munmap syscall
 const SYSCALL_MUNMAP: usize = 215;
-/// fork syscall
-const SYSCALL_FORK: usize = 220;
+/// clone syscall, fork is clone without flags
+const SYSCALL_CLONE: usize = 220;
 /// exec syscall
 const SYSCALL_EXEC: usize = 221;
 /// mmap syscall
 const SYSCALL_MMAP: usize = 222;
+/// fadvise64 syscall
+const SYSCALL_FADVISE64: usize = 223;
+/// msync syscall
+const SYSCALL_MSYNC: usize = 227;
+/// madvise syscall
+const SYSCALL_MADVISE: usize = 233;
 /// waitpid syscall
 const SYSCALL_WAITPID: usize = 260;
+/// process_vm_readv syscall
+const SYSCALL_PROCESS_VM_READV: usize = 270;
+/// memfd_create syscall
+const SYSCALL_MEMFD_CREATE: usize = 279;
 /// spawn syscall
 const SYSCALL_SPAWN: usize = 400;
 /// taskinfo syscall
 const SYSCALL_TASK_INFO: usize = 410;
+/// wait4 syscall. Linux numbers it 260, which the non-blocking waitpid
+/// already takes here, so it is a private number like spawn and taskinfo
+const SYSCALL_WAIT4: usize = 420;
+/// mmap syscall with flags, fd and offset, kept apart from the mmap of the
+/// lab interface, which passes only start, len and prot
+const SYSCALL_MMAP_FILE: usize = 430;
 
 mod fs;
 mod process;
@@ -57,30 +161,94 @@ use crate::task::update_taskinfo;
 use fs::*;
 pub use process::*;
 
-use crate::fs::Stat;
+use crate::fs::{EpollEvent, Stat, StatFs};
 
 /// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 4]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     update_taskinfo(syscall_id);
     match syscall_id {
-        SYSCALL_OPEN => sys_open(args[1] as *const u8, args[2] as u32),
+        SYSCALL_EPOLL_CREATE1 => sys_epoll_create(),
+        SYSCALL_EPOLL_CTL => sys_epoll_ctl(args[0], args[1], args[2], args[3] as *const EpollEvent),
+        SYSCALL_EPOLL_PWAIT => sys_epoll_wait(args[0], args[1] as *mut EpollEvent, args[2], args[3] as isize),
+        SYSCALL_FACCESSAT => sys_access(args[1] as *const u8, args[2] as u32),
+        SYSCALL_CHDIR => sys_chdir(args[0] as *const u8),
+        SYSCALL_FCHDIR => sys_fchdir(args[0]),
+        SYSCALL_CHROOT => sys_chroot(args[0] as *const u8),
+        SYSCALL_FCHMODAT => sys_chmod(args[1] as *const u8, args[2] as u32),
+        SYSCALL_FCHOWNAT => sys_chown(args[1] as *const u8, args[2] as u32, args[3] as u32),
+        SYSCALL_DUP3 => sys_dup3(args[0], args[1], args[2] as u32),
+        SYSCALL_OPEN => sys_openat(args[0] as isize, args[1] as *const u8, args[2] as u32, args[3] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
+        SYSCALL_VHANGUP => sys_vhangup(),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize),
         SYSCALL_LINKAT => sys_linkat(args[1] as *const u8, args[3] as *const u8),
+        SYSCALL_FLOCK => sys_flock(args[0], args[1]),
+        SYSCALL_MKDIRAT => sys_mkdir(args[1] as *const u8, args[2] as u32),
+        SYSCALL_UNLINKAT if args[2] & AT_REMOVEDIR != 0 => sys_rmdir(args[1] as *const u8),
         SYSCALL_UNLINKAT => sys_unlinkat(args[1] as *const u8),
+        SYSCALL_RENAMEAT => sys_rename(args[1] as *const u8, args[3] as *const u8),
+        SYSCALL_UMOUNT2 => sys_umount(args[0] as *const u8),
+        SYSCALL_MOUNT => sys_mount(args[0], args[1] as *const u8, args[2] as u32),
+        SYSCALL_STATFS => sys_statfs(args[0] as *const u8, args[1] as *mut StatFs),
+        SYSCALL_FSTATFS => sys_fstatfs(args[0], args[1] as *mut StatFs),
+        SYSCALL_GETDENTS64 => sys_getdents64(args[0], args[1] as *mut u8, args[2]),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
+        SYSCALL_TRUNCATE => sys_truncate(args[0] as *const u8, args[1] as isize),
+        SYSCALL_FTRUNCATE => sys_ftruncate(args[0], args[1] as isize),
+        SYSCALL_FALLOCATE => sys_fallocate(args[0], args[1], args[2] as isize, args[3] as isize),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_PREAD => sys_pread(args[0], args[1] as *const u8, args[2], args[3] as isize),
+        SYSCALL_PWRITE => sys_pwrite(args[0], args[1] as *const u8, args[2], args[3] as isize),
+        SYSCALL_SENDFILE => sys_sendfile(args[0], args[1], args[2] as *mut isize, args[3]),
+        SYSCALL_SPLICE => sys_splice(
+            args[0],
+            args[1] as *mut isize,
+            args[2],
+            args[3] as *mut isize,
+            args[4],
+            args[5] as u32,
+        ),
+        SYSCALL_TEE => sys_tee(args[0], args[1], args[2], args[3] as u32),
+        SYSCALL_RECVFROM => sys_recvfrom(args[0], args[1] as *const u8, args[2], args[3] as u32),
+        SYSCALL_POLL => sys_poll(args[0] as *mut PollFd, args[1], args[2] as isize),
         SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
+        SYSCALL_SYNC => sys_sync(),
+        SYSCALL_FSYNC => sys_fsync(args[0]),
+        SYSCALL_UTIMENSAT => sys_utimensat(args[1] as *const u8, args[2] as *const TimeVal),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
         SYSCALL_YIELD => sys_yield(),
+        SYSCALL_SCHED_SETAFFINITY => sys_sched_setaffinity(args[0], args[1]),
+        SYSCALL_SCHED_GETAFFINITY => sys_sched_getaffinity(args[0]),
+        SYSCALL_GETCPU => sys_getcpu(args[0] as *mut u32, args[1] as *mut u32),
         SYSCALL_GETPID => sys_getpid(),
-        SYSCALL_FORK => sys_fork(),
+        SYSCALL_GETPPID => sys_getppid(),
+        SYSCALL_GETTID => sys_gettid(),
+        SYSCALL_CLONE => sys_clone(args[0], args[1]),
         SYSCALL_EXEC => sys_exec(args[0] as *const u8),
         SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_WAIT4 => sys_wait4(
+            args[0] as isize,
+            args[1] as *mut i32,
+            args[2],
+            args[3] as *mut RUsage,
+        ),
+        SYSCALL_GETRLIMIT => sys_getrlimit(args[0], args[1] as *mut RLimit),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], args[1] as *const RLimit),
+        SYSCALL_PRCTL => sys_prctl(args[0], args[1]),
         SYSCALL_GET_TIME => sys_get_time(args[0] as *mut TimeVal, args[1]),
+        SYSCALL_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1] as *mut TimeVal),
         SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
         SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
+        SYSCALL_MMAP_FILE => sys_mmap_file(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_FADVISE64 => sys_fadvise(args[0], args[1], args[2], args[3]),
+        SYSCALL_READAHEAD => sys_readahead(args[0], args[1], args[2]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_MSYNC => sys_msync(args[0], args[1], args[2]),
+        SYSCALL_MADVISE => sys_madvise(args[0], args[1], args[2]),
         SYSCALL_SBRK => sys_sbrk(args[0] as i32),
+        SYSCALL_PROCESS_VM_READV => sys_process_vm_readv(args[0], args[1] as *mut u8, args[2], args[3]),
+        SYSCALL_MEMFD_CREATE => sys_memfd_create(args[0] as *const u8, args[1] as u32),
         SYSCALL_SPAWN => sys_spawn(args[0] as *const u8),
         SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as isize),
         _ => panic!("Unsupported syscall_id: {}", syscall_id),
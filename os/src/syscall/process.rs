@@ -3,13 +3,17 @@
 use alloc::sync::Arc;
 
 use crate::{
-    config::MAX_SYSCALL_NUM,
-    fs::{open_file, OpenFlags},
-    mm::{translated_refmut, translated_str, MapPermission, VirtAddr},
+    config::{ALL_CPUS_MASK, MAX_FD_LIMIT, MAX_SYSCALL_NUM, PAGE_SIZE, TASK_COMM_LEN},
+    fs::{open_file, File, OSInode, OpenFlags},
+    mm::{
+        translated_byte_buffer_mut, translated_ref, translated_refmut, translated_str,
+        translated_user_buffer, MapPermission, MappedFile, UserBuffer, VirtAddr,
+    },
     task::{
         add_task, current_task, current_user_token, exit_current_and_run_next,
         suspend_current_and_run_next, TaskStatus, get_taskinfo,check_maparea,
-        add_maparea, remove_maparea, take_current_task, set_current,
+        add_maparea, remove_maparea, take_current_task, set_current, prefetch_maparea,
+        discard_maparea, TaskControlBlock, current_hart_id, add_file_maparea, sync_maparea,
 
     },
     timer::get_time_us,
@@ -49,6 +53,35 @@ impl TaskInfo {
     }
 }
 
+/// Resource usage of a reaped child
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct RUsage {
+    /// Total running time in microseconds
+    pub cpu_time: usize,
+    /// Peak number of resident pages
+    pub max_rss: usize,
+    /// Total number of syscalls
+    pub syscall_count: usize,
+}
+
+/// Resource limit, see getrlimit(2)
+#[repr(C)]
+#[derive(Debug)]
+pub struct RLimit {
+    /// Soft limit, enforced by the kernel
+    pub rlim_cur: usize,
+    /// Hard limit, ceiling of the soft limit
+    pub rlim_max: usize,
+}
+
+/// Limit on the CPU time in seconds
+pub const RLIMIT_CPU: usize = 0;
+/// Limit on the number of open files
+pub const RLIMIT_NOFILE: usize = 7;
+/// No limit on the resource
+pub const RLIM_INFINITY: usize = usize::MAX;
+
 /// Exit the current task
 pub fn sys_exit(exit_code: i32) -> ! {
     trace!("kernel:pid[{}] sys_exit", current_task().unwrap().pid.0);
@@ -63,12 +96,35 @@ pub fn sys_yield() -> isize {
     0
 }
 
-/// Get the pid of current task
+/// Get the pid of current task, the id of its thread group
 pub fn sys_getpid() -> isize {
     trace!("kernel: sys_getpid pid:{}", current_task().unwrap().pid.0);
+    current_task().unwrap().tgid as isize
+}
+
+/// Get the id of current task, its pid outside a thread group
+pub fn sys_gettid() -> isize {
+    trace!("kernel:pid[{}] sys_gettid", current_task().unwrap().pid.0);
     current_task().unwrap().pid.0 as isize
 }
 
+/// Get the pid of the parent of current task
+pub fn sys_getppid() -> isize {
+    trace!("kernel:pid[{}] sys_getppid", current_task().unwrap().pid.0);
+    current_task().unwrap().getppid() as isize
+}
+
+/// clone() flags: signal sent to the parent when the child exits
+pub const CSIGNAL: usize = 0xff;
+/// clone() flag: share the address space
+pub const CLONE_VM: usize = 0x100;
+/// clone() flag: share the fd table
+pub const CLONE_FILES: usize = 0x400;
+/// clone() flag: suspend the parent until the child execs or exits
+pub const CLONE_VFORK: usize = 0x4000;
+/// clone() flag: put the child in the thread group of the parent
+pub const CLONE_THREAD: usize = 0x10000;
+
 /// Fork the current task
 pub fn sys_fork() -> isize {
     trace!("kernel:pid[{}] sys_fork", current_task().unwrap().pid.0);
@@ -85,6 +141,43 @@ pub fn sys_fork() -> isize {
     new_pid as isize
 }
 
+/// Create a child of the current task sharing what `flags` select, clone
+/// without flags being fork. The child starts on `stack` if it is not 0,
+/// otherwise on a copy of the current stack. CLONE_THREAD needs CLONE_VM,
+/// other flags are refused
+pub fn sys_clone(flags: usize, stack: usize) -> isize {
+    trace!("kernel:pid[{}] sys_clone", current_task().unwrap().pid.0);
+    // 低 8 位是退出时通知父进程的信号，没有信号机制，忽略
+    if flags & !CSIGNAL == 0 && stack == 0 {
+        return sys_fork();
+    }
+    if flags & !(CSIGNAL | CLONE_VM | CLONE_FILES | CLONE_VFORK | CLONE_THREAD) != 0 {
+        return -1;
+    }
+    // 线程组内的任务必须共享地址空间 (EINVAL)
+    if flags & CLONE_THREAD != 0 && flags & CLONE_VM == 0 {
+        return -1;
+    }
+    let current_task = current_task().unwrap();
+    let new_task = current_task.clone_task(flags);
+    let new_pid = new_task.pid.0;
+    let mut new_inner = new_task.inner_exclusive_access();
+    let trap_cx = new_inner.get_trap_cx();
+    // for child process, clone returns 0
+    trap_cx.x[10] = 0;
+    if stack != 0 {
+        trap_cx.x[2] = stack;
+    }
+    new_inner.vfork = flags & CLONE_VFORK != 0;
+    drop(new_inner);
+    add_task(new_task.clone());
+    // 子进程 exec 或退出前父进程不再运行
+    while new_task.inner_exclusive_access().vfork {
+        current_task.child_exit.wait();
+    }
+    new_pid as isize
+}
+
 /// exec
 pub fn sys_exec(path: *const u8) -> isize {
     trace!("kernel:pid[{}] sys_exec", current_task().unwrap().pid.0);
@@ -95,44 +188,95 @@ pub fn sys_exec(path: *const u8) -> isize {
         // 通过 OSInode::read_all 将该文件的数据全部读到一个向量 all_data 中
         let all_data = app_inode.read_all();
         let task = current_task().unwrap();
-        task.exec(all_data.as_slice());
-        0
+        if task.exec(all_data.as_slice()) {
+            task.inner_exclusive_access().set_name(program_name(&path));
+            0
+        } else {
+            -1
+        }
     } else {
         -1
     }
 }
 
+/// The last component of the program `path`, which names the task running it
+fn program_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// wait4() option: return -2 at once instead of blocking if no child has exited
+pub const WNOHANG: usize = 1;
+
 /// If there is not a child process whose pid is same as given, return -1.
 /// Else if there is a child process but it is still running, return -2.
 pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
-    //trace!("kernel: sys_waitpid");
+    sys_wait4(pid, exit_code_ptr, WNOHANG, core::ptr::null_mut())
+}
+
+/// Like `sys_waitpid`, but also fill `rusage` with the accounting of the reaped child
+/// when it is not null. Block until such a child exits unless `options` has WNOHANG.
+pub fn sys_wait4(
+    pid: isize,
+    exit_code_ptr: *mut i32,
+    options: usize,
+    rusage: *mut RUsage,
+) -> isize {
+    //trace!("kernel: sys_wait4");
     let task = current_task().unwrap();
     // find a child process
 
     // ---- access current PCB exclusively
     let mut inner = task.inner_exclusive_access();
-    if !inner
-        .children
-        .iter()
-        .any(|p| pid == -1 || pid as usize == p.getpid())
-    {
-        return -1;
-        // ---- release current PCB
-    }
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        // ++++ temporarily access child PCB exclusively
-        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
-        // ++++ release child PCB
-    });
-    if let Some((idx, _)) = pair {
+    let pair = loop {
+        if !inner
+            .children
+            .iter()
+            .any(|p| pid == -1 || pid as usize == p.getpid())
+        {
+            return -1;
+            // ---- release current PCB
+        }
+        let pair = inner.children.iter().enumerate().find(|(_, p)| {
+            // ++++ temporarily access child PCB exclusively
+            p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
+            // ++++ release child PCB
+        });
+        if pair.is_some() || options & WNOHANG != 0 {
+            break pair.map(|(idx, _)| idx);
+        }
+        drop(inner);
+        // 子进程退出时会唤醒这里，之后重新查找
+        task.child_exit.wait();
+        inner = task.inner_exclusive_access();
+    };
+    if let Some(idx) = pair {
         let child = inner.children.remove(idx);
         // confirm that child will be deallocated after being removed from children list
         assert_eq!(Arc::strong_count(&child), 1);
         let found_pid = child.getpid();
         // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
+        let child_inner = child.inner_exclusive_access();
+        let exit_code = child_inner.exit_code;
+        let usage = RUsage {
+            cpu_time: child_inner.cpu_time,
+            max_rss: child_inner.max_rss,
+            syscall_count: child_inner
+                .task_info
+                .syscall_times
+                .iter()
+                .map(|times| *times as usize)
+                .sum(),
+        };
+        drop(child_inner);
         // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
+        let token = inner.get_user_token();
+        drop(inner);
+        if !exit_code_ptr.is_null() {
+            *translated_refmut(token, exit_code_ptr) = exit_code;
+        }
+        if !rusage.is_null() {
+            *translated_refmut(token, rusage) = usage;
+        }
         found_pid as isize
     } else {
         -2
@@ -140,6 +284,42 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
     // ---- release current PCB automatically
 }
 
+/// Copy `len` bytes at `remote_addr` in the address space of child `pid`
+/// into `local_buf` of current task, return the number of bytes copied
+pub fn sys_process_vm_readv(pid: usize, local_buf: *mut u8, remote_addr: usize, len: usize) -> isize {
+    trace!("kernel:pid[{}] sys_process_vm_readv", current_task().unwrap().pid.0);
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    // 暂时只允许父进程读取子进程的内存
+    let Some(target) = inner.children.iter().find(|child| child.getpid() == pid).cloned() else {
+        return -1;
+    };
+    let token = inner.get_user_token();
+    drop(inner);
+    let target_inner = target.inner_exclusive_access();
+    if target_inner.is_zombie() {
+        return -1;
+    }
+    let Some(remote) = translated_user_buffer(
+        target_inner.get_user_token(),
+        remote_addr as *const u8,
+        len,
+    ) else {
+        return -1;
+    };
+    // 远端与本地的缓冲区各自可能跨页，按远端的每一页分段写入本地缓冲区
+    let mut copied = 0usize;
+    for page in remote {
+        let mut local = UserBuffer::new(translated_byte_buffer_mut(
+            token,
+            local_buf.wrapping_add(copied),
+            page.len(),
+        ));
+        copied += local.copy_from_slice(page);
+    }
+    copied as isize
+}
+
 /// YOUR JOB: get time with second and microsecond
 /// HINT: You might reimplement it with virtual memory management.
 /// HINT: What if [`TimeVal`] is splitted by two pages ?
@@ -157,6 +337,35 @@ pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
     0
 }
 
+/// wall-clock time, counted from boot as the board has no RTC
+pub const CLOCK_REALTIME: usize = 0;
+/// time since boot, never goes backward
+pub const CLOCK_MONOTONIC: usize = 1;
+/// cpu time consumed by the calling task
+pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+
+/// Get the time of clock `clk_id` with second and microsecond
+pub fn sys_clock_gettime(clk_id: usize, ts: *mut TimeVal) -> isize {
+    trace!("kernel:pid[{}] sys_clock_gettime", current_task().unwrap().pid.0);
+    let us = match clk_id {
+        CLOCK_REALTIME | CLOCK_MONOTONIC => get_time_us(),
+        CLOCK_PROCESS_CPUTIME_ID => {
+            let task = current_task().unwrap();
+            let mut inner = task.inner_exclusive_access();
+            // 先结算本次被调度以来的运行时间
+            inner.charge_cpu_time();
+            inner.cpu_time
+        }
+        _ => return -1,
+    };
+    let token = current_user_token();
+    // 逐字段写入，TimeVal 跨页时两个字段位于不同的物理页
+    let sec = ts as *mut usize;
+    *translated_refmut(token, sec) = us / 1_000_000;
+    *translated_refmut(token, sec.wrapping_add(1)) = us % 1_000_000;
+    0
+}
+
 /// YOUR JOB: Finish sys_task_info to pass testcases
 /// HINT: You might reimplement it with virtual memory management.
 /// HINT: What if [`TaskInfo`] is splitted by two pages ?
@@ -175,12 +384,32 @@ pub fn sys_task_info(_ti: *mut TaskInfo) -> isize {
     0
 }
 
+/// mmap() flag: writes reach the file and other mappings of it
+pub const MAP_SHARED: usize = 0x1;
+/// mmap() flag: writes stay private to the mapping
+pub const MAP_PRIVATE: usize = 0x2;
+/// mmap() flag: the mapping is not backed by any file
+pub const MAP_ANONYMOUS: usize = 0x20;
+
 /// YOUR JOB: Implement mmap.
+/// Map zeroed memory at [start, start + len)
 pub fn sys_mmap(_start: usize, _len: usize, _port: usize) -> isize {
-    trace!(
-        "kernel:pid[{}] sys_mmap NOT IMPLEMENTED",
-        current_task().unwrap().pid.0
-    );
+    trace!("kernel:pid[{}] sys_mmap", current_task().unwrap().pid.0);
+    mmap(_start, _len, _port, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0)
+}
+
+/// mmap() with flags, fd and offset: with MAP_SHARED or MAP_PRIVATE and
+/// without MAP_ANONYMOUS, map the file `fd` from the page-aligned `offset`,
+/// each page read in from the file on first access
+pub fn sys_mmap_file(start: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    trace!("kernel:pid[{}] sys_mmap_file", current_task().unwrap().pid.0);
+    if flags & MAP_ANONYMOUS == 0 && flags & (MAP_SHARED | MAP_PRIVATE) == 0 {
+        return -1;
+    }
+    mmap(start, len, prot, flags, fd, offset)
+}
+
+fn mmap(_start: usize, _len: usize, _port: usize, flags: usize, fd: usize, offset: usize) -> isize {
     if _len == 0 {
         return 0;
     }
@@ -208,7 +437,32 @@ pub fn sys_mmap(_start: usize, _len: usize, _port: usize) -> isize {
     if 4 as usize & _port != 0 {
         map_perm |= MapPermission::X;
     }
-    add_maparea(start_va, end_va, map_perm);
+    if flags & MAP_ANONYMOUS != 0 {
+        add_maparea(start_va, end_va, map_perm);
+        return 0;
+    }
+    let shared = flags & MAP_SHARED != 0;
+    if offset & (PAGE_SIZE - 1) != 0 {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let Some(file) = task.inner_exclusive_access().file(fd) else {
+        return -1;
+    };
+    let Some(inode) = file.as_any().downcast_ref::<OSInode>() else {
+        return -1;
+    };
+    // 共享的可写映射会写回文件，要求文件以可写方式打开
+    if !inode.readable() || inode.inode().is_dir() || (shared && map_perm.contains(MapPermission::W) && !inode.writable()) {
+        return -1;
+    }
+    let file = MappedFile {
+        inode: inode.inode(),
+        offset,
+        shared,
+    };
+    drop(task);
+    add_file_maparea(start_va, end_va, map_perm, file);
     0
 }
 
@@ -228,6 +482,64 @@ pub fn sys_munmap(_start: usize, _len: usize) -> isize {
     remove_maparea(start_va, end_va)
 }
 
+/// madvise() advice: the pages will be accessed soon
+pub const MADV_WILLNEED: usize = 3;
+/// madvise() advice: the contents of the pages are no longer needed
+pub const MADV_DONTNEED: usize = 4;
+
+/// Give the kernel a hint about the use of [start, start + len), which must
+/// lie inside one existing mapping. MADV_WILLNEED reads the pages of a file
+/// mapping in at once, after MADV_DONTNEED the pages read as zeros, or the
+/// file contents in a file mapping
+pub fn sys_madvise(start: usize, len: usize, advice: usize) -> isize {
+    trace!("kernel:pid[{}] sys_madvise", current_task().unwrap().pid.0);
+    let start_va = VirtAddr::from(start);
+    if !start_va.aligned() {
+        return -1;
+    }
+    if len == 0 {
+        return 0;
+    }
+    let end_va = VirtAddr::from(start + len);
+    match advice {
+        // 文件映射的页面在首次访问时才读入，这里提前读入
+        MADV_WILLNEED if prefetch_maparea(start_va, end_va) => 0,
+        MADV_DONTNEED if discard_maparea(start_va, end_va) => 0,
+        _ => -1,
+    }
+}
+
+/// msync() flag: schedule the write back and return at once
+pub const MS_ASYNC: usize = 1;
+/// msync() flag: invalidate other mappings of the same file
+pub const MS_INVALIDATE: usize = 2;
+/// msync() flag: return once the write back is done
+pub const MS_SYNC: usize = 4;
+
+/// Write the modified pages of [start, start + len), which must lie inside
+/// one existing mapping, back to the file it maps. Private and anonymous
+/// mappings have nothing to write back
+pub fn sys_msync(start: usize, len: usize, flags: usize) -> isize {
+    trace!("kernel:pid[{}] sys_msync", current_task().unwrap().pid.0);
+    let start_va = VirtAddr::from(start);
+    if !start_va.aligned() {
+        return -1;
+    }
+    // MS_ASYNC 与 MS_SYNC 互斥
+    if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0 || flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC {
+        return -1;
+    }
+    if len == 0 {
+        return 0;
+    }
+    let end_va = VirtAddr::from(start + len);
+    if sync_maparea(start_va, end_va) {
+        0
+    } else {
+        -1
+    }
+}
+
 /// change data segment size
 pub fn sys_sbrk(size: i32) -> isize {
     trace!("kernel:pid[{}] sys_sbrk", current_task().unwrap().pid.0);
@@ -253,6 +565,7 @@ pub fn sys_spawn(_path: *const u8) -> isize {
         let all_data = app_inode.read_all();
         let current_task = current_task().unwrap();
         let new_task = current_task.spawn(all_data.as_slice());
+        new_task.inner_exclusive_access().set_name(program_name(&_path));
         let new_pid = new_task.pid.0;
         let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
         trap_cx.x[10] = 0;
@@ -280,3 +593,138 @@ pub fn sys_set_priority(_prio: isize) -> isize {
     set_current(task);
     _prio
 }
+
+/// Current task if `pid` is 0 or its own pid, otherwise its child `pid`
+fn self_or_child(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    let task = current_task().unwrap();
+    if pid == 0 || pid == task.getpid() {
+        return Some(task);
+    }
+    let inner = task.inner_exclusive_access();
+    inner.children.iter().find(|child| child.getpid() == pid).cloned()
+}
+
+/// Set the CPUs task `pid` may run on, 0 for current task. Bits of CPUs that
+/// do not exist are dropped and at least one CPU must be left
+pub fn sys_sched_setaffinity(pid: usize, mask: usize) -> isize {
+    trace!("kernel:pid[{}] sys_sched_setaffinity", current_task().unwrap().pid.0);
+    if mask & ALL_CPUS_MASK == 0 {
+        return -1;
+    }
+    let Some(task) = self_or_child(pid) else {
+        return -1;
+    };
+    // 目前只有一个 CPU，掩码只被记录下来，留给将来的多核调度器
+    task.inner_exclusive_access().cpu_mask = mask & ALL_CPUS_MASK;
+    0
+}
+
+/// Get the CPUs task `pid` may run on, 0 for current task
+pub fn sys_sched_getaffinity(pid: usize) -> isize {
+    trace!("kernel:pid[{}] sys_sched_getaffinity", current_task().unwrap().pid.0);
+    let Some(task) = self_or_child(pid) else {
+        return -1;
+    };
+    let mask = task.inner_exclusive_access().cpu_mask;
+    mask as isize
+}
+
+/// Store the id of the CPU running the current task to `*cpu` and its NUMA
+/// node, always 0, to `*node`. Either pointer may be null
+pub fn sys_getcpu(cpu: *mut u32, node: *mut u32) -> isize {
+    trace!("kernel:pid[{}] sys_getcpu", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    for (ptr, value) in [(cpu, current_hart_id() as u32), (node, 0)] {
+        if !ptr.is_null() {
+            // 按字节写入，未对齐的指针跨页时也能正确写到两个物理页
+            UserBuffer::new(translated_byte_buffer_mut(token, ptr as *const u8, 4))
+                .copy_from_slice(&value.to_ne_bytes());
+        }
+    }
+    0
+}
+
+/// Get the resource limit of current task, only RLIMIT_CPU and RLIMIT_NOFILE
+/// are supported
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    trace!("kernel:pid[{}] sys_getrlimit", current_task().unwrap().pid.0);
+    if rlim.is_null() {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let limit = match resource {
+        RLIMIT_CPU => RLimit {
+            rlim_cur: inner.cpu_limit,
+            rlim_max: RLIM_INFINITY,
+        },
+        RLIMIT_NOFILE => RLimit {
+            rlim_cur: inner.fd_limit,
+            rlim_max: MAX_FD_LIMIT,
+        },
+        _ => return -1,
+    };
+    let token = inner.get_user_token();
+    drop(inner);
+    *translated_refmut(token, rlim) = limit;
+    0
+}
+
+/// Set the resource limit of current task, only RLIMIT_CPU and RLIMIT_NOFILE
+/// are supported and the hard limit cannot be changed
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    trace!("kernel:pid[{}] sys_setrlimit", current_task().unwrap().pid.0);
+    if rlim.is_null() {
+        return -1;
+    }
+    let rlim = translated_ref(current_user_token(), rlim);
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let hard_limit = match resource {
+        RLIMIT_CPU => RLIM_INFINITY,
+        RLIMIT_NOFILE => MAX_FD_LIMIT,
+        _ => return -1,
+    };
+    if rlim.rlim_max != hard_limit || rlim.rlim_cur > rlim.rlim_max {
+        return -1;
+    }
+    if resource == RLIMIT_CPU {
+        // 已用的 CPU 时间同样计入配额，超出后在下一次时钟中断时被终止
+        inner.cpu_limit = rlim.rlim_cur;
+    } else {
+        // 已打开的 fd 不受影响，只限制之后的分配
+        inner.fd_limit = rlim.rlim_cur;
+    }
+    0
+}
+
+/// prctl() option: set the name of the current task
+pub const PR_SET_NAME: usize = 15;
+/// prctl() option: get the name of the current task
+pub const PR_GET_NAME: usize = 16;
+
+/// Apply prctl() `option` to the current task, only PR_SET_NAME, setting the
+/// name to the string at `arg2` cut to TASK_COMM_LEN - 1 bytes, and
+/// PR_GET_NAME, storing the NUL terminated name to the TASK_COMM_LEN bytes
+/// buffer at `arg2`, are supported
+pub fn sys_prctl(option: usize, arg2: usize) -> isize {
+    trace!("kernel:pid[{}] sys_prctl", current_task().unwrap().pid.0);
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    match option {
+        PR_SET_NAME => {
+            let name = translated_str(token, arg2 as *const u8);
+            task.inner_exclusive_access().set_name(&name);
+        }
+        PR_GET_NAME => {
+            let mut name = [0u8; TASK_COMM_LEN];
+            let inner = task.inner_exclusive_access();
+            name[..inner.name.len()].copy_from_slice(inner.name.as_bytes());
+            drop(inner);
+            UserBuffer::new(translated_byte_buffer_mut(token, arg2 as *const u8, TASK_COMM_LEN))
+                .copy_from_slice(&name);
+        }
+        _ => return -1,
+    }
+    0
+}
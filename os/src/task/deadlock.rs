@@ -0,0 +1,120 @@
+//! Opt-in detector of tasks waiting on each other through pipes
+//!
+//! A task blocked reading an empty pipe waits for the tasks holding its
+//! write end, and a task blocked writing a full pipe waits for the tasks
+//! holding its read end. When the waits form a cycle none of the tasks can
+//! go on, so the cycle is reported through the kernel log.
+use super::{TaskControlBlock, INITPROC};
+use crate::fs::{Pipe, PipeRingBuffer};
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// The pipe a task is blocked on, and whether it waits to read
+struct Wait {
+    buffer: Weak<UPSafeCell<PipeRingBuffer>>,
+    read: bool,
+}
+
+impl Wait {
+    /// Whether the task still cannot go on, it may have been woken up but
+    /// not scheduled yet
+    fn blocked(&self) -> bool {
+        let Some(buffer) = self.buffer.upgrade() else {
+            return false;
+        };
+        let ring_buffer = buffer.exclusive_access();
+        if self.read {
+            ring_buffer.available_read() == 0 && !ring_buffer.all_write_ends_closed()
+        } else {
+            ring_buffer.available_write() == 0
+        }
+    }
+}
+
+lazy_static! {
+    /// pid -> the pipe the task is blocked on
+    static ref WAITS: UPSafeCell<BTreeMap<usize, Wait>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Record that task `pid` blocks on the pipe `buffer`, to read if `read`,
+/// and log the wait cycle this closes. Return whether there is one
+pub fn block_on_pipe(pid: usize, buffer: &Arc<UPSafeCell<PipeRingBuffer>>, read: bool) -> bool {
+    let wait = Wait {
+        buffer: Arc::downgrade(buffer),
+        read,
+    };
+    WAITS.exclusive_access().insert(pid, wait);
+    match find_cycle(pid) {
+        Some(cycle) => {
+            warn!("[kernel] deadlock: tasks {:?} wait on each other through pipes", cycle);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Forget the wait of task `pid` once it goes on
+pub fn unblock(pid: usize) {
+    WAITS.exclusive_access().remove(&pid);
+}
+
+/// Find a path of waits from task `pid` back to itself
+fn find_cycle(pid: usize) -> Option<Vec<usize>> {
+    let tasks = all_tasks();
+    let waits = WAITS.exclusive_access();
+    // 深度优先搜索，只经过仍被阻塞的任务：未阻塞的任务总能继续执行
+    let mut stack = vec![vec![pid]];
+    let mut visited = Vec::new();
+    while let Some(path) = stack.pop() {
+        let waiter = *path.last().unwrap();
+        let Some(wait) = waits.get(&waiter).filter(|wait| wait.blocked()) else {
+            continue;
+        };
+        for holder in holders(&tasks, wait) {
+            if holder == pid {
+                return Some(path);
+            }
+            if !visited.contains(&holder) {
+                visited.push(holder);
+                let mut next = path.clone();
+                next.push(holder);
+                stack.push(next);
+            }
+        }
+    }
+    None
+}
+
+/// Pids of the tasks holding the other end of the pipe of `wait`
+fn holders(tasks: &[Arc<TaskControlBlock>], wait: &Wait) -> Vec<usize> {
+    let Some(buffer) = wait.buffer.upgrade() else {
+        return Vec::new();
+    };
+    tasks
+        .iter()
+        .filter(|task| {
+            task.inner_exclusive_access().fd_table().iter().flatten().any(|entry| {
+                entry.file.as_any()
+                    .downcast_ref::<Pipe>()
+                    .is_some_and(|pipe| pipe.is_end_of(&buffer, !wait.read))
+            })
+        })
+        .map(|task| task.getpid())
+        .collect()
+}
+
+/// Every task in the process tree
+fn all_tasks() -> Vec<Arc<TaskControlBlock>> {
+    let mut tasks = Vec::new();
+    let mut stack = vec![INITPROC.clone()];
+    while let Some(task) = stack.pop() {
+        stack.extend(task.inner_exclusive_access().children.iter().cloned());
+        tasks.push(task);
+    }
+    tasks
+}
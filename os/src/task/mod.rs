@@ -14,6 +14,8 @@
 //! Be careful when you see `__switch` ASM function in `switch.S`. Control flow around this function
 //! might not be what you expect.
 mod context;
+#[cfg(feature = "deadlock_detect")]
+mod deadlock;
 mod id;
 mod manager;
 mod processor;
@@ -21,21 +23,35 @@ mod switch;
 #[allow(clippy::module_inception)]
 #[allow(rustdoc::private_intra_doc_links)]
 mod task;
+mod wait_queue;
 pub use crate::syscall::TaskInfo;
 use crate::fs::{open_file, OpenFlags};
 use alloc::sync::Arc;
+use alloc::vec;
 pub use context::TaskContext;
+#[cfg(feature = "deadlock_detect")]
+pub use deadlock::{block_on_pipe, unblock};
 use lazy_static::*;
 pub use manager::{fetch_task, TaskManager};
 use switch::__switch;
-pub use task::{TaskControlBlock, TaskStatus};
-pub use crate::mm::{MapPermission, MemorySet, PhysPageNum, VirtAddr};
+pub use task::{FdEntry, TaskControlBlock, TaskStatus};
+pub use wait_queue::WaitQueue;
+pub use crate::mm::{MapPermission, MappedFile, MemorySet, PhysPageNum, VirtAddr};
 pub use id::{kstack_alloc, pid_alloc, KernelStack, PidHandle};
 pub use manager::add_task;
 pub use processor::{
-    current_task, current_trap_cx, current_user_token, run_tasks, schedule, take_current_task,
-    Processor, set_current,
+    current_hart_id, current_task, current_trap_cx, current_trap_cx_user_va, current_user_token,
+    run_tasks, schedule,
+    take_current_task, Processor, set_current, set_current_hart_id,
 };
+/// Whether the current task has used up its CPU time quota
+pub fn current_cpu_quota_exceeded() -> bool {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .cpu_quota_exceeded()
+}
+
 /// Suspend the current 'Running' task and run the next task in task list.
 pub fn suspend_current_and_run_next() {
     // There must be an application running.
@@ -46,6 +62,7 @@ pub fn suspend_current_and_run_next() {
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
     // Change status to Ready
     task_inner.task_status = TaskStatus::Ready;
+    task_inner.charge_cpu_time();
     drop(task_inner);
     // ---- release current PCB
 
@@ -78,6 +95,9 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     inner.task_status = TaskStatus::Zombie;
     // Record exit code
     inner.exit_code = exit_code;
+    // Record accounting before the address space is gone
+    inner.charge_cpu_time();
+    inner.update_max_rss();
     // do not move to its parent but under initproc
 
     // ++++++ access initproc TCB exclusively
@@ -90,13 +110,37 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     }
     // ++++++ release parent PCB
 
+    // 有子进程转交给 initproc 时，它可能正在等待子进程退出
+    let adopted = !inner.children.is_empty();
     inner.children.clear();
+    // 仍在等待 vfork 的父进程由下面的唤醒放行
+    inner.vfork = false;
     // deallocate user space
-    inner.memory_set.recycle_data_pages();
-    // drop file descriptors
-    inner.fd_table.clear();
+    // 地址空间仍被其他任务共享时留给它们，只归还本任务的 trap 上下文页
+    if Arc::strong_count(&inner.memory_set) == 1 {
+        inner.memory_set().recycle_data_pages();
+    } else {
+        inner.release_trap_cx();
+    }
+    // drop file descriptors, unless other tasks share them
+    if Arc::strong_count(&inner.fd_table) == 1 {
+        let mut fd_table = inner.fd_table();
+        // 缓冲中的输出在关闭前写出，避免进程退出时丢失
+        for entry in fd_table.iter().flatten() {
+            entry.file.flush();
+        }
+        fd_table.clear();
+    }
+    let parent = inner.parent.as_ref().and_then(|parent| parent.upgrade());
     drop(inner);
     // **** release current PCB
+    // 唤醒阻塞在 wait4 中的父进程
+    if let Some(parent) = parent {
+        parent.child_exit.notify_all();
+    }
+    if adopted {
+        INITPROC.child_exit.notify_all();
+    }
     // drop task manually to maintain rc correctly
     drop(task);
     // we do not have to save task context
@@ -112,15 +156,36 @@ lazy_static! {
     pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new({
         let inode = open_file("ch6b_initproc", OpenFlags::RDONLY).unwrap();
         let v = inode.read_all();
-        TaskControlBlock::new(v.as_slice())
+        let task = TaskControlBlock::new(v.as_slice());
+        task.inner_exclusive_access().set_name("ch6b_initproc");
+        task
     });
 }
 
+lazy_static! {
+    /// Tasks polling for readiness that raises no interrupt, e.g. stdin or
+    /// the fds of poll(), woken on every timer tick, whenever the ready
+    /// queue runs dry and whenever a pipe changes state
+    pub static ref TICK_QUEUE: WaitQueue = WaitQueue::new();
+}
+
 ///Add init process to the manager
 pub fn add_initproc() {
     add_task(INITPROC.clone());
 }
 
+/// Find a live or zombie task by pid, walking the process tree from initproc
+pub fn find_task(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    let mut stack = vec![INITPROC.clone()];
+    while let Some(task) = stack.pop() {
+        if task.getpid() == pid {
+            return Some(task);
+        }
+        stack.extend(task.inner_exclusive_access().children.iter().cloned());
+    }
+    None
+}
+
 
 /// 添加一个逻辑段到应用地址空间
 pub fn add_maparea(start_va: VirtAddr, end_va: VirtAddr, permission: MapPermission){
@@ -131,6 +196,15 @@ pub fn add_maparea(start_va: VirtAddr, end_va: VirtAddr, permission: MapPermissi
     set_current(task);
 }
 
+/// 把文件映射到应用地址空间的一个新逻辑段
+pub fn add_file_maparea(start_va: VirtAddr, end_va: VirtAddr, permission: MapPermission, file: MappedFile) {
+    let task = take_current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.add_file_maparea(start_va, end_va, permission, file);
+    drop(inner);
+    set_current(task);
+}
+
 /// 删除应用地址空间的一个逻辑段
 pub fn remove_maparea(start_va: VirtAddr, end_va: VirtAddr) -> isize{
     let task = take_current_task().unwrap();
@@ -145,7 +219,48 @@ pub fn remove_maparea(start_va: VirtAddr, end_va: VirtAddr) -> isize{
 pub fn check_maparea(start_va: VirtAddr, end_va: VirtAddr) -> bool {
     let task = take_current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    let i = inner.memory_set.check_conflict(start_va, end_va);
+    let i = inner.check_maparea(start_va, end_va);
+    drop(inner);
+    set_current(task);
+    i
+}
+
+/// 预先载入应用地址空间中一段文件映射的页面，区域必须落在一个逻辑段内
+pub fn prefetch_maparea(start_va: VirtAddr, end_va: VirtAddr) -> bool {
+    let task = take_current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let i = inner.prefetch_maparea(start_va, end_va);
+    drop(inner);
+    set_current(task);
+    i
+}
+
+/// 丢弃应用地址空间中一段页面的内容
+pub fn discard_maparea(start_va: VirtAddr, end_va: VirtAddr) -> bool {
+    let task = take_current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let i = inner.discard_maparea(start_va, end_va);
+    drop(inner);
+    set_current(task);
+    i
+}
+
+/// Load the page at `va` of a mapping of current task if it has no frame
+/// yet and `token` is the address space of current task. Return false
+/// if there is no such page to load
+pub fn load_page(token: usize, va: VirtAddr) -> bool {
+    let Some(task) = current_task() else {
+        return false;
+    };
+    let mut inner = task.inner_exclusive_access();
+    inner.get_user_token() == token && inner.load_page(va)
+}
+
+/// 把应用地址空间中一段被修改的页面写回共享映射的文件
+pub fn sync_maparea(start_va: VirtAddr, end_va: VirtAddr) -> bool {
+    let task = take_current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let i = inner.sync_maparea(start_va, end_va);
     drop(inner);
     set_current(task);
     i
@@ -5,9 +5,10 @@
 //! and the replacement and transfer of control flow of different applications are executed.
 
 use super::__switch;
-use super::{fetch_task, TaskStatus};
+use super::{fetch_task, TaskStatus, TICK_QUEUE};
 use super::{TaskContext, TaskControlBlock};
 use crate::sync::UPSafeCell;
+use crate::timer::get_time_us;
 use crate::trap::TrapContext;
 use alloc::sync::Arc;
 use lazy_static::*;
@@ -19,6 +20,9 @@ pub struct Processor {
 
     ///The basic control flow of each core, helping to select and switch process
     idle_task_cx: TaskContext,
+
+    ///Id of the hart this processor is, as passed by SBI at boot
+    hart_id: usize,
 }
 
 impl Processor {
@@ -27,6 +31,7 @@ impl Processor {
         Self {
             current: None,
             idle_task_cx: TaskContext::zero_init(),
+            hart_id: 0,
         }
     }
 
@@ -56,6 +61,16 @@ lazy_static! {
     pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
 }
 
+/// Record the id of the hart the kernel runs on, called once at boot
+pub fn set_current_hart_id(hart_id: usize) {
+    PROCESSOR.exclusive_access().hart_id = hart_id;
+}
+
+/// Id of the hart the current task runs on
+pub fn current_hart_id() -> usize {
+    PROCESSOR.exclusive_access().hart_id
+}
+
 ///The main part of process execution and scheduling
 ///Loop `fetch_task` to get the process that needs to run, and switch the process through `__switch`
 pub fn run_tasks() {
@@ -67,6 +82,7 @@ pub fn run_tasks() {
             let mut task_inner = task.inner_exclusive_access();
             let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
             task_inner.task_status = TaskStatus::Running;
+            task_inner.last_scheduled = get_time_us();
             // release coming task_inner manually
             drop(task_inner);
             // release coming task TCB manually
@@ -76,7 +92,7 @@ pub fn run_tasks() {
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
-        } else {
+        } else if TICK_QUEUE.notify_all() == 0 {
             warn!("no tasks available in run_tasks");
         }
     }
@@ -98,6 +114,11 @@ pub fn current_user_token() -> usize {
     task.get_user_token()
 }
 
+/// Get the user address of the trap context of current task
+pub fn current_trap_cx_user_va() -> usize {
+    current_task().unwrap().inner_exclusive_access().trap_cx_va
+}
+
 ///Get the mutable reference to trap context of current task
 pub fn current_trap_cx() -> &'static mut TrapContext {
     current_task()
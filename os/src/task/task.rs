@@ -1,16 +1,20 @@
 //! Types related to task management & Functions for completely changing TCB
 use super::TaskContext;
-use super::{kstack_alloc, pid_alloc, KernelStack, PidHandle};
-use crate::config::TRAP_CONTEXT_BASE;
-use crate::fs::{File, Stdin, Stdout};
-use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE, MapPermission};
+use super::{kstack_alloc, pid_alloc, KernelStack, PidHandle, WaitQueue, INITPROC};
+use crate::config::{ALL_CPUS_MASK, DEFAULT_FD_LIMIT, STDOUT_LINE_BUFFERED, TASK_COMM_LEN, TRAP_CONTEXT_BASE};
+use crate::fs::{File, Stdin, Stdout, ROOT_INODE};
+use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE, MapPermission, MappedFile};
 use crate::sync::UPSafeCell;
+use crate::syscall::{CLONE_FILES, CLONE_THREAD, CLONE_VM, RLIM_INFINITY};
 use crate::trap::{trap_handler, TrapContext};
+use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefMut;
+use easy_fs::Inode;
 use crate::syscall::TaskInfo;
+use crate::timer::get_time_us;
 /// Task control block structure
 ///
 /// Directly save the contents that will not change during running
@@ -19,9 +23,16 @@ pub struct TaskControlBlock {
     /// Process identifier
     pub pid: PidHandle,
 
+    /// Thread group identifier, the pid of the task that started the
+    /// group: tasks cloned with CLONE_THREAD join the group of their parent
+    pub tgid: usize,
+
     /// Kernel stack corresponding to PID
     pub kernel_stack: KernelStack,
 
+    /// The task itself blocks here in wait4 until a child exits
+    pub child_exit: WaitQueue,
+
     /// Mutable
     inner: UPSafeCell<TaskControlBlockInner>,
 }
@@ -34,14 +45,37 @@ impl TaskControlBlock {
     /// Get the address of app's page table
     pub fn get_user_token(&self) -> usize {
         let inner = self.inner_exclusive_access();
-        inner.memory_set.token()
+        inner.get_user_token()
     }
 }
 
+/// An open file in the fd table
+#[derive(Clone)]
+pub struct FdEntry {
+    /// The open file
+    pub file: Arc<dyn File + Send + Sync>,
+    /// Close the fd on exec (O_CLOEXEC), shared and cleared along with the slot
+    pub cloexec: bool,
+}
+
+impl FdEntry {
+    /// An entry for `file` kept open across exec
+    pub fn new(file: Arc<dyn File + Send + Sync>) -> Self {
+        Self { file, cloexec: false }
+    }
+}
+
+/// Open files indexed by fd, shared by the tasks cloned with CLONE_FILES
+pub type FdTable = Vec<Option<FdEntry>>;
+
 pub struct TaskControlBlockInner {
     /// The physical page number of the frame where the trap context is placed
     pub trap_cx_ppn: PhysPageNum,
 
+    /// User address of the trap context: TRAP_CONTEXT_BASE, or a page below
+    /// it for a task sharing the address space of another one
+    pub trap_cx_va: usize,
+
     /// Application data can only appear in areas
     /// where the application address space is lower than base_size
     pub base_size: usize,
@@ -52,8 +86,8 @@ pub struct TaskControlBlockInner {
     /// Maintain the execution status of the current process
     pub task_status: TaskStatus,
 
-    /// Application address space
-    pub memory_set: MemorySet,
+    /// Application address space, shared by the tasks cloned with CLONE_VM
+    pub memory_set: Arc<UPSafeCell<MemorySet>>,
 
     /// Parent process of the current process.
     /// Weak will not affect the reference count of the parent
@@ -70,7 +104,8 @@ pub struct TaskControlBlockInner {
     /// Option 使得我们可以区分一个文件描述符当前是否空闲，当它是 None 的时候是空闲的，而 Some 则代表它已被占用
     /// Arc 首先提供了共享引用能力，此外被它包裹的内容会被放到内核堆而不是栈上，于是它便不需要在编译期有着确定的大小
     /// dyn 关键字表明 Arc 里面的类型实现了 File/Send/Sync 三个 Trait，需要等到运行时才能知道它的具体类型
-    pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    /// 用 CLONE_FILES 创建的任务共享同一个表
+    pub fd_table: Arc<UPSafeCell<FdTable>>,
 
     /// Heap bottom
     pub heap_bottom: usize,
@@ -86,6 +121,37 @@ pub struct TaskControlBlockInner {
 
     /// 优先级等级
     pub pro_lev: usize,
+
+    /// Accumulated running time on CPU in microseconds
+    pub cpu_time: usize,
+
+    /// The time when the task was last switched in, in microseconds
+    pub last_scheduled: usize,
+
+    /// Peak number of resident user pages
+    pub max_rss: usize,
+
+    /// Max number of open files, fds are always below it
+    pub fd_limit: usize,
+
+    /// CPU time quota in seconds, the task is killed once `cpu_time` exceeds it
+    pub cpu_limit: usize,
+
+    /// CPUs the task may run on, bit i for CPU i
+    pub cpu_mask: usize,
+
+    /// Current working directory, relative paths are resolved from it
+    pub cwd: Arc<Inode>,
+
+    /// Root directory set by chroot(), absolute paths are resolved from it
+    pub root: Arc<Inode>,
+
+    /// Name for debugging, the program name unless changed by prctl()
+    pub name: String,
+
+    /// Set while the parent waits in clone(CLONE_VFORK) for the task to
+    /// exec or exit
+    pub vfork: bool,
 }
 
 impl TaskControlBlockInner {
@@ -93,7 +159,19 @@ impl TaskControlBlockInner {
         self.trap_cx_ppn.get_mut()
     }
     pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
+        self.memory_set.exclusive_access().token()
+    }
+    /// Exclusive access to the address space
+    pub fn memory_set(&self) -> RefMut<'_, MemorySet> {
+        self.memory_set.exclusive_access()
+    }
+    /// Exclusive access to the fd table
+    pub fn fd_table(&self) -> RefMut<'_, FdTable> {
+        self.fd_table.exclusive_access()
+    }
+    /// The file open as `fd`
+    pub fn file(&self, fd: usize) -> Option<Arc<dyn File + Send + Sync>> {
+        self.fd_table().get(fd)?.as_ref().map(|entry| Arc::clone(&entry.file))
     }
     fn get_status(&self) -> TaskStatus {
         self.task_status
@@ -101,27 +179,56 @@ impl TaskControlBlockInner {
     pub fn is_zombie(&self) -> bool {
         self.get_status() == TaskStatus::Zombie
     }
-    pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            fd
+    /// Find the lowest free fd, return None if fd_limit is reached
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        let mut fd_table = self.fd_table();
+        let len = fd_table.len().min(self.fd_limit);
+        if let Some(fd) = (0..len).find(|fd| fd_table[*fd].is_none()) {
+            Some(fd)
+        } else if fd_table.len() < self.fd_limit {
+            fd_table.push(None);
+            Some(fd_table.len() - 1)
         } else {
-            self.fd_table.push(None);
-            self.fd_table.len() - 1
+            None
         }
     }
 
      /// 添加一个逻辑段到应用地址空间
      pub fn add_maparea(&mut self, start_va: VirtAddr, end_va: VirtAddr, permission: MapPermission) {
-        self.memory_set.insert_framed_area(start_va, end_va, permission);
+        self.memory_set().insert_framed_area(start_va, end_va, permission);
+    }
+    /// 把文件映射到应用地址空间的一个新逻辑段
+    pub fn add_file_maparea(&mut self, start_va: VirtAddr, end_va: VirtAddr, permission: MapPermission, file: MappedFile) {
+        self.memory_set().insert_file_area(start_va, end_va, permission, file);
     }
     /// 删除应用地址空间的一个逻辑段
     pub fn remove_maparea(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> isize {
-        self.memory_set.remove_framed_area(start_va, end_va)
+        self.memory_set().remove_framed_area(start_va, end_va)
     }
 
     /// 检测新的映射区域是否与已有的映射区域冲突
     pub fn check_maparea(&self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
-        self.memory_set.check_conflict(start_va, end_va)
+        self.memory_set().check_conflict(start_va, end_va)
+    }
+
+    /// 预先载入文件映射区域内的页面
+    pub fn prefetch_maparea(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        self.memory_set().prefetch_pages(start_va, end_va)
+    }
+
+    /// 丢弃区域内页面的内容，之后读到的都是 0
+    pub fn discard_maparea(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        self.memory_set().discard_pages(start_va, end_va)
+    }
+
+    /// 载入映射中尚未分配页帧的页面，文件映射从文件读入
+    pub fn load_page(&mut self, va: VirtAddr) -> bool {
+        self.memory_set().load_page(va)
+    }
+
+    /// 把区域内被修改的页面写回共享映射的文件
+    pub fn sync_maparea(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        self.memory_set().sync_area(start_va, end_va)
     }
 
     /// update taskinfo
@@ -135,6 +242,64 @@ impl TaskControlBlockInner {
         self.task_info.clone()
     }
 
+    /// Charge the time since the task was last switched in to `cpu_time`
+    pub fn charge_cpu_time(&mut self) {
+        let now = get_time_us();
+        self.cpu_time += now - self.last_scheduled;
+        self.last_scheduled = now;
+    }
+
+    /// Charge the running time and check whether it exceeds `cpu_limit`
+    pub fn cpu_quota_exceeded(&mut self) -> bool {
+        self.charge_cpu_time();
+        self.cpu_time > self.cpu_limit.saturating_mul(1_000_000)
+    }
+
+    /// Total bytes (read, written) through the files currently open
+    pub fn io_stats(&self) -> (u64, u64) {
+        self.fd_table()
+            .iter()
+            .flatten()
+            .map(|entry| entry.file.io_stats())
+            .fold((0, 0), |(r, w), (fr, fw)| (r + fr, w + fw))
+    }
+    /// Discard pending console input if the task reads the console,
+    /// leaving the terminal clean for the next program
+    pub fn reset_terminal(&self) {
+        let fd_table = self.fd_table();
+        let stdin = fd_table
+            .iter()
+            .flatten()
+            .find_map(|entry| entry.file.as_any().downcast_ref::<Stdin>());
+        if let Some(stdin) = stdin {
+            stdin.reset();
+        }
+    }
+    /// Set the name of the task shown for debugging, cut to fit in
+    /// TASK_COMM_LEN bytes with the terminating NUL
+    pub fn set_name(&mut self, name: &str) {
+        let mut len = name.len().min(TASK_COMM_LEN - 1);
+        // 截断位置落在多字节字符中间时向前退到字符边界
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.name = String::from(&name[..len]);
+    }
+    /// Fold the peak resident pages of the address space into the
+    /// high-water mark, before the address space is replaced or freed
+    pub fn update_max_rss(&mut self) {
+        let peak = self.memory_set().peak_pages();
+        self.max_rss = self.max_rss.max(peak);
+    }
+    /// Give back the trap context page of the task in an address space
+    /// shared with other tasks, for a later clone(CLONE_VM) to reuse
+    pub fn release_trap_cx(&mut self) {
+        if Arc::strong_count(&self.memory_set) > 1 && self.trap_cx_va != TRAP_CONTEXT_BASE {
+            let vpn = VirtAddr::from(self.trap_cx_va).floor();
+            self.memory_set().remove_area_with_start_vpn(vpn);
+        }
+    }
+
 }
 
 impl TaskControlBlock {
@@ -150,36 +315,54 @@ impl TaskControlBlock {
             .ppn();
         // alloc a pid and a kernel stack in kernel space
         let pid_handle = pid_alloc();
+        let tgid = pid_handle.0;
         let kernel_stack = kstack_alloc();
         let kernel_stack_top = kernel_stack.get_top();
         // push a task context which goes to trap_return to the top of kernel stack
         let task_control_block = Self {
             pid: pid_handle,
+            tgid,
             kernel_stack,
+            child_exit: WaitQueue::new(),
             inner: unsafe {
                 UPSafeCell::new(TaskControlBlockInner {
                     trap_cx_ppn,
+                    trap_cx_va: TRAP_CONTEXT_BASE,
                     base_size: user_sp,
                     task_cx: TaskContext::goto_trap_return(kernel_stack_top),
                     task_status: TaskStatus::Ready,
-                    memory_set,
+                    memory_set: Arc::new(UPSafeCell::new(memory_set)),
                     parent: None,
                     children: Vec::new(),
                     exit_code: 0,
                     // 为进程打开标准输入文件和标准输出文件
-                    fd_table: vec![
+                    fd_table: Arc::new(UPSafeCell::new(vec![
                         // 0 -> stdin
-                        Some(Arc::new(Stdin)),
+                        Some(FdEntry::new(Arc::new(Stdin::new()))),
                         // 1 -> stdout
-                        Some(Arc::new(Stdout)),
+                        Some(FdEntry::new(Arc::new(if STDOUT_LINE_BUFFERED {
+                            Stdout::line_buffered()
+                        } else {
+                            Stdout::new()
+                        }))),
                         // 2 -> stderr
-                        Some(Arc::new(Stdout)),
-                    ],
+                        Some(FdEntry::new(Arc::new(Stdout::new()))),
+                    ])),
                     heap_bottom: user_sp,
                     program_brk: user_sp,
                     task_info: TaskInfo::new(),
                     cur_stride: 0,
                     pro_lev: 16,
+                    cpu_time: 0,
+                    last_scheduled: 0,
+                    max_rss: 0,
+                    fd_limit: DEFAULT_FD_LIMIT,
+                    cpu_limit: RLIM_INFINITY,
+                    cpu_mask: ALL_CPUS_MASK,
+                    cwd: ROOT_INODE.clone(),
+                    root: ROOT_INODE.clone(),
+                    name: String::new(),
+                    vfork: false,
                 })
             },
         };
@@ -195,10 +378,14 @@ impl TaskControlBlock {
         task_control_block
     }
 
-    /// Load a new elf to replace the original application address space and start execution
-    pub fn exec(&self, elf_data: &[u8]) {
+    /// Load a new elf to replace the original application address space and start execution,
+    /// return false and keep the original address space if the elf is invalid
+    pub fn exec(&self, elf_data: &[u8]) -> bool {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        // 先建好新的地址空间，ELF 无效时原地址空间保持不变
+        let Some((memory_set, user_sp, entry_point)) = MemorySet::try_from_elf(elf_data) else {
+            return false;
+        };
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT_BASE).into())
             .unwrap()
@@ -206,10 +393,24 @@ impl TaskControlBlock {
 
         // **** access current TCB exclusively
         let mut inner = self.inner_exclusive_access();
+        inner.update_max_rss();
+        inner.release_trap_cx();
         // substitute memory_set
-        inner.memory_set = memory_set;
+        inner.memory_set = Arc::new(unsafe { UPSafeCell::new(memory_set) });
         // update trap_cx ppn
         inner.trap_cx_ppn = trap_cx_ppn;
+        inner.trap_cx_va = TRAP_CONTEXT_BASE;
+        // 与其他任务共享的 fd 表换成私有的副本，关闭 O_CLOEXEC 的 fd 不影响它们
+        if Arc::strong_count(&inner.fd_table) > 1 {
+            let fd_table = inner.fd_table().clone();
+            inner.fd_table = Arc::new(unsafe { UPSafeCell::new(fd_table) });
+        }
+        // close fds marked O_CLOEXEC
+        for entry in inner.fd_table().iter_mut() {
+            if entry.as_ref().is_some_and(|entry| entry.cloexec) {
+                *entry = None;
+            }
+        }
         // initialize trap_cx
         let trap_cx = TrapContext::app_init_context(
             entry_point,
@@ -219,38 +420,72 @@ impl TaskControlBlock {
             trap_handler as usize,
         );
         *inner.get_trap_cx() = trap_cx;
+        // 在 clone(CLONE_VFORK) 中等待的父进程可以继续运行了
+        let vfork_parent = core::mem::take(&mut inner.vfork)
+            .then(|| inner.parent.as_ref().and_then(|parent| parent.upgrade()))
+            .flatten();
+        drop(inner);
         // **** release current PCB
+        if let Some(parent) = vfork_parent {
+            parent.child_exit.notify_all();
+        }
+        true
     }
 
     /// parent process fork the child process
     pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
+        self.clone_task(0)
+    }
+
+    /// Create a child sharing with the parent what `flags` select: CLONE_VM
+    /// the address space, CLONE_FILES the fd table and CLONE_THREAD the
+    /// thread group. The child resumes from a copy of the trap context
+    pub fn clone_task(self: &Arc<TaskControlBlock>, flags: usize) -> Arc<TaskControlBlock> {
         // ---- hold parent PCB lock
         let mut parent_inner = self.inner_exclusive_access();
-        // copy user space(include trap context)
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        let (memory_set, trap_cx_va) = if flags & CLONE_VM != 0 {
+            // 共享地址空间，在其中为子任务另映射一页 trap 上下文
+            let trap_cx_va = parent_inner.memory_set().alloc_trap_cx();
+            (Arc::clone(&parent_inner.memory_set), trap_cx_va)
+        } else {
+            // copy user space(include trap context)
+            let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set());
+            (Arc::new(unsafe { UPSafeCell::new(memory_set) }), parent_inner.trap_cx_va)
+        };
         let trap_cx_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT_BASE).into())
+            .exclusive_access()
+            .translate(VirtAddr::from(trap_cx_va).into())
             .unwrap()
             .ppn();
+        if flags & CLONE_VM != 0 {
+            trap_cx_ppn
+                .get_bytes_array()
+                .copy_from_slice(parent_inner.trap_cx_ppn.get_bytes_array());
+        }
         // alloc a pid and a kernel stack in kernel space
         let pid_handle = pid_alloc();
+        let tgid = if flags & CLONE_THREAD != 0 {
+            self.tgid
+        } else {
+            pid_handle.0
+        };
         let kernel_stack = kstack_alloc();
         let kernel_stack_top = kernel_stack.get_top();
-        // copy fd table
-        let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
-        for fd in parent_inner.fd_table.iter() {
-            if let Some(file) = fd {
-                new_fd_table.push(Some(file.clone()));
-            } else {
-                new_fd_table.push(None);
-            }
-        }
+        let fd_table = if flags & CLONE_FILES != 0 {
+            Arc::clone(&parent_inner.fd_table)
+        } else {
+            // copy fd table
+            Arc::new(unsafe { UPSafeCell::new(parent_inner.fd_table().clone()) })
+        };
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
+            tgid,
             kernel_stack,
+            child_exit: WaitQueue::new(),
             inner: unsafe {
                 UPSafeCell::new(TaskControlBlockInner {
                     trap_cx_ppn,
+                    trap_cx_va,
                     base_size: parent_inner.base_size,
                     task_cx: TaskContext::goto_trap_return(kernel_stack_top),
                     task_status: TaskStatus::Ready,
@@ -258,12 +493,22 @@ impl TaskControlBlock {
                     parent: Some(Arc::downgrade(self)),
                     children: Vec::new(),
                     exit_code: 0,
-                    fd_table: new_fd_table,
+                    fd_table,
                     heap_bottom: parent_inner.heap_bottom,
                     program_brk: parent_inner.program_brk,
                     task_info: TaskInfo::new(),
                     cur_stride: 0,
                     pro_lev: 16,
+                    cpu_time: 0,
+                    last_scheduled: 0,
+                    max_rss: 0,
+                    fd_limit: parent_inner.fd_limit,
+                    cpu_limit: parent_inner.cpu_limit,
+                    cpu_mask: parent_inner.cpu_mask,
+                    cwd: parent_inner.cwd.clone(),
+                    root: parent_inner.root.clone(),
+                    name: parent_inner.name.clone(),
+                    vfork: false,
                 })
             },
         });
@@ -284,6 +529,15 @@ impl TaskControlBlock {
         self.pid.0
     }
 
+    /// get pid of parent process, orphans are adopted by initproc
+    pub fn getppid(&self) -> usize {
+        self.inner_exclusive_access()
+            .parent
+            .as_ref()
+            .and_then(|parent| parent.upgrade())
+            .map_or_else(|| INITPROC.getpid(), |parent| parent.getpid())
+    }
+
     /// change the location of the program break. return None if failed.
     pub fn change_program_brk(&self, size: i32) -> Option<usize> {
         let mut inner = self.inner_exclusive_access();
@@ -295,11 +549,11 @@ impl TaskControlBlock {
         }
         let result = if size < 0 {
             inner
-                .memory_set
+                .memory_set()
                 .shrink_to(VirtAddr(heap_bottom), VirtAddr(new_brk as usize))
         } else {
             inner
-                .memory_set
+                .memory_set()
                 .append_to(VirtAddr(heap_bottom), VirtAddr(new_brk as usize))
         };
         if result {
@@ -324,34 +578,46 @@ impl TaskControlBlock {
         let pid_handle = pid_alloc();
         let kernel_stack = kstack_alloc();
         let kernel_stack_top = kernel_stack.get_top();
+        let tgid = pid_handle.0;
         // copy fd table
-        let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
-        for fd in parent_inner.fd_table.iter() {
-            if let Some(file) = fd {
-                new_fd_table.push(Some(file.clone()));
-            } else {
-                new_fd_table.push(None);
-            }
-        }
+        // fds marked O_CLOEXEC are not inherited, as spawn is fork + exec
+        let new_fd_table: FdTable = parent_inner
+            .fd_table()
+            .iter()
+            .map(|entry| entry.clone().filter(|entry| !entry.cloexec))
+            .collect();
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
+            tgid,
             kernel_stack,
+            child_exit: WaitQueue::new(),
             inner: unsafe {
                 UPSafeCell::new(TaskControlBlockInner {
                     trap_cx_ppn,
+                    trap_cx_va: TRAP_CONTEXT_BASE,
                     base_size: user_sp,  
                     task_cx: TaskContext::goto_trap_return(kernel_stack_top),
                     task_status: TaskStatus::Ready,
-                    memory_set,
+                    memory_set: Arc::new(UPSafeCell::new(memory_set)),
                     parent: Some(Arc::downgrade(self)),  //将父进程的弱引用计数放到子进程的进程控制块中
                     children: Vec::new(),
                     exit_code: 0,
-                    fd_table: new_fd_table,
+                    fd_table: Arc::new(UPSafeCell::new(new_fd_table)),
                     heap_bottom: parent_inner.heap_bottom,
                     program_brk: parent_inner.program_brk,
                     task_info: TaskInfo::new(),
                     cur_stride: 0,
                     pro_lev: 16,
+                    cpu_time: 0,
+                    last_scheduled: 0,
+                    max_rss: 0,
+                    fd_limit: parent_inner.fd_limit,
+                    cpu_limit: parent_inner.cpu_limit,
+                    cpu_mask: parent_inner.cpu_mask,
+                    cwd: parent_inner.cwd.clone(),
+                    root: parent_inner.root.clone(),
+                    name: String::new(),
+                    vfork: false,
                 })
             },
         });
@@ -387,6 +653,8 @@ pub enum TaskStatus {
     Ready,
     /// running
     Running,
+    /// parked on a wait queue
+    Blocked,
     /// exited
     Zombie,
 }
@@ -0,0 +1,64 @@
+//! Queue of tasks parked until an event happens
+//!
+//! A task calling [`WaitQueue::wait`] leaves the ready queue and is only
+//! scheduled again after another task or an interrupt handler notifies
+//! the queue, instead of spinning through `suspend_current_and_run_next`.
+use super::{add_task, schedule, take_current_task, TaskContext, TaskControlBlock, TaskStatus};
+use crate::sync::UPSafeCell;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// Tasks blocked on one event, woken in FIFO order
+pub struct WaitQueue {
+    waiters: UPSafeCell<VecDeque<Arc<TaskControlBlock>>>,
+}
+
+impl WaitQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self {
+            waiters: unsafe { UPSafeCell::new(VecDeque::new()) },
+        }
+    }
+
+    /// Block the current task on the queue and run the next task,
+    /// return once it has been notified and scheduled again
+    pub fn wait(&self) {
+        let task = take_current_task().unwrap();
+        let mut task_inner = task.inner_exclusive_access();
+        let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+        task_inner.task_status = TaskStatus::Blocked;
+        task_inner.charge_cpu_time();
+        drop(task_inner);
+        // 不放回就绪队列，由等待队列持有任务直到被唤醒
+        self.waiters.exclusive_access().push_back(task);
+        schedule(task_cx_ptr);
+    }
+
+    /// Move the longest waiting task back to the ready queue,
+    /// return whether there was one
+    pub fn notify_one(&self) -> bool {
+        let Some(task) = self.waiters.exclusive_access().pop_front() else {
+            return false;
+        };
+        task.inner_exclusive_access().task_status = TaskStatus::Ready;
+        add_task(task);
+        true
+    }
+
+    /// Move every waiting task back to the ready queue,
+    /// return the number of tasks woken
+    pub fn notify_all(&self) -> usize {
+        let mut woken = 0;
+        while self.notify_one() {
+            woken += 1;
+        }
+        woken
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
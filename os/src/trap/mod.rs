@@ -14,10 +14,13 @@
 
 mod context;
 
-use crate::config::{TRAMPOLINE, TRAP_CONTEXT_BASE};
+use crate::config::TRAMPOLINE;
+use crate::fs::sweep_cold_blocks;
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    current_cpu_quota_exceeded, current_trap_cx, current_trap_cx_user_va, current_user_token,
+    exit_current_and_run_next,
+    load_page, suspend_current_and_run_next, VirtAddr, TICK_QUEUE,
 };
 use crate::timer::set_next_trigger;
 use core::arch::{asm, global_asm};
@@ -66,11 +69,21 @@ pub fn trap_handler() -> ! {
             let mut cx = current_trap_cx();
             cx.sepc += 4;
             // get system call return value
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12], cx.x[13]]);
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
             // cx is changed during sys_exec, so we have to call it again
             cx = current_trap_cx();
             cx.x[10] = result as usize;
         }
+        Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::InstructionPageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+            if load_page(current_user_token(), VirtAddr::from(stval)) =>
+        {
+            // 文件映射和丢弃过的页面在访问时才载入，返回后重新执行该指令
+        }
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::InstructionFault)
@@ -93,7 +106,15 @@ pub fn trap_handler() -> ! {
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
-            suspend_current_and_run_next();
+            TICK_QUEUE.notify_all();
+            sweep_cold_blocks();
+            if current_cpu_quota_exceeded() {
+                println!("[kernel] CPU time quota exceeded in application, kernel killed it.");
+                // cpu quota exceeded exit code
+                exit_current_and_run_next(-4);
+            } else {
+                suspend_current_and_run_next();
+            }
         }
         _ => {
             panic!(
@@ -114,7 +135,7 @@ pub fn trap_handler() -> ! {
 /// finally, jump to new addr of __restore asm function
 pub fn trap_return() -> ! {
     set_user_trap_entry();
-    let trap_cx_ptr = TRAP_CONTEXT_BASE;
+    let trap_cx_ptr = current_trap_cx_user_va();
     let user_satp = current_user_token();
     extern "C" {
         fn __alltraps();
@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{access, close, open, unlink, AccessFlags, OpenFlags};
+
+/// access 只检查路径是否存在及权限，不打开文件
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(access("access_file\0", AccessFlags::empty()), -1);
+    let fd = open("access_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(access("access_file\0", AccessFlags::empty()), 0);
+    assert_eq!(access("access_file\0", AccessFlags::R_OK | AccessFlags::W_OK), 0);
+    // 目录可以读，不能按文件写
+    assert_eq!(access("/\0", AccessFlags::R_OK), 0);
+    assert_eq!(access("/\0", AccessFlags::W_OK), -1);
+    assert_eq!(unlink("access_file\0"), 0);
+    assert_eq!(access("access_file\0", AccessFlags::empty()), -1);
+    println!("Test access OK!");
+    0
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, pipe, read, sched_getaffinity, sched_setaffinity, waitpid, write};
+
+/// 目前只有 CPU 0
+const ALL_CPUS: isize = 1;
+
+/// 不存在的 CPU 被忽略，掩码中至少保留一个 CPU；子进程继承父进程的掩码
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(sched_getaffinity(0), ALL_CPUS);
+    assert_eq!(sched_setaffinity(0, 0b11), 0);
+    assert_eq!(sched_getaffinity(0), ALL_CPUS);
+    assert_eq!(sched_setaffinity(0, 0b10), -1);
+    assert_eq!(sched_setaffinity(0, 0), -1);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[1]);
+        assert_eq!(sched_getaffinity(0), ALL_CPUS);
+        let mut byte = [0u8];
+        assert_eq!(read(fds[0], &mut byte), 1);
+        exit(0);
+    }
+    close(fds[0]);
+    // 父进程可以查询和设置子进程，不能操作其他进程
+    assert_eq!(sched_getaffinity(pid as usize), ALL_CPUS);
+    assert_eq!(sched_setaffinity(pid as usize, 1), 0);
+    assert_eq!(sched_getaffinity(999_999), -1);
+    assert_eq!(write(fds[1], b"x"), 1);
+    close(fds[1]);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("Test affinity OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{spawn, wait};
+
+/// 内核启动的第一个进程：运行 usertests，并回收交给它的孤儿进程，
+/// 所有子进程都退出后以 usertests 的退出码结束，内核随之关机
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = spawn("usertests\0");
+    if pid < 0 {
+        println!("[initproc] failed to spawn usertests");
+        return -1;
+    }
+    let mut result = -1;
+    loop {
+        let mut exit_code: i32 = Default::default();
+        let child = wait(&mut exit_code);
+        if child < 0 {
+            return result;
+        }
+        if child == pid {
+            result = exit_code;
+        } else {
+            println!("[initproc] Released a zombie process, pid={}, exit_code={}", child, exit_code);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chmod, close, fstat, open, unlink, OpenFlags, Stat, StatMode};
+
+/// chmod 修改的权限位在 fstat 中可见，并在打开文件时生效
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("chmod_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(chmod("chmod_file\0", 0o444), 0);
+    let fd = open("chmod_file\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd as usize, &mut stat), 0);
+    assert_eq!((stat.mode & StatMode::PERM).bits(), 0o444);
+    close(fd as usize);
+    // 只读文件不能以写方式打开
+    assert_eq!(open("chmod_file\0", OpenFlags::WRONLY), -1);
+    assert_eq!(open("chmod_file\0", OpenFlags::RDWR), -1);
+    assert_eq!(chmod("chmod_file\0", 0o1000), -1);
+    assert_eq!(chmod("chmod_missing\0", 0o644), -1);
+    assert_eq!(chmod("chmod_file\0", 0o644), 0);
+    let fd = open("chmod_file\0", OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(unlink("chmod_file\0"), 0);
+    println!("Test chmod OK!");
+    0
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chown, close, fstat, open, unlink, OpenFlags, Stat};
+
+/// 新文件属于 0:0，chown 之后 fstat 报告新的属主
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("chown_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!((stat.uid, stat.gid), (0, 0));
+    assert_eq!(chown("chown_file\0", 1000, 100), 0);
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!((stat.uid, stat.gid), (1000, 100));
+    // 磁盘上只有 16 位
+    assert_eq!(chown("chown_file\0", 1 << 16, 0), -1);
+    assert_eq!(chown("chown_missing\0", 0, 0), -1);
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!((stat.uid, stat.gid), (1000, 100));
+    close(fd);
+    assert_eq!(unlink("chown_file\0"), 0);
+    println!("Test chown OK!");
+    0
+}
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    access, chdir, chroot, close, exit, fork, mkdir, open, rmdir, unlink, waitpid, AccessFlags,
+    OpenFlags,
+};
+
+/// chroot 之后绝对路径从新的根目录解析，`..` 停在根目录，子进程继承根目录
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("chroot_dir\0", 0o755), 0);
+    let fd = open("chroot_dir/inner\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    // chroot 无法撤销，在子进程中进行
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(chroot("chroot_dir\0"), 0);
+        assert_eq!(access("/inner\0", AccessFlags::empty()), 0);
+        assert_eq!(access("/chroot_dir\0", AccessFlags::empty()), -1);
+        // cwd 也移到了新的根目录中
+        assert_eq!(access("inner\0", AccessFlags::empty()), 0);
+        assert_eq!(access("/../../inner\0", AccessFlags::empty()), 0);
+        assert_eq!(chdir("..\0"), 0);
+        assert_eq!(access("inner\0", AccessFlags::empty()), 0);
+        let pid = fork();
+        if pid == 0 {
+            assert_eq!(access("/inner\0", AccessFlags::empty()), 0);
+            assert_eq!(access("../chroot_dir\0", AccessFlags::empty()), -1);
+            exit(0);
+        }
+        let mut exit_code = -1;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, 0);
+        // 普通文件和不存在的路径不能作为根目录
+        assert!(chroot("/inner\0") < 0);
+        assert!(chroot("/missing\0") < 0);
+        exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // 父进程的根目录不变
+    assert_eq!(access("/chroot_dir/inner\0", AccessFlags::empty()), 0);
+    assert_eq!(unlink("chroot_dir/inner\0"), 0);
+    assert_eq!(rmdir("chroot_dir\0"), 0);
+    println!("Test chroot OK!");
+    0
+}
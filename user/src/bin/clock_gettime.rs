@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    clock_gettime, exit, fork, waitpid, yield_, TimeVal, CLOCK_MONOTONIC, CLOCK_PROCESS_CPUTIME_ID,
+    CLOCK_REALTIME,
+};
+
+fn now(clock_id: usize) -> usize {
+    let mut ts = TimeVal::default();
+    assert_eq!(clock_gettime(clock_id, &mut ts), 0);
+    assert!(ts.usec < 1_000_000);
+    ts.as_us()
+}
+
+/// 单调时钟不回退；进程 CPU 时间不计入让出 CPU 期间其他任务的运行时间
+#[no_mangle]
+pub fn main() -> i32 {
+    assert!(now(CLOCK_REALTIME) > 0);
+    let start = now(CLOCK_MONOTONIC);
+    let cpu_start = now(CLOCK_PROCESS_CPUTIME_ID);
+    // 子进程忙等一段时间，父进程在此期间让出 CPU
+    let pid = fork();
+    if pid == 0 {
+        let begin = now(CLOCK_MONOTONIC);
+        while now(CLOCK_MONOTONIC) - begin < 100_000 {}
+        exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    yield_();
+    let elapsed = now(CLOCK_MONOTONIC) - start;
+    let cpu = now(CLOCK_PROCESS_CPUTIME_ID) - cpu_start;
+    assert!(elapsed >= 100_000);
+    assert!(cpu < elapsed);
+    let mut ts = TimeVal::default();
+    assert_eq!(clock_gettime(99, &mut ts), -1);
+    println!("Test clock_gettime OK!");
+    0
+}
@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    clone, close, fork, getpid, gettid, pipe, wait4, waitpid, yield_, RUsage, CLONE_FILES,
+    CLONE_THREAD, CLONE_VFORK, CLONE_VM, WNOHANG,
+};
+
+const STACK_SIZE: usize = 4096 * 4;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+static PIPE_FD: AtomicUsize = AtomicUsize::new(0);
+static CHILD_PID: AtomicUsize = AtomicUsize::new(0);
+static CHILD_TID: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn thread(arg: usize) -> i32 {
+    COUNTER.fetch_add(arg, Ordering::SeqCst);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    PIPE_FD.store(fds[0], Ordering::SeqCst);
+    close(fds[1]);
+    CHILD_PID.store(getpid() as usize, Ordering::SeqCst);
+    CHILD_TID.store(gettid() as usize, Ordering::SeqCst);
+    7
+}
+
+extern "C" fn vfork_child(arg: usize) -> i32 {
+    // 子进程让出 CPU 时父进程也要等它退出才继续运行
+    for _ in 0..10 {
+        yield_();
+    }
+    COUNTER.store(arg, Ordering::SeqCst);
+    0
+}
+
+/// fork 复制地址空间和 fd 表，CLONE_VM/CLONE_FILES/CLONE_THREAD 共享它们，
+/// CLONE_VFORK 的父进程在子进程退出后才返回
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut exit_code = 0;
+    // fork 出的子进程写的是自己的副本，打开的 fd 也不出现在父进程中
+    let pid = fork();
+    if pid == 0 {
+        COUNTER.store(100, Ordering::SeqCst);
+        let mut fds = [0usize; 2];
+        assert_eq!(pipe(&mut fds), 0);
+        return fds[0] as i32;
+    }
+    assert!(pid > 0);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(COUNTER.load(Ordering::SeqCst), 0);
+    assert_eq!(close(exit_code as usize), -1);
+
+    // 线程共享地址空间和 fd 表，属于同一线程组
+    let stack = vec![0u8; STACK_SIZE];
+    let stack_top = (stack.as_ptr() as usize + STACK_SIZE) & !0xf;
+    let tid = clone(CLONE_VM | CLONE_FILES | CLONE_THREAD, stack_top, thread, 5);
+    assert!(tid > 0);
+    assert_eq!(waitpid(tid as usize, &mut exit_code), tid);
+    assert_eq!(exit_code, 7);
+    assert_eq!(COUNTER.load(Ordering::SeqCst), 5);
+    assert_eq!(CHILD_PID.load(Ordering::SeqCst), getpid() as usize);
+    assert_eq!(CHILD_TID.load(Ordering::SeqCst), tid as usize);
+    assert_ne!(gettid(), tid);
+    assert_eq!(close(PIPE_FD.load(Ordering::SeqCst)), 0);
+    // 线程组必须共享地址空间
+    assert_eq!(clone(CLONE_THREAD, stack_top, thread, 0), -1);
+
+    // vfork 返回时子进程已经退出
+    let pid = clone(CLONE_VM | CLONE_VFORK, stack_top, vfork_child, 9);
+    assert!(pid > 0);
+    assert_eq!(COUNTER.load(Ordering::SeqCst), 9);
+    let mut rusage = RUsage::default();
+    assert_eq!(wait4(pid, &mut exit_code, WNOHANG, &mut rusage), pid);
+    assert_eq!(exit_code, 0);
+    println!("Test clone OK!");
+    0
+}
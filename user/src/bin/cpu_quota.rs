@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{fork, getrlimit, setrlimit, waitpid, RLimit, RLIMIT_CPU, RLIM_INFINITY};
+
+/// 内核终止超出 CPU 配额的任务时使用的退出码
+const EXIT_CPU_QUOTA: i32 = -4;
+
+/// 死循环的子进程在用完 1 秒的 CPU 配额后被内核终止
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut limit = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_CPU, &mut limit), 0);
+    assert_eq!(limit.rlim_cur, RLIM_INFINITY);
+    let pid = fork();
+    if pid == 0 {
+        let limit = RLimit { rlim_cur: 1, rlim_max: RLIM_INFINITY };
+        assert_eq!(setrlimit(RLIMIT_CPU, &limit), 0);
+        #[allow(clippy::empty_loop)]
+        loop {}
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, EXIT_CPU_QUOTA);
+    // 硬限制不能修改
+    let limit = RLimit { rlim_cur: 1, rlim_max: 1 };
+    assert_eq!(setrlimit(RLIMIT_CPU, &limit), -1);
+    println!("Test cpu_quota OK!");
+    0
+}
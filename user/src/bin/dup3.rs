@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup3, lseek, memfd_create, read, write, OpenFlags, SEEK_SET};
+
+/// dup3 复制到指定的 fd，两个 fd 共享同一个打开的文件
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = memfd_create("dup3\0");
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"abc"), 3);
+    assert_eq!(dup3(fd, 10, OpenFlags::empty()), 10);
+    // 偏移量是共享的
+    assert_eq!(lseek(10, 0, SEEK_SET), 0);
+    let mut buf = [0u8; 3];
+    assert_eq!(read(fd, &mut buf), 3);
+    assert_eq!(&buf, b"abc");
+    // 目标 fd 已打开时先被关闭
+    let other = memfd_create("other\0");
+    assert!(other >= 0);
+    assert_eq!(dup3(other as usize, 10, OpenFlags::CLOEXEC), 10);
+    assert_eq!(read(10, &mut buf), 0);
+    assert_eq!(dup3(fd, fd, OpenFlags::empty()), -1);
+    assert_eq!(dup3(fd, 11, OpenFlags::RDWR), -1);
+    assert_eq!(dup3(99, 11, OpenFlags::empty()), -1);
+    close(10);
+    close(other as usize);
+    close(fd);
+    println!("Test dup3 OK!");
+    0
+}
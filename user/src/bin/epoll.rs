@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    clock_gettime, close, epoll_create, epoll_ctl, epoll_wait, exit, fork, pipe, waitpid, write, EpollEvent,
+    TimeVal, CLOCK_MONOTONIC, EPOLLIN, EPOLLOUT, EPOLL_CTL_ADD, EPOLL_CTL_DEL,
+};
+
+fn now_ms() -> usize {
+    let mut ts = TimeVal::default();
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut ts), 0);
+    ts.as_us() / 1000
+}
+
+/// epoll_wait 睡到超时或被子进程写入管道唤醒，返回注册时的 data
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let epfd = epoll_create();
+    assert!(epfd >= 0);
+    let epfd = epfd as usize;
+    let event = EpollEvent { events: EPOLLIN, data: 7 };
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, fds[0], &event), 0);
+    let mut events = [EpollEvent::default(); 4];
+    assert_eq!(epoll_wait(epfd, &mut events, 0), 0);
+    let start = now_ms();
+    assert_eq!(epoll_wait(epfd, &mut events, 50), 0);
+    assert!(now_ms() - start >= 50);
+    // 写端总是可写
+    let event = EpollEvent { events: EPOLLOUT, data: 9 };
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_ADD, fds[1], &event), 0);
+    assert_eq!(epoll_wait(epfd, &mut events, -1), 1);
+    assert_eq!((events[0].events, events[0].data), (EPOLLOUT, 9));
+    assert_eq!(epoll_ctl(epfd, EPOLL_CTL_DEL, fds[1], &event), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        let begin = now_ms();
+        while now_ms() - begin < 20 {}
+        assert_eq!(write(fds[1], b"x"), 1);
+        exit(0);
+    }
+    close(fds[1]);
+    assert_eq!(epoll_wait(epfd, &mut events, -1), 1);
+    assert_eq!((events[0].events, events[0].data), (EPOLLIN, 7));
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(fds[0]);
+    close(epfd);
+    println!("Test epoll OK!");
+    0
+}
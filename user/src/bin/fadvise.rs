@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use user_lib::{
+    close, fadvise, open, pipe, read, unlink, write, OpenFlags, POSIX_FADV_DONTNEED, POSIX_FADV_SEQUENTIAL,
+};
+
+/// 丢弃缓存的块之前先写回，之后从磁盘重新读出相同的内容
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fadvise_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let data: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+    assert_eq!(write(fd, &data), data.len() as isize);
+    assert_eq!(fadvise(fd, 0, 0, POSIX_FADV_SEQUENTIAL), 0);
+    assert_eq!(fadvise(fd, 0, 0, POSIX_FADV_DONTNEED), 0);
+    close(fd);
+    let fd = open("fadvise_file\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let mut buf = vec![0u8; data.len()];
+    assert_eq!(read(fd, &mut buf), data.len() as isize);
+    assert_eq!(buf, data);
+    assert_eq!(fadvise(fd, 1024, 512, POSIX_FADV_DONTNEED), 0);
+    assert_eq!(fadvise(fd, 0, 0, 99), -1);
+    close(fd);
+    // 管道没有磁盘块
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(fadvise(fds[0], 0, 0, POSIX_FADV_DONTNEED), -1);
+    close(fds[0]);
+    close(fds[1]);
+    assert_eq!(unlink("fadvise_file\0"), 0);
+    println!("Test fadvise OK!");
+    0
+}
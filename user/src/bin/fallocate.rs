@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use user_lib::{
+    close, fallocate, fstat, fstatfs, open, pipe, read, unlink, write, OpenFlags, Stat, StatFs,
+};
+
+const LEN: usize = 4096;
+
+/// fallocate 分配的块读出全 0 并计入已用空间，空间不够时什么都不分配
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fallocate_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"head"), 4);
+    let mut before = StatFs::default();
+    assert_eq!(fstatfs(fd, &mut before), 0);
+    assert_eq!(fallocate(fd, 0, 0, LEN as isize), 0);
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.size, LEN as u64);
+    let mut after = StatFs::default();
+    assert_eq!(fstatfs(fd, &mut after), 0);
+    // 第一块已经有数据，只新分配其余的块
+    assert_eq!(after.bfree, before.bfree - (LEN as u64 / before.bsize - 1));
+    let fd2 = open("fallocate_file\0", OpenFlags::RDONLY);
+    assert!(fd2 >= 0);
+    let mut buf = vec![0xffu8; LEN + 1];
+    assert_eq!(read(fd2 as usize, &mut buf), LEN as isize);
+    assert_eq!(&buf[..4], b"head");
+    assert!(buf[4..LEN].iter().all(|byte| *byte == 0));
+    // 比剩余空间多一块：一块都不分配，大小不变 (ENOSPC)
+    let len = (after.bfree + 1) * after.bsize;
+    assert_eq!(fallocate(fd, 0, LEN as isize, len as isize), -1);
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.size, LEN as u64);
+    let mut failed = StatFs::default();
+    assert_eq!(fstatfs(fd, &mut failed), 0);
+    assert_eq!(failed.bfree, after.bfree);
+    // 不支持的模式；只读的 fd；管道
+    assert_eq!(fallocate(fd, 1, 0, 1), -1);
+    assert_eq!(fallocate(fd2 as usize, 0, 0, 1), -1);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(fallocate(fds[1], 0, 0, 1), -1);
+    close(fds[0]);
+    close(fds[1]);
+    close(fd2 as usize);
+    close(fd);
+    assert_eq!(unlink("fallocate_file\0"), 0);
+    println!("Test fallocate OK!");
+    0
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{access, chdir, close, fchdir, mkdir, open, rmdir, unlink, AccessFlags, OpenFlags};
+
+/// fchdir 进入打开的目录后，相对路径从该目录解析，绝对路径仍从根目录解析
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("fchdir_dir\0", 0o755), 0);
+    let dir = open("fchdir_dir\0", OpenFlags::RDONLY);
+    assert!(dir >= 0);
+    let dir = dir as usize;
+    assert_eq!(fchdir(dir), 0);
+    // 根目录下的绝对路径不能落到 cwd 中
+    assert_eq!(mkdir("/fchdir_top\0", 0o755), 0);
+    assert_eq!(access("fchdir_top\0", AccessFlags::empty()), -1);
+    let fd = open("/fchdir_top_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(access("fchdir_top_file\0", AccessFlags::empty()), -1);
+    let fd = open("inner\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(access("/fchdir_dir/inner\0", AccessFlags::empty()), 0);
+    // 普通文件不能作为 cwd
+    let file = open("inner\0", OpenFlags::RDONLY);
+    assert!(file >= 0);
+    assert_eq!(fchdir(file as usize), -1);
+    close(file as usize);
+    close(dir);
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(access("fchdir_top\0", AccessFlags::empty()), 0);
+    assert_eq!(unlink("fchdir_dir/inner\0"), 0);
+    assert_eq!(unlink("fchdir_top_file\0"), 0);
+    assert_eq!(rmdir("fchdir_dir\0"), 0);
+    assert_eq!(rmdir("fchdir_top\0"), 0);
+    println!("Test fchdir OK!");
+    0
+}
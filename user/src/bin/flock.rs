@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup3, exit, flock, fork, open, pipe, pread, read, unlink, waitpid, write, yield_,
+    OpenFlags, EWOULDBLOCK, LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN,
+};
+
+fn open_file() -> usize {
+    let fd = open("flock_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    fd as usize
+}
+
+/// 冲突的锁要等持有者释放，LOCK_NB 时立即返回 EWOULDBLOCK；
+/// 最后一个 fd 关闭或进程退出时锁被释放
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open_file();
+    assert_eq!(flock(fd, LOCK_EX), 0);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        // 子进程单独打开文件，不共享父进程的锁
+        close(fd);
+        close(fds[0]);
+        let fd = open_file();
+        assert_eq!(flock(fd, LOCK_EX | LOCK_NB), EWOULDBLOCK);
+        assert_eq!(flock(fd, LOCK_SH | LOCK_NB), EWOULDBLOCK);
+        assert_eq!(write(fds[1], b"x"), 1);
+        // 阻塞到父进程写完并解锁
+        assert_eq!(flock(fd, LOCK_EX), 0);
+        let mut buf = [0u8; 8];
+        assert_eq!(pread(fd, &mut buf, 0), 8);
+        assert_eq!(&buf, b"released");
+        // 退出时释放锁
+        exit(0);
+    }
+    close(fds[1]);
+    let mut buf = [0u8; 1];
+    assert_eq!(read(fds[0], &mut buf), 1);
+    close(fds[0]);
+    // 让子进程真正阻塞在 flock 中
+    for _ in 0..10 {
+        yield_();
+    }
+    assert_eq!(write(fd, b"released"), 8);
+    assert_eq!(flock(fd, LOCK_UN), 0);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(flock(fd, LOCK_EX | LOCK_NB), 0);
+
+    // 共享锁之间不冲突，但与排他锁冲突
+    assert_eq!(flock(fd, LOCK_SH), 0);
+    let other = open_file();
+    assert_eq!(flock(other, LOCK_SH | LOCK_NB), 0);
+    assert_eq!(flock(other, LOCK_EX | LOCK_NB), EWOULDBLOCK);
+    assert_eq!(flock(fd, LOCK_UN), 0);
+    assert_eq!(flock(other, LOCK_EX | LOCK_NB), 0);
+    // dup 出的 fd 共享锁，全部关闭后才释放
+    let dup = dup3(other, 20, OpenFlags::empty());
+    assert_eq!(dup, 20);
+    close(other);
+    assert_eq!(flock(fd, LOCK_SH | LOCK_NB), EWOULDBLOCK);
+    close(20);
+    assert_eq!(flock(fd, LOCK_EX | LOCK_NB), 0);
+    // 管道不能加锁；未知的操作
+    assert!(flock(fd, 0) < 0);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert!(flock(fds[0], LOCK_EX) < 0);
+    close(fds[0]);
+    close(fds[1]);
+    close(fd);
+    assert_eq!(unlink("flock_file\0"), 0);
+    println!("Test flock OK!");
+    0
+}
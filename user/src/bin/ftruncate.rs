@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, ftruncate, lseek, open, read, unlink, write, OpenFlags, Stat, SEEK_SET};
+
+/// ftruncate 缩小后丢弃尾部，再扩大时新增部分读出全 0
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("ftruncate_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, &[b'x'; 100]), 100);
+    assert_eq!(ftruncate(fd, 10), 0);
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.size, 10);
+    assert_eq!(ftruncate(fd, 20), 0);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let mut buf = [0xffu8; 32];
+    assert_eq!(read(fd, &mut buf), 20);
+    assert!(buf[..10].iter().all(|byte| *byte == b'x'));
+    assert!(buf[10..20].iter().all(|byte| *byte == 0));
+    assert_eq!(ftruncate(fd, -1), -1);
+    close(fd);
+    assert_eq!(unlink("ftruncate_file\0"), 0);
+    println!("Test ftruncate OK!");
+    0
+}
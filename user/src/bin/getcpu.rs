@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::{null_mut, read_unaligned};
+use user_lib::{getcpu, mmap, munmap, PROT_READ, PROT_WRITE};
+
+const START: usize = 0x1000_0000;
+const LEN: usize = 4096 * 2;
+
+/// 单核上 cpu 总是 0，写入跨页的指针时两页都写对
+#[no_mangle]
+pub fn main() -> i32 {
+    let (mut cpu, mut node) = (u32::MAX, u32::MAX);
+    assert_eq!(getcpu(&mut cpu, &mut node), 0);
+    assert_eq!((cpu, node), (0, 0));
+    assert_eq!(getcpu(null_mut(), null_mut()), 0);
+    assert_eq!(mmap(START, LEN, PROT_READ | PROT_WRITE), 0);
+    let mem = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, LEN) };
+    mem.fill(0xff);
+    // 前两个字节在第一页，后两个字节在第二页
+    let cpu = (START + 4096 - 2) as *mut u32;
+    assert_eq!(getcpu(cpu, null_mut()), 0);
+    assert_eq!(unsafe { read_unaligned(cpu) }, 0);
+    assert_eq!(mem[4096 - 3], 0xff);
+    assert_eq!(mem[4096 + 2], 0xff);
+    assert_eq!(munmap(START, LEN), 0);
+    println!("Test getcpu OK!");
+    0
+}
@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use user_lib::{close, fstat, getdents64, mkdir, open, rmdir, unlink, OpenFlags, Stat, DT_DIR, DT_REG};
+
+/// d_ino, d_off, d_reclen, d_type 之后才是名字
+const HEADER_SZ: usize = 19;
+
+/// Read every dirent of directory `fd` as (name, inode, type)
+fn read_dir(fd: usize, buf: &mut [u8]) -> Vec<(String, u64, u8)> {
+    let mut entries = Vec::new();
+    loop {
+        let filled = getdents64(fd, buf);
+        assert!(filled >= 0);
+        if filled == 0 {
+            return entries;
+        }
+        let mut pos = 0;
+        while pos < filled as usize {
+            let record = &buf[pos..];
+            let ino = u64::from_le_bytes(record[..8].try_into().unwrap());
+            let reclen = u16::from_le_bytes(record[16..18].try_into().unwrap()) as usize;
+            assert_eq!(reclen % 8, 0);
+            let name = &record[HEADER_SZ..reclen];
+            let name = &name[..name.iter().position(|b| *b == 0).unwrap()];
+            entries.push((String::from(core::str::from_utf8(name).unwrap()), ino, record[18]));
+            pos += reclen;
+        }
+    }
+}
+
+fn ino_of(path: &str) -> u64 {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd as usize, &mut stat), 0);
+    close(fd as usize);
+    stat.ino
+}
+
+/// 记录带有 inode 编号和类型，小缓冲区分多次读取时记录不会被截断
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("getdents_dir\0", 0o755), 0);
+    for path in ["getdents_dir/a\0", "getdents_dir/long_file_name\0"] {
+        let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd >= 0);
+        close(fd as usize);
+    }
+    assert_eq!(mkdir("getdents_dir/sub\0", 0o755), 0);
+    let fd = open("getdents_dir\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    // 连一条记录都放不下
+    assert_eq!(getdents64(fd, &mut [0u8; 16]), -1);
+    // 每次只放得下一两条记录
+    let entries = read_dir(fd, &mut [0u8; 48]);
+    close(fd);
+    let find = |name: &str| entries.iter().find(|(n, _, _)| n == name).map(|(_, ino, ty)| (*ino, *ty));
+    assert_eq!(find("a"), Some((ino_of("getdents_dir/a\0"), DT_REG)));
+    assert_eq!(find("long_file_name"), Some((ino_of("getdents_dir/long_file_name\0"), DT_REG)));
+    assert_eq!(find("sub"), Some((ino_of("getdents_dir/sub\0"), DT_DIR)));
+    assert_eq!(entries.iter().filter(|(n, _, _)| n == "a").count(), 1);
+    // 普通文件不是目录
+    let fd = open("getdents_dir/a\0", OpenFlags::RDONLY);
+    assert_eq!(getdents64(fd as usize, &mut [0u8; 64]), -1);
+    close(fd as usize);
+    assert_eq!(unlink("getdents_dir/a\0"), 0);
+    assert_eq!(unlink("getdents_dir/long_file_name\0"), 0);
+    assert_eq!(rmdir("getdents_dir/sub\0"), 0);
+    assert_eq!(rmdir("getdents_dir\0"), 0);
+    println!("Test getdents64 OK!");
+    0
+}
@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use user_lib::{close, getdents64, mkdir, open, rmdir, unlink, OpenFlags};
+
+/// d_ino, d_off, d_reclen, d_type 之后才是名字
+const HEADER_SZ: usize = 19;
+const FILES: usize = 8;
+
+/// Read the one dirent that fits in `buf`, None at the end of the directory
+fn next_entry(fd: usize, buf: &mut [u8]) -> Option<String> {
+    let filled = getdents64(fd, buf);
+    assert!(filled >= 0);
+    if filled == 0 {
+        return None;
+    }
+    let reclen = u16::from_le_bytes(buf[16..18].try_into().unwrap()) as usize;
+    assert_eq!(reclen, filled as usize);
+    let name = &buf[HEADER_SZ..reclen];
+    let name = &name[..name.iter().position(|b| *b == 0).unwrap()];
+    Some(String::from(core::str::from_utf8(name).unwrap()))
+}
+
+/// 读到一半时删除已读过的目录项，剩下的每一项仍恰好返回一次
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("getdents_unlink\0", 0o755), 0);
+    for i in 0..FILES {
+        let fd = open(&format!("getdents_unlink/f{}\0", i), OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd >= 0);
+        close(fd as usize);
+    }
+    let fd = open("getdents_unlink\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    // 缓冲区每次只放得下一条记录
+    let mut buf = [0u8; 32];
+    let mut names = Vec::new();
+    for _ in 0..FILES / 2 {
+        names.push(next_entry(fd, &mut buf).unwrap());
+    }
+    let removed = names[1].clone();
+    assert_eq!(unlink(&format!("getdents_unlink/{}\0", removed)), 0);
+    while let Some(name) = next_entry(fd, &mut buf) {
+        names.push(name);
+    }
+    close(fd);
+    names.sort();
+    let expected: Vec<String> = (0..FILES).map(|i| format!("f{}", i)).collect();
+    assert_eq!(names, expected);
+    for name in names.iter().filter(|name| **name != removed) {
+        assert_eq!(unlink(&format!("getdents_unlink/{}\0", name)), 0);
+    }
+    assert_eq!(rmdir("getdents_unlink\0"), 0);
+    println!("Test getdents_unlink OK!");
+    0
+}
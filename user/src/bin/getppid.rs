@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, getpid, getppid, waitpid};
+
+/// 子进程的 getppid() 应当是 fork 它的进程
+#[no_mangle]
+pub fn main() -> i32 {
+    let parent = getpid();
+    let pid = fork();
+    if pid == 0 {
+        exit(if getppid() == parent { 0 } else { 1 });
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("Test getppid OK!");
+    0
+}
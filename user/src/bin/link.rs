@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, link, mkdir, open, read, rmdir, unlink, write, OpenFlags, Stat};
+
+fn stat_of(path: &str) -> Stat {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd as usize, &mut stat), 0);
+    close(fd as usize);
+    stat
+}
+
+/// 硬链接的两个名字指向同一个 inode，链接数随之增减
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("link_a\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"shared"), 6);
+    close(fd as usize);
+    assert_eq!(link("link_a\0", "link_b\0"), 0);
+    let (a, b) = (stat_of("link_a\0"), stat_of("link_b\0"));
+    assert_eq!(a.ino, b.ino);
+    assert_eq!((a.nlink, b.nlink), (2, 2));
+    // 目标已存在或源不存在
+    assert_eq!(link("link_a\0", "link_b\0"), -1);
+    assert_eq!(link("link_missing\0", "link_c\0"), -1);
+    assert_eq!(unlink("link_a\0"), 0);
+    assert_eq!(stat_of("link_b\0").nlink, 1);
+    let fd = open("link_b\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut buf = [0u8; 6];
+    assert_eq!(read(fd as usize, &mut buf), 6);
+    assert_eq!(&buf, b"shared");
+    close(fd as usize);
+    // 另一个目录中的链接同样计数
+    assert_eq!(mkdir("link_dir\0", 0o755), 0);
+    assert_eq!(link("link_b\0", "link_dir/c\0"), 0);
+    assert_eq!(stat_of("link_b\0").nlink, 2);
+    assert_eq!(stat_of("link_dir/c\0").nlink, 2);
+    assert_eq!(unlink("link_dir/c\0"), 0);
+    assert_eq!(rmdir("link_dir\0"), 0);
+    assert_eq!(stat_of("link_b\0").nlink, 1);
+    assert_eq!(unlink("link_b\0"), 0);
+    println!("Test link OK!");
+    0
+}
@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{madvise, mmap, munmap, MADV_DONTNEED, MADV_WILLNEED, PROT_READ, PROT_WRITE};
+
+const START: usize = 0x1000_0000;
+const LEN: usize = 4096 * 2;
+
+/// DONTNEED 之后再访问读到全零；范围必须落在已有映射内
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mmap(START, LEN, PROT_READ | PROT_WRITE), 0);
+    let mem = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, LEN) };
+    mem.fill(0xa5);
+    assert_eq!(madvise(START, LEN, MADV_WILLNEED), 0);
+    assert!(mem.iter().all(|b| *b == 0xa5));
+    assert_eq!(madvise(START + 4096, 4096, MADV_DONTNEED), 0);
+    assert!(mem[..4096].iter().all(|b| *b == 0xa5));
+    assert!(mem[4096..].iter().all(|b| *b == 0));
+    // 丢弃后的页面仍可写
+    mem[4096] = 1;
+    assert_eq!(mem[4096], 1);
+    // 超出映射或未对齐
+    assert_eq!(madvise(START, LEN + 4096, MADV_DONTNEED), -1);
+    assert_eq!(madvise(START + 1, 4096, MADV_DONTNEED), -1);
+    assert_eq!(madvise(START, LEN, 99), -1);
+    assert_eq!(munmap(START, LEN), 0);
+    assert_eq!(madvise(START, LEN, MADV_WILLNEED), -1);
+    println!("Test madvise OK!");
+    0
+}
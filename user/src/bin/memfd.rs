@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, memfd_create, read, write, SEEK_CUR, SEEK_END, SEEK_SET};
+
+/// 内存文件可以读写和定位，越过末尾写入时中间补零
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = memfd_create("scratch\0");
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"hello"), 5);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd, &mut buf), 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(lseek(fd, 3, SEEK_CUR), 8);
+    assert_eq!(write(fd, b"!"), 1);
+    assert_eq!(lseek(fd, 0, SEEK_END), 9);
+    assert_eq!(lseek(fd, -4, SEEK_END), 5);
+    assert_eq!(read(fd, &mut buf), 4);
+    assert_eq!(&buf[..4], b"\0\0\0!");
+    assert_eq!(lseek(fd, -1, SEEK_SET), -1);
+    close(fd);
+    println!("Test memfd OK!");
+    0
+}
@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, mkdir, open, rmdir, unlink, OpenFlags, Stat, StatMode};
+
+const ENOENT: isize = 2;
+const EEXIST: isize = 17;
+const ENOTDIR: isize = 20;
+const EINVAL: isize = 22;
+const ENAMETOOLONG: isize = 36;
+const ENOTEMPTY: isize = 39;
+
+/// mkdir/rmdir 成功时返回 0，失败时返回对应的负 errno
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("mkdir_dir\0", 0o750), 0);
+    assert_eq!(mkdir("mkdir_dir\0", 0o750), -EEXIST);
+    assert_eq!(mkdir("/\0", 0o750), -EEXIST);
+    assert_eq!(mkdir("mkdir_missing/sub\0", 0o750), -ENOENT);
+    // 名字超过 26 字节
+    assert_eq!(mkdir("mkdir_a_directory_name_too_long\0", 0o750), -ENAMETOOLONG);
+    assert_eq!(mkdir("mkdir_dir/sub\0", 0o700), 0);
+    let fd = open("mkdir_dir\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd as usize, &mut stat), 0);
+    assert!(stat.mode.contains(StatMode::DIR));
+    assert_eq!((stat.mode & StatMode::PERM).bits(), 0o750);
+    close(fd as usize);
+    let fd = open("mkdir_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(mkdir("mkdir_file/sub\0", 0o750), -ENOTDIR);
+    assert_eq!(rmdir("mkdir_file\0"), -ENOTDIR);
+    assert_eq!(rmdir("mkdir_dir\0"), -ENOTEMPTY);
+    assert_eq!(rmdir("mkdir_dir/..\0"), -EINVAL);
+    assert_eq!(rmdir("mkdir_dir/sub\0"), 0);
+    assert_eq!(rmdir("mkdir_dir/sub\0"), -ENOENT);
+    assert_eq!(rmdir("mkdir_dir\0"), 0);
+    assert_eq!(unlink("mkdir_file\0"), 0);
+    println!("Test mkdir_rmdir OK!");
+    0
+}
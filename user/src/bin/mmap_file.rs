@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use user_lib::{
+    close, lseek, madvise, mmap, mmap_file, munmap, open, pipe, read, unlink, write, OpenFlags,
+    MADV_WILLNEED, MAP_ANONYMOUS, MAP_PRIVATE, MAP_SHARED, PROT_READ, PROT_WRITE, SEEK_SET,
+};
+
+const START: usize = 0x1000_0000;
+const LEN: usize = 4096 * 2;
+const SIZE: usize = 5000;
+
+fn mapping() -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(START as *mut u8, LEN) }
+}
+
+/// 文件映射的页面首次访问时读入，共享映射在 munmap 时写回，私有映射不写回
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("mmap_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let mut data = vec![0u8; SIZE];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    assert_eq!(write(fd, &data), SIZE as isize);
+    let rw = PROT_READ | PROT_WRITE;
+    // 内核读一个尚未访问过的页面
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(mmap_file(START, LEN, rw, MAP_PRIVATE, fd, 0), 0);
+    assert_eq!(write(fds[1], &mapping()[4096..4112]), 16);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fds[0], &mut buf), 16);
+    assert_eq!(buf, data[4096..4112]);
+    // 私有映射的写入不会写回文件
+    let mem = mapping();
+    assert_eq!(&mem[..SIZE], &data[..]);
+    assert!(mem[SIZE..].iter().all(|byte| *byte == 0));
+    mem[0] = 0xee;
+    assert_eq!(munmap(START, LEN), 0);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, &mut buf[..1]), 1);
+    assert_eq!(buf[0], data[0]);
+    // 共享映射从偏移处开始，munmap 时写回被修改的页面，不改变文件大小
+    assert_eq!(mmap_file(START, 4096, rw, MAP_SHARED, fd, 4096), 0);
+    let mem = mapping();
+    assert_eq!(mem[0], data[4096]);
+    mem[10] = 0x77;
+    assert_eq!(munmap(START, 4096), 0);
+    let mut file = vec![0u8; LEN];
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, &mut file), SIZE as isize);
+    data[4096 + 10] = 0x77;
+    assert_eq!(&file[..SIZE], &data[..]);
+    // WILLNEED 立即读入私有映射的页面，之后对文件的修改不再出现在映射中
+    assert_eq!(mmap_file(START, LEN, rw, MAP_PRIVATE, fd, 0), 0);
+    assert_eq!(madvise(START, LEN, MADV_WILLNEED), 0);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(write(fd, &[0xdd]), 1);
+    assert_eq!(mapping()[0], data[0]);
+    assert_eq!(munmap(START, LEN), 0);
+    // 偏移未对齐、缺少映射类型或 fd 无效
+    assert_eq!(mmap_file(START, LEN, rw, MAP_SHARED, fd, 100), -1);
+    assert_eq!(mmap_file(START, LEN, rw, 0, fd, 0), -1);
+    assert_eq!(mmap_file(START, LEN, rw, MAP_SHARED, 99, 0), -1);
+    // 三个参数的 mmap 与 MAP_ANONYMOUS 都是全零的匿名映射
+    assert_eq!(mmap(START, LEN, rw), 0);
+    assert!(mapping().iter().all(|byte| *byte == 0));
+    assert_eq!(munmap(START, LEN), 0);
+    assert_eq!(mmap_file(START, LEN, rw, MAP_PRIVATE | MAP_ANONYMOUS, fd, 0), 0);
+    assert!(mapping().iter().all(|byte| *byte == 0));
+    assert_eq!(munmap(START, LEN), 0);
+    close(fds[0]);
+    close(fds[1]);
+    close(fd);
+    assert_eq!(unlink("mmap_file\0"), 0);
+    println!("Test mmap_file OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, mount, open, rmdir, umount, unlink, MountFlags, OpenFlags};
+
+/// 挂载点和设备不合法时 mount/umount 失败，且不改变目录树
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("mount_dir\0", 0o755), 0);
+    // 0 号设备是根文件系统，不能再挂载一次
+    assert_eq!(mount(0, "mount_dir\0", MountFlags::empty()), -1);
+    // 不存在的设备
+    assert_eq!(mount(usize::MAX, "mount_dir\0", MountFlags::empty()), -1);
+    assert_eq!(mount(1, "mount_missing\0", MountFlags::empty()), -1);
+    assert_eq!(mount(1, "mount_dir\0", MountFlags::OVERLAY), -1);
+    // 挂载点必须是目录
+    let fd = open("mount_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(mount(1, "mount_file\0", MountFlags::empty()), -1);
+    // 没有挂载任何东西的目录不能卸载
+    assert_eq!(umount("mount_dir\0"), -1);
+    assert_eq!(umount("/\0"), -1);
+    assert_eq!(unlink("mount_file\0"), 0);
+    assert_eq!(rmdir("mount_dir\0"), 0);
+    println!("Test mount OK!");
+    0
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, lseek, mmap, mmap_file, msync, munmap, open, read, unlink, write, OpenFlags, MAP_SHARED,
+    MS_ASYNC, MS_SYNC, PROT_READ, PROT_WRITE, SEEK_SET,
+};
+
+const START: usize = 0x1000_0000;
+const LEN: usize = 4096 * 2;
+
+fn read_file(fd: usize, offset: usize) -> u8 {
+    let mut byte = [0u8];
+    assert_eq!(lseek(fd, offset as isize, SEEK_SET), offset as isize);
+    assert_eq!(read(fd, &mut byte), 1);
+    byte[0]
+}
+
+/// msync 把共享文件映射中被修改的页面写回文件，映射保持不变
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("msync\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let page = [1u8; 4096];
+    assert_eq!(write(fd, &page), 4096);
+    assert_eq!(write(fd, &page), 4096);
+    assert_eq!(mmap_file(START, LEN, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0), 0);
+    let mem = unsafe { core::slice::from_raw_parts_mut(START as *mut u8, LEN) };
+    mem[4096 + 5] = 9;
+    assert_eq!(read_file(fd, 4096 + 5), 1);
+    assert_eq!(msync(START, LEN, MS_SYNC), 0);
+    assert_eq!(read_file(fd, 4096 + 5), 9);
+    // 写回之后的修改由下一次 msync 写回
+    mem[4096 + 5] = 10;
+    assert_eq!(msync(START + 4096, 4096, MS_ASYNC), 0);
+    assert_eq!(read_file(fd, 4096 + 5), 10);
+    // 超出映射、未对齐或标志冲突
+    assert_eq!(msync(START, LEN + 4096, MS_SYNC), -1);
+    assert_eq!(msync(START + 1, 4096, MS_SYNC), -1);
+    assert_eq!(msync(START, LEN, MS_SYNC | MS_ASYNC), -1);
+    assert_eq!(munmap(START, LEN), 0);
+    assert_eq!(msync(START, LEN, MS_SYNC), -1);
+    // 匿名映射没有需要写回的内容
+    assert_eq!(mmap(START, LEN, PROT_READ | PROT_WRITE), 0);
+    assert_eq!(msync(START, LEN, MS_SYNC), 0);
+    assert_eq!(munmap(START, LEN), 0);
+    close(fd);
+    assert_eq!(unlink("msync\0"), 0);
+    println!("Test msync OK!");
+    0
+}
@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chdir, close, mkdir, open, openat, read, rmdir, unlink, write, OpenFlags, AT_FDCWD};
+
+/// 相对路径从 dirfd 指向的目录解析，与 cwd 无关；绝对路径忽略 dirfd
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("openat_dir\0", 0o755), 0);
+    assert_eq!(mkdir("openat_other\0", 0o755), 0);
+    let fd = open("openat_dir/file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"inside"), 6);
+    close(fd as usize);
+    let dir = open("openat_dir\0", OpenFlags::RDONLY);
+    assert!(dir >= 0);
+    // cwd 换到另一个目录后 dirfd 仍然有效
+    assert_eq!(chdir("openat_other\0"), 0);
+    assert!(openat(AT_FDCWD, "file\0", OpenFlags::RDONLY) < 0);
+    let fd = openat(dir, "file\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut buf = [0u8; 6];
+    assert_eq!(read(fd as usize, &mut buf), 6);
+    assert_eq!(&buf, b"inside");
+    // 普通文件不能作为 dirfd，关闭的 fd 也不行
+    assert!(openat(fd, "file\0", OpenFlags::RDONLY) < 0);
+    close(fd as usize);
+    assert!(openat(fd, "file\0", OpenFlags::RDONLY) < 0);
+    let fd = openat(dir, "/openat_dir/file\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    let fd = openat(dir, "created\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    close(dir as usize);
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(unlink("openat_dir/created\0"), 0);
+    assert_eq!(unlink("openat_dir/file\0"), 0);
+    assert_eq!(rmdir("openat_dir\0"), 0);
+    assert_eq!(rmdir("openat_other\0"), 0);
+    println!("Test openat OK!");
+    0
+}
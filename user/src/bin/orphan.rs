@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use user_lib::{close, exit, fork, getpid, getppid, open, pipe, read, waitpid, write, yield_, OpenFlags};
+
+/// initproc 的 pid
+const INIT_PID: isize = 0;
+const ORPHAN_EXIT_CODE: i32 = 42;
+
+fn read_isize(fd: usize) -> isize {
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd, &mut buf), 8);
+    isize::from_le_bytes(buf)
+}
+
+/// 父进程先于子进程退出时子进程交给 initproc，由它 wait(-1) 回收
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let parent = fork();
+    if parent == 0 {
+        if fork() == 0 {
+            // 孙进程：等父进程退出后报告新的父进程
+            let old_parent = getppid();
+            while getppid() == old_parent {
+                yield_();
+            }
+            write(fds[1], &getpid().to_le_bytes());
+            write(fds[1], &getppid().to_le_bytes());
+            exit(ORPHAN_EXIT_CODE);
+        }
+        exit(0);
+    }
+    close(fds[1]);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(parent as usize, &mut exit_code), parent);
+    assert_eq!(exit_code, 0);
+    let orphan = read_isize(fds[0]);
+    assert_eq!(read_isize(fds[0]), INIT_PID);
+    close(fds[0]);
+    // initproc 回收之后 /proc 中就找不到它了
+    let path = format!("/proc/{}/status\0", orphan);
+    let mut reaped = false;
+    for _ in 0..1000 {
+        let fd = open(&path, OpenFlags::RDONLY);
+        if fd < 0 {
+            reaped = true;
+            break;
+        }
+        close(fd as usize);
+        yield_();
+    }
+    assert!(reaped);
+    println!("Test orphan OK!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, pipe, read, waitpid, write};
+
+const LEN: usize = 1000;
+
+/// 写入的数据远大于管道缓冲区，读写两端要多次绕过环形缓冲区的末尾
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        let mut data = [0u8; LEN];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = (i % 251) as u8;
+        }
+        assert_eq!(write(fds[1], &data), LEN as isize);
+        close(fds[1]);
+        exit(0);
+    }
+    close(fds[1]);
+    let mut buf = [0u8; LEN];
+    let mut got = 0;
+    loop {
+        let n = read(fds[0], &mut buf[got..]);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        got += n as usize;
+    }
+    assert_eq!(got, LEN);
+    for (i, &byte) in buf.iter().enumerate() {
+        assert_eq!(byte, (i % 251) as u8);
+    }
+    close(fds[0]);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("Test pipe_large OK!");
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, memfd_create, pipe, read, recv, write, MSG_PEEK};
+
+/// MSG_PEEK 读出的字节仍留在管道中，随后的读得到相同的内容
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(write(fds[1], b"peek me"), 7);
+    let mut peeked = [0u8; 4];
+    assert_eq!(recv(fds[0], &mut peeked, MSG_PEEK), 4);
+    assert_eq!(&peeked, b"peek");
+    let mut buf = [0u8; 16];
+    assert_eq!(recv(fds[0], &mut buf, MSG_PEEK), 7);
+    assert_eq!(&buf[..7], b"peek me");
+    // 不带标志时照常取走数据
+    assert_eq!(recv(fds[0], &mut peeked, 0), 4);
+    assert_eq!(&peeked, b"peek");
+    assert_eq!(read(fds[0], &mut buf), 3);
+    assert_eq!(&buf[..3], b" me");
+    // 写端；不是管道；未知的标志
+    assert_eq!(recv(fds[1], &mut buf, MSG_PEEK), -1);
+    let memfd = memfd_create("pipe_peek\0");
+    assert!(memfd >= 0);
+    assert_eq!(recv(memfd as usize, &mut buf, MSG_PEEK), -1);
+    close(memfd as usize);
+    assert_eq!(recv(fds[0], &mut buf, 0x100), -1);
+    // 写端关闭且没有数据时返回 0
+    close(fds[1]);
+    assert_eq!(recv(fds[0], &mut buf, MSG_PEEK), 0);
+    close(fds[0]);
+    println!("Test pipe_peek OK!");
+    0
+}
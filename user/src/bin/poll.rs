@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    clock_gettime, close, exit, fork, pipe, poll, waitpid, write, PollFd, TimeVal, CLOCK_MONOTONIC,
+    POLLIN, POLLNVAL, POLLOUT,
+};
+
+fn now_ms() -> usize {
+    let mut ts = TimeVal::default();
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut ts), 0);
+    ts.as_us() / 1000
+}
+
+/// 没有就绪的 fd 时 poll 睡到超时；子进程写管道会唤醒父进程的 poll
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let mut pollfds = [
+        PollFd { fd: fds[0] as i32, events: POLLIN, revents: 0 },
+        PollFd { fd: fds[1] as i32, events: POLLOUT, revents: 0 },
+        PollFd { fd: 99, events: POLLIN, revents: 0 },
+    ];
+    // 空管道：只有写端和无效 fd 就绪
+    assert_eq!(poll(&mut pollfds, 0), 2);
+    assert_eq!(pollfds[0].revents, 0);
+    assert_eq!(pollfds[1].revents, POLLOUT);
+    assert_eq!(pollfds[2].revents, POLLNVAL);
+    // 超时
+    let start = now_ms();
+    assert_eq!(poll(&mut pollfds[..1], 50), 0);
+    assert!(now_ms() - start >= 50);
+    // 子进程稍后写入，父进程无限期等待读端就绪
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        let begin = now_ms();
+        while now_ms() - begin < 20 {}
+        assert_eq!(write(fds[1], b"x"), 1);
+        exit(0);
+    }
+    close(fds[1]);
+    assert_eq!(poll(&mut pollfds[..1], -1), 1);
+    assert_eq!(pollfds[0].revents, POLLIN);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    close(fds[0]);
+    println!("Test poll OK!");
+    0
+}
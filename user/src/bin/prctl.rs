@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    dup3, exec, exit, fork, lseek, memfd_create, prctl, waitpid, OpenFlags, PR_GET_NAME,
+    PR_SET_NAME, SEEK_CUR, TASK_COMM_LEN,
+};
+
+/// exec 之前打开的标记 fd，exec 后仍然打开，用来识别重新执行的自己
+const MARKER_FD: usize = 30;
+
+/// `name` must end with \0
+fn set_name(name: &str) -> isize {
+    prctl(PR_SET_NAME, name.as_ptr() as usize)
+}
+
+fn name() -> [u8; TASK_COMM_LEN] {
+    let mut name = [0xffu8; TASK_COMM_LEN];
+    assert_eq!(prctl(PR_GET_NAME, name.as_mut_ptr() as usize), 0);
+    name
+}
+
+fn name_is(name: &[u8; TASK_COMM_LEN], expected: &[u8]) -> bool {
+    name[..expected.len()] == *expected && name[expected.len()] == 0
+}
+
+/// 设置的名字能读回，过长时截断；fork 继承名字，exec 重置为程序名
+#[no_mangle]
+pub fn main() -> i32 {
+    if lseek(MARKER_FD, 0, SEEK_CUR) >= 0 {
+        // 被 exec 重新执行，名字是程序文件名
+        return if name_is(&name(), b"prctl") { 0 } else { 1 };
+    }
+    assert_eq!(set_name("worker\0"), 0);
+    assert!(name_is(&name(), b"worker"));
+    assert_eq!(set_name("a_name_longer_than_sixteen\0"), 0);
+    assert!(name_is(&name(), b"a_name_longer_t"));
+    assert_eq!(set_name("worker\0"), 0);
+    let pid = fork();
+    if pid == 0 {
+        assert!(name_is(&name(), b"worker"));
+        let marker = memfd_create("prctl\0");
+        assert!(marker >= 0);
+        assert_eq!(dup3(marker as usize, MARKER_FD, OpenFlags::empty()), MARKER_FD as isize);
+        exec("prctl\0");
+        exit(2);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // 父进程的名字不受子进程 exec 影响；未知的选项
+    assert!(name_is(&name(), b"worker"));
+    assert_eq!(prctl(0, 0), -1);
+    println!("Test prctl OK!");
+    0
+}
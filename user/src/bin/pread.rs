@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, pipe, pread, pwrite, unlink, write, OpenFlags, SEEK_CUR};
+
+/// pread/pwrite 在给定偏移处读写，不移动文件偏移
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("pread_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"0123456789"), 10);
+    assert_eq!(pwrite(fd, b"ab", 4), 2);
+    let mut buf = [0u8; 4];
+    assert_eq!(pread(fd, &mut buf, 3), 4);
+    assert_eq!(&buf, b"3ab6");
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 10);
+    // 越过文件末尾读不到数据
+    assert_eq!(pread(fd, &mut buf, 20), 0);
+    assert_eq!(pread(fd, &mut buf, -1), -1);
+    close(fd);
+    // 管道没有偏移的概念
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(pwrite(fds[1], b"x", 0), -1);
+    close(fds[0]);
+    close(fds[1]);
+    assert_eq!(unlink("pread_file\0"), 0);
+    println!("Test pread OK!");
+    0
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use user_lib::{close, getpid, getppid, open, read, OpenFlags};
+
+/// 读取 /proc/self/status，内容反映当前任务的状态
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/proc/self/status\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut buf = [0u8; 512];
+    let len = read(fd as usize, &mut buf);
+    assert!(len > 0);
+    let text = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    assert!(text.contains(&format!("\nPid:\t{}\n", getpid())));
+    assert!(text.contains(&format!("\nPPid:\t{}\n", getppid())));
+    assert!(text.contains("\nState:\tR (running)\n"));
+    // 读到末尾后返回 0
+    assert_eq!(read(fd as usize, &mut buf), 0);
+    close(fd as usize);
+    // 按 pid 打开同一个文件
+    let path = format!("/proc/{}/status\0", getpid());
+    let fd = open(&path, OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    close(fd as usize);
+    assert_eq!(open("/proc/self/missing\0", OpenFlags::RDONLY), -1);
+    assert_eq!(open("/proc/self/status\0", OpenFlags::WRONLY), -1);
+    println!("Test proc_status OK!");
+    0
+}
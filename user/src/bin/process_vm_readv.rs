@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use core::ptr::addr_of_mut;
+
+use user_lib::{close, exit, fork, getpid, pipe, process_vm_readv, read, waitpid, write};
+
+/// 跨越页边界的缓冲区
+static mut DATA: [u8; 8192] = [0; 8192];
+
+/// 父进程读到的是子进程地址空间中的内容，而不是自己的副本
+#[no_mangle]
+pub fn main() -> i32 {
+    let data = addr_of_mut!(DATA) as *mut u8;
+    let (mut ready, mut done) = ([0usize; 2], [0usize; 2]);
+    assert_eq!(pipe(&mut ready), 0);
+    assert_eq!(pipe(&mut done), 0);
+    let pid = fork();
+    if pid == 0 {
+        for i in 0..8192 {
+            unsafe { data.add(i).write((i % 253) as u8) };
+        }
+        assert_eq!(write(ready[1], b"r"), 1);
+        // 等父进程读完再退出
+        let mut byte = [0u8];
+        assert_eq!(read(done[0], &mut byte), 1);
+        exit(0);
+    }
+    let mut byte = [0u8];
+    assert_eq!(read(ready[0], &mut byte), 1);
+    let mut buf = vec![0u8; 5000];
+    assert_eq!(process_vm_readv(pid as usize, &mut buf, data as usize + 3000), 5000);
+    assert!(buf.iter().enumerate().all(|(i, b)| *b == ((i + 3000) % 253) as u8));
+    assert_eq!(unsafe { data.add(3001).read() }, 0);
+    // 只能读取自己的子进程
+    assert_eq!(process_vm_readv(getpid() as usize, &mut buf, data as usize), -1);
+    assert_eq!(write(done[1], b"d"), 1);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    for fd in ready.into_iter().chain(done) {
+        close(fd);
+    }
+    println!("Test process_vm_readv OK!");
+    0
+}
@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use user_lib::{
+    close, fadvise, lseek, open, pipe, read, readahead, unlink, write, OpenFlags,
+    POSIX_FADV_DONTNEED, SEEK_CUR,
+};
+
+/// 预读不移动文件偏移，之后读到的内容与写入的一致
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("readahead_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let data: Vec<u8> = (0..8192).map(|i| (i % 253) as u8).collect();
+    assert_eq!(write(fd, &data), data.len() as isize);
+    close(fd);
+    let fd = open("readahead_file\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    // 先把块从缓存中丢掉，再预读回来
+    assert_eq!(fadvise(fd, 0, 0, POSIX_FADV_DONTNEED), 0);
+    assert_eq!(readahead(fd, 1024, 4096), 0);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 0);
+    let mut buf = vec![0u8; data.len()];
+    assert_eq!(read(fd, &mut buf), data.len() as isize);
+    assert_eq!(buf, data);
+    // 超出文件末尾的部分忽略
+    assert_eq!(readahead(fd, 4096, 1 << 20), 0);
+    assert_eq!(readahead(fd, 1 << 20, 4096), 0);
+    close(fd);
+    // 管道没有磁盘块；无效的 fd
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(readahead(fds[0], 0, 4096), -1);
+    close(fds[0]);
+    close(fds[1]);
+    assert_eq!(readahead(fd, 0, 4096), -1);
+    assert_eq!(unlink("readahead_file\0"), 0);
+    println!("Test readahead OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{access, close, mkdir, open, read, rename, rmdir, unlink, write, AccessFlags, OpenFlags};
+
+/// rename 在目录之间移动文件，文件内容随之移动
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("rename_dir\0", 0o755), 0);
+    let fd = open("rename_a\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"moved"), 5);
+    close(fd as usize);
+    assert_eq!(rename("rename_a\0", "rename_dir/rename_b\0"), 0);
+    assert_eq!(access("rename_a\0", AccessFlags::empty()), -1);
+    let fd = open("rename_dir/rename_b\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut buf = [0u8; 8];
+    assert_eq!(read(fd as usize, &mut buf), 5);
+    assert_eq!(&buf[..5], b"moved");
+    close(fd as usize);
+    assert_eq!(rename("rename_missing\0", "rename_c\0"), -1);
+    assert_eq!(rename("rename_dir/rename_b\0", "rename_nodir/rename_b\0"), -1);
+    assert_eq!(unlink("rename_dir/rename_b\0"), 0);
+    assert_eq!(rmdir("rename_dir\0"), 0);
+    println!("Test rename OK!");
+    0
+}
@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, getrlimit, memfd_create, setrlimit, RLimit, RLIMIT_NOFILE};
+
+/// 降低 RLIMIT_NOFILE 后，分配 fd 超过上限时失败，关闭一个后又能分配
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut limit = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_NOFILE, &mut limit), 0);
+    assert!(limit.rlim_cur <= limit.rlim_max);
+    // 硬上限不能修改
+    let raised = RLimit {
+        rlim_cur: limit.rlim_cur,
+        rlim_max: limit.rlim_max + 1,
+    };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &raised), -1);
+    let lowered = RLimit {
+        rlim_cur: 5,
+        rlim_max: limit.rlim_max,
+    };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &lowered), 0);
+    // 0、1、2 已被标准输入输出占用
+    assert_eq!(memfd_create("a\0"), 3);
+    assert_eq!(memfd_create("b\0"), 4);
+    assert_eq!(memfd_create("c\0"), -1);
+    close(3);
+    assert_eq!(memfd_create("c\0"), 3);
+    println!("Test rlimit_nofile OK!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+
+use user_lib::{
+    close, lseek, memfd_create, open, read, sendfile, unlink, write, OpenFlags, SEEK_CUR, SEEK_SET,
+};
+
+const LEN: usize = 5000;
+
+/// sendfile 在内核中复制文件内容，给出偏移量时不移动源文件的偏移
+#[no_mangle]
+pub fn main() -> i32 {
+    let src = open("sendfile_src\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(src >= 0);
+    let src = src as usize;
+    // 两个缓冲区放在栈上会超出用户栈
+    let mut data = vec![0u8; LEN];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    assert_eq!(write(src, &data), LEN as isize);
+    let dst = memfd_create("sendfile_dst\0");
+    assert!(dst >= 0);
+    let dst = dst as usize;
+    // 跨过一页的缓冲区，一直复制到文件末尾
+    let mut offset = 100;
+    assert_eq!(sendfile(dst, src, Some(&mut offset), 2 * LEN), (LEN - 100) as isize);
+    assert_eq!(offset, LEN as isize);
+    assert_eq!(lseek(src, 0, SEEK_CUR), LEN as isize);
+    assert_eq!(lseek(dst, 0, SEEK_SET), 0);
+    let mut buf = vec![0u8; LEN];
+    assert_eq!(read(dst, &mut buf), (LEN - 100) as isize);
+    assert_eq!(&buf[..LEN - 100], &data[100..]);
+    // 不给偏移量时从源文件的当前偏移读，并移动它
+    assert_eq!(lseek(src, 0, SEEK_SET), 0);
+    assert_eq!(sendfile(dst, src, None, 10), 10);
+    assert_eq!(lseek(src, 0, SEEK_CUR), 10);
+    assert_eq!(sendfile(dst, 99, None, 10), -1);
+    close(dst);
+    close(src);
+    assert_eq!(unlink("sendfile_src\0"), 0);
+    println!("Test sendfile OK!");
+    0
+}
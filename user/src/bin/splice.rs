@@ -0,0 +1,99 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use user_lib::{
+    close, exit, fork, lseek, open, pipe, pread, read, splice, unlink, waitpid, write, OpenFlags,
+    SEEK_CUR,
+};
+
+const LEN: usize = 1000;
+
+/// 管道缓冲区远小于搬运的数据，一端反复 splice 直到搬完，另一端普通读写
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("splice_file\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let data: Vec<u8> = (0..LEN).map(|i| (i % 247) as u8).collect();
+    assert_eq!(write(fd, &data), LEN as isize);
+
+    // 文件 -> 管道，给出偏移量时文件偏移不动
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        let mut offset = 100;
+        while (offset as usize) < LEN {
+            let moved = splice(fd, Some(&mut offset), fds[1], None, LEN, 0);
+            assert!(moved > 0);
+        }
+        assert_eq!(splice(fd, Some(&mut offset), fds[1], None, LEN, 0), 0);
+        assert_eq!(lseek(fd, 0, SEEK_CUR), LEN as isize);
+        close(fds[1]);
+        exit(0);
+    }
+    close(fds[1]);
+    let mut buf = vec![0u8; LEN];
+    let mut got = 0;
+    loop {
+        let n = read(fds[0], &mut buf[got..]);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        got += n as usize;
+    }
+    assert_eq!(&buf[..got], &data[100..]);
+    close(fds[0]);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // 管道 -> 文件，不给偏移量时写在文件偏移处并移动它
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        assert_eq!(write(fds[1], &data), LEN as isize);
+        close(fds[1]);
+        exit(0);
+    }
+    close(fds[1]);
+    let mut moved = 0;
+    loop {
+        let n = splice(fds[0], None, fd, None, LEN, 0);
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        moved += n as usize;
+    }
+    assert_eq!(moved, LEN);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 2 * LEN as isize);
+    assert_eq!(pread(fd, &mut buf, LEN as isize), LEN as isize);
+    assert_eq!(buf, data);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // 管道一端不能给偏移量；两端都是管道
+    let mut offset = 0;
+    assert_eq!(splice(fds[0], Some(&mut offset), fd, None, 10, 0), -1);
+    let mut other = [0usize; 2];
+    assert_eq!(pipe(&mut other), 0);
+    assert_eq!(splice(fds[0], None, other[1], None, 10, 0), -1);
+    close(other[0]);
+    close(other[1]);
+    close(fds[0]);
+    close(fd);
+    assert_eq!(unlink("splice_file\0"), 0);
+    println!("Test splice OK!");
+    0
+}
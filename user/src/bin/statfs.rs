@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstatfs, open, pipe, statfs, unlink, write, OpenFlags, StatFs};
+
+/// 创建并写入文件后空闲块和空闲 inode 减少，删除后空闲块回升
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut before = StatFs::default();
+    assert_eq!(statfs("/\0", &mut before), 0);
+    assert!(before.bsize > 0);
+    assert!(before.bfree <= before.blocks && before.ffree <= before.files);
+    let fd = open("statfs_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let data = [b'x'; 2048];
+    assert_eq!(write(fd, &data), data.len() as isize);
+    let mut during = StatFs::default();
+    assert_eq!(fstatfs(fd, &mut during), 0);
+    assert_eq!(during.blocks, before.blocks);
+    assert_eq!(during.ffree, before.ffree - 1);
+    assert!(during.bfree + data.len() as u64 / before.bsize <= before.bfree);
+    close(fd);
+    assert_eq!(unlink("statfs_file\0"), 0);
+    let mut after = StatFs::default();
+    assert_eq!(statfs("/\0", &mut after), 0);
+    assert_eq!(after.ffree, before.ffree);
+    assert!(after.bfree >= during.bfree + data.len() as u64 / before.bsize);
+    // 不存在的路径；管道不属于任何文件系统
+    assert_eq!(statfs("statfs_missing\0", &mut after), -1);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(fstatfs(fds[0], &mut after), -1);
+    close(fds[0]);
+    close(fds[1]);
+    println!("Test statfs OK!");
+    0
+}
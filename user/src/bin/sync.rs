@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, sync, unlink, write, OpenFlags};
+
+/// sync 写回所有文件系统，之后数据仍可正常读出
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("sync_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    let data = [b's'; 1500];
+    assert_eq!(write(fd as usize, &data), data.len() as isize);
+    close(fd as usize);
+    assert_eq!(sync(), 0);
+    // 没有脏块时也成功
+    assert_eq!(sync(), 0);
+    let fd = open("sync_file\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut buf = [0u8; 1500];
+    assert_eq!(read(fd as usize, &mut buf), data.len() as isize);
+    assert_eq!(buf, data);
+    close(fd as usize);
+    assert_eq!(unlink("sync_file\0"), 0);
+    println!("Test sync OK!");
+    0
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, memfd_create, pipe, read, tee, write, SPLICE_F_NONBLOCK};
+
+/// tee 复制的数据在两个管道中都能读到
+#[no_mangle]
+pub fn main() -> i32 {
+    let (mut a, mut b) = ([0usize; 2], [0usize; 2]);
+    assert_eq!(pipe(&mut a), 0);
+    assert_eq!(pipe(&mut b), 0);
+    assert_eq!(write(a[1], b"hello tee"), 9);
+    // 只复制前 5 个字节
+    assert_eq!(tee(a[0], b[1], 5, 0), 5);
+    assert_eq!(tee(a[0], b[1], 100, 0), 9);
+    let mut buf = [0u8; 32];
+    assert_eq!(read(b[0], &mut buf), 14);
+    assert_eq!(&buf[..14], b"hellohello tee");
+    assert_eq!(read(a[0], &mut buf), 9);
+    assert_eq!(&buf[..9], b"hello tee");
+    // 输入为空时非阻塞返回 EAGAIN
+    assert_eq!(tee(a[0], b[1], 100, SPLICE_F_NONBLOCK), -1);
+    // 同一个管道；不是管道；方向反了
+    assert_eq!(tee(a[0], a[1], 100, 0), -1);
+    let memfd = memfd_create("tee\0");
+    assert!(memfd >= 0);
+    assert_eq!(tee(a[0], memfd as usize, 100, 0), -1);
+    assert_eq!(tee(b[1], a[0], 100, 0), -1);
+    close(memfd as usize);
+    // 写端都关闭且没有数据时返回 0
+    close(a[1]);
+    assert_eq!(tee(a[0], b[1], 100, 0), 0);
+    close(a[0]);
+    close(b[0]);
+    close(b[1]);
+    println!("Test tee OK!");
+    0
+}
@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, open, truncate, unlink, write, OpenFlags, Stat};
+
+/// 按路径截断文件，不存在的路径和目录都失败
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("truncate_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, &[b'x'; 64]), 64);
+    assert_eq!(truncate("truncate_file\0", 16), 0);
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.size, 16);
+    assert_eq!(truncate("truncate_missing\0", 0), -1);
+    assert_eq!(truncate("/\0", 0), -1);
+    close(fd);
+    assert_eq!(unlink("truncate_file\0"), 0);
+    println!("Test truncate OK!");
+    0
+}
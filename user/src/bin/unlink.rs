@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{access, close, mkdir, open, rmdir, statfs, unlink, write, AccessFlags, OpenFlags, StatFs};
+
+fn free_blocks() -> u64 {
+    let mut stat = StatFs::default();
+    assert_eq!(statfs("/\0", &mut stat), 0);
+    stat.bfree
+}
+
+/// 删除最后一个链接后路径不再存在，数据块全部归还
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("unlink_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    let before = free_blocks();
+    let data = [b'u'; 4096];
+    assert_eq!(write(fd as usize, &data), data.len() as isize);
+    close(fd as usize);
+    assert!(free_blocks() < before);
+    assert_eq!(unlink("unlink_file\0"), 0);
+    assert_eq!(access("unlink_file\0", AccessFlags::empty()), -1);
+    assert!(open("unlink_file\0", OpenFlags::RDONLY) < 0);
+    assert_eq!(free_blocks(), before);
+    // 不存在的路径；目录要用 rmdir
+    assert_eq!(unlink("unlink_file\0"), -1);
+    assert_eq!(mkdir("unlink_dir\0", 0o755), 0);
+    assert_eq!(unlink("unlink_dir\0"), -1);
+    assert_eq!(rmdir("unlink_dir\0"), 0);
+    println!("Test unlink OK!");
+    0
+}
@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{spawn, waitpid};
+
+/// 依次运行的测试程序，名字以 \0 结尾
+static TESTS: &[&str] = &[
+    "getppid\0",
+    "wait4\0",
+    "pipe_large\0",
+    "memfd\0",
+    "ftruncate\0",
+    "truncate\0",
+    "access\0",
+    "chmod\0",
+    "dup3\0",
+    "sendfile\0",
+    "pread\0",
+    "mount\0",
+    "fchdir\0",
+    "rename\0",
+    "mkdir_rmdir\0",
+    "proc_status\0",
+    "clock_gettime\0",
+    "poll\0",
+    "madvise\0",
+    "statfs\0",
+    "chown\0",
+    "link\0",
+    "unlink\0",
+    "sync\0",
+    "utimensat\0",
+    "fadvise\0",
+    "process_vm_readv\0",
+    "cpu_quota\0",
+    "getdents64\0",
+    "affinity\0",
+    "openat\0",
+    "epoll\0",
+    "getcpu\0",
+    "mmap_file\0",
+    "msync\0",
+    "clone\0",
+    "fallocate\0",
+    "readahead\0",
+    "tee\0",
+    "pipe_peek\0",
+    "splice\0",
+    "vhangup\0",
+    "flock\0",
+    "chroot\0",
+    "prctl\0",
+    "getdents_unlink\0",
+    "orphan\0",
+    "rlimit_nofile\0",
+];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut passed = 0;
+    for test in TESTS {
+        println!("Usertests: Running {}", test);
+        let pid = spawn(test);
+        let mut exit_code: i32 = Default::default();
+        let wait_pid = waitpid(pid as usize, &mut exit_code);
+        if pid >= 0 && wait_pid == pid && exit_code == 0 {
+            passed += 1;
+        } else {
+            println!("Usertests: Test {} failed, exit code {}", test, exit_code);
+        }
+    }
+    println!("Usertests: {}/{} passed", passed, TESTS.len());
+    if passed == TESTS.len() {
+        println!("Usertests passed!");
+        0
+    } else {
+        -1
+    }
+}
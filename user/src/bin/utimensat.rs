@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, open, unlink, utimensat, OpenFlags, Stat, TimeVal, UTIME_OMIT};
+
+fn times_of(fd: usize) -> (u64, u64) {
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    (stat.atime, stat.mtime)
+}
+
+/// 显式设置的时间原样由 fstat 报告，UTIME_OMIT 的字段保持不变
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("utimensat_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let times = [TimeVal { sec: 1000, usec: 0 }, TimeVal { sec: 123_456, usec: 0 }];
+    assert_eq!(utimensat("utimensat_file\0", Some(&times)), 0);
+    assert_eq!(times_of(fd), (1000, 123_456));
+    let times = [TimeVal { sec: 0, usec: UTIME_OMIT }, TimeVal { sec: 654_321, usec: 0 }];
+    assert_eq!(utimensat("utimensat_file\0", Some(&times)), 0);
+    assert_eq!(times_of(fd), (1000, 654_321));
+    // 不设时间时两者都取当前时间（从开机起计）
+    assert_eq!(utimensat("utimensat_file\0", None), 0);
+    let (atime, mtime) = times_of(fd);
+    assert_eq!(atime, mtime);
+    assert!(mtime < 1000);
+    let times = [TimeVal { sec: 0, usec: 1_000_000 }, TimeVal { sec: 0, usec: 0 }];
+    assert_eq!(utimensat("utimensat_file\0", Some(&times)), -1);
+    assert_eq!(utimensat("utimensat_missing\0", None), -1);
+    close(fd);
+    assert_eq!(unlink("utimensat_file\0"), 0);
+    println!("Test utimensat OK!");
+    0
+}
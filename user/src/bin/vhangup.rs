@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup3, vhangup, OpenFlags};
+
+/// vhangup 只丢弃未读的输入，标准输入在哪个 fd 上都照常打开
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(vhangup(), 0);
+    // 标准输入移到别的 fd 上也能找到
+    assert_eq!(dup3(0, 10, OpenFlags::empty()), 10);
+    assert_eq!(close(0), 0);
+    assert_eq!(vhangup(), 0);
+    assert_eq!(dup3(10, 0, OpenFlags::empty()), 0);
+    // 重置后标准输入仍然打开
+    assert_eq!(close(10), 0);
+    assert_eq!(dup3(0, 10, OpenFlags::empty()), 10);
+    // 没有打开标准输入时什么都不做
+    assert_eq!(close(0), 0);
+    assert_eq!(close(10), 0);
+    assert_eq!(vhangup(), 0);
+    println!("Test vhangup OK!");
+    0
+}
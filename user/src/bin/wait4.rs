@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, getpid, wait4, yield_, RUsage, WNOHANG};
+
+/// wait4 阻塞到子进程退出，并带回它的资源统计
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        for _ in 0..10 {
+            getpid();
+            yield_();
+        }
+        exit(7);
+    }
+    let mut exit_code = 0;
+    let mut rusage = RUsage::default();
+    // 子进程还在运行时 WNOHANG 立即返回 -2
+    assert_eq!(wait4(pid, &mut exit_code, WNOHANG, &mut rusage), -2);
+    assert_eq!(wait4(pid, &mut exit_code, 0, &mut rusage), pid);
+    assert_eq!(exit_code, 7);
+    assert!(rusage.syscall_count >= 10);
+    assert!(rusage.max_rss > 0);
+    // 已经没有子进程了
+    assert_eq!(wait4(-1, &mut exit_code, 0, &mut rusage), -1);
+    println!("Test wait4 OK!");
+    0
+}
@@ -0,0 +1,8 @@
+use super::exit;
+
+#[panic_handler]
+fn panic_handler(info: &core::panic::PanicInfo) -> ! {
+    // 测试中失败的断言从这里打印出来，并以非零退出码结束
+    println!("Panicked: {}", info);
+    exit(-1);
+}
@@ -0,0 +1,648 @@
+//! User library of the kernel tests: syscall wrappers, console output and a heap
+#![no_std]
+#![feature(linkage)]
+
+#[macro_use]
+pub mod console;
+mod lang_items;
+mod syscall;
+
+extern crate alloc;
+
+use bitflags::bitflags;
+use buddy_system_allocator::LockedHeap;
+use syscall::*;
+
+const USER_HEAP_SIZE: usize = 32768;
+
+static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
+
+#[global_allocator]
+static HEAP: LockedHeap = LockedHeap::empty();
+
+#[no_mangle]
+#[link_section = ".text.entry"]
+pub extern "C" fn _start() -> ! {
+    unsafe {
+        HEAP.lock()
+            .init(core::ptr::addr_of_mut!(HEAP_SPACE) as usize, USER_HEAP_SIZE);
+    }
+    exit(main());
+}
+
+#[linkage = "weak"]
+#[no_mangle]
+fn main() -> i32 {
+    panic!("Cannot find main!");
+}
+
+/// dirfd meaning the current working directory
+pub const AT_FDCWD: isize = -100;
+/// unlinkat() flag: remove a directory instead of a file
+pub const AT_REMOVEDIR: usize = 0x200;
+
+bitflags! {
+    /// The flags argument of open()
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const EXCL = 1 << 7;
+        const CREATE = 1 << 9;
+        const TRUNC = 1 << 10;
+        const CLOEXEC = 1 << 19;
+    }
+}
+
+bitflags! {
+    /// The file type and permission bits in [`Stat`]
+    pub struct StatMode: u32 {
+        const NULL  = 0;
+        const DIR   = 0o040000;
+        const FILE  = 0o100000;
+        const PERM  = 0o777;
+    }
+}
+
+/// File status filled by fstat()
+#[repr(C)]
+#[derive(Debug)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: StatMode,
+    pub nlink: u32,
+    pub size: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub atime: u64,
+    pub mtime: u64,
+    pad: [u64; 3],
+}
+
+impl Stat {
+    pub fn new() -> Self {
+        Stat {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::NULL,
+            nlink: 0,
+            size: 0,
+            uid: 0,
+            gid: 0,
+            atime: 0,
+            mtime: 0,
+            pad: [0; 3],
+        }
+    }
+}
+
+impl Default for Stat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+bitflags! {
+    /// The mode argument of access(), F_OK is the empty set
+    pub struct AccessFlags: u32 {
+        const X_OK = 1 << 0;
+        const W_OK = 1 << 1;
+        const R_OK = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// The flags argument of mount()
+    pub struct MountFlags: u32 {
+        /// mount as a read-only lower layer under the target directory
+        const OVERLAY = 1 << 0;
+    }
+}
+
+/// lseek() whence: from the start of the file
+pub const SEEK_SET: usize = 0;
+/// lseek() whence: from the current offset
+pub const SEEK_CUR: usize = 1;
+/// lseek() whence: from the end of the file
+pub const SEEK_END: usize = 2;
+
+/// wait4() option: return -2 at once if the child is still running
+pub const WNOHANG: usize = 1;
+
+/// Resource usage of a reaped child, filled by wait4()
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct RUsage {
+    /// Total running time in microseconds
+    pub cpu_time: usize,
+    /// Peak number of resident pages
+    pub max_rss: usize,
+    /// Total number of syscalls
+    pub syscall_count: usize,
+}
+
+/// Resource limit, see getrlimit(2)
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct RLimit {
+    /// Soft limit, enforced by the kernel
+    pub rlim_cur: usize,
+    /// Hard limit, ceiling of the soft limit
+    pub rlim_max: usize,
+}
+
+/// Limit on the CPU time in seconds
+pub const RLIMIT_CPU: usize = 0;
+/// Limit on the number of open files
+pub const RLIMIT_NOFILE: usize = 7;
+/// No limit
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+/// Time value
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    /// The time in microseconds
+    pub fn as_us(&self) -> usize {
+        self.sec * 1_000_000 + self.usec
+    }
+}
+
+/// getdents64() d_type: unknown
+pub const DT_UNKNOWN: u8 = 0;
+/// getdents64() d_type: directory
+pub const DT_DIR: u8 = 4;
+/// getdents64() d_type: regular file
+pub const DT_REG: u8 = 8;
+
+/// fadvise() advice: the data will be accessed sequentially
+pub const POSIX_FADV_SEQUENTIAL: usize = 2;
+/// fadvise() advice: the data will not be accessed soon
+pub const POSIX_FADV_DONTNEED: usize = 4;
+
+/// utimensat() time: set the time to the current time
+pub const UTIME_NOW: usize = (1 << 30) - 1;
+/// utimensat() time: leave the time unchanged
+pub const UTIME_OMIT: usize = (1 << 30) - 2;
+
+/// wall-clock time, counted from boot
+pub const CLOCK_REALTIME: usize = 0;
+/// time since boot, never goes backward
+pub const CLOCK_MONOTONIC: usize = 1;
+/// cpu time consumed by the calling task
+pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+
+/// poll() event: there is data to read
+pub const POLLIN: i16 = 0x1;
+/// poll() event: writing now will not block
+pub const POLLOUT: i16 = 0x4;
+/// poll() event: the fd is not open
+pub const POLLNVAL: i16 = 0x20;
+
+/// One fd watched by poll()
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+/// The size and usage of a filesystem
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StatFs {
+    pub bsize: u64,
+    pub blocks: u64,
+    pub bfree: u64,
+    pub files: u64,
+    pub ffree: u64,
+    pub maxfilesize: u64,
+}
+
+/// epoll event: there is data to read
+pub const EPOLLIN: u32 = 0x1;
+/// epoll event: writing now will not block
+pub const EPOLLOUT: u32 = 0x4;
+/// epoll_ctl() op: register a new fd
+pub const EPOLL_CTL_ADD: usize = 1;
+/// epoll_ctl() op: stop watching an fd
+pub const EPOLL_CTL_DEL: usize = 2;
+
+/// One event reported by epoll_wait()
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+/// flock() operation: take a shared lock
+pub const LOCK_SH: usize = 1;
+/// flock() operation: take an exclusive lock
+pub const LOCK_EX: usize = 2;
+/// flock() flag: fail with -EWOULDBLOCK instead of waiting
+pub const LOCK_NB: usize = 4;
+/// flock() operation: drop the lock
+pub const LOCK_UN: usize = 8;
+/// flock() result with LOCK_NB while another open holds a conflicting lock
+pub const EWOULDBLOCK: isize = -11;
+
+/// prctl() option: set the name of the task to a NUL terminated string
+pub const PR_SET_NAME: usize = 15;
+/// prctl() option: get the name of the task into a TASK_COMM_LEN buffer
+pub const PR_GET_NAME: usize = 16;
+/// Size of a task name buffer, including the terminating NUL
+pub const TASK_COMM_LEN: usize = 16;
+
+/// tee()/splice() flag: fail instead of waiting for data or room
+pub const SPLICE_F_NONBLOCK: usize = 2;
+
+/// recv() flag: return the buffered bytes without consuming them
+pub const MSG_PEEK: usize = 2;
+
+/// mmap() protection: readable
+pub const PROT_READ: usize = 0x1;
+/// mmap() protection: writable
+pub const PROT_WRITE: usize = 0x2;
+/// mmap() flag: writes reach the file
+pub const MAP_SHARED: usize = 0x1;
+/// mmap() flag: writes stay private to the mapping
+pub const MAP_PRIVATE: usize = 0x2;
+/// mmap() flag: map zeroed memory instead of a file
+pub const MAP_ANONYMOUS: usize = 0x20;
+/// msync() flag: schedule the write back and return at once
+pub const MS_ASYNC: usize = 1;
+/// msync() flag: return once the write back is done
+pub const MS_SYNC: usize = 4;
+/// madvise() advice: the pages will be accessed soon
+pub const MADV_WILLNEED: usize = 3;
+/// madvise() advice: the contents of the pages are no longer needed
+pub const MADV_DONTNEED: usize = 4;
+
+/// clone() flag: share the address space
+pub const CLONE_VM: usize = 0x100;
+/// clone() flag: share the fd table
+pub const CLONE_FILES: usize = 0x400;
+/// clone() flag: suspend the caller until the child execs or exits
+pub const CLONE_VFORK: usize = 0x4000;
+/// clone() flag: put the child in the thread group of the caller
+pub const CLONE_THREAD: usize = 0x10000;
+
+/// waitpid() result while the child is still running
+const CHILD_RUNNING: isize = -2;
+
+pub fn exit(exit_code: i32) -> ! {
+    syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0, 0, 0, 0]);
+    unreachable!()
+}
+pub fn yield_() -> isize {
+    syscall(SYSCALL_YIELD, [0; 6])
+}
+pub fn getpid() -> isize {
+    syscall(SYSCALL_GETPID, [0; 6])
+}
+pub fn getppid() -> isize {
+    syscall(SYSCALL_GETPPID, [0; 6])
+}
+/// Apply prctl() `option`, PR_SET_NAME or PR_GET_NAME, to the current task
+pub fn prctl(option: usize, arg2: usize) -> isize {
+    syscall(SYSCALL_PRCTL, [option, arg2, 0, 0, 0, 0])
+}
+pub fn gettid() -> isize {
+    syscall(SYSCALL_GETTID, [0; 6])
+}
+/// Set the CPUs task `pid` may run on, 0 for current task
+pub fn sched_setaffinity(pid: usize, mask: usize) -> isize {
+    syscall(SYSCALL_SCHED_SETAFFINITY, [pid, mask, 0, 0, 0, 0])
+}
+/// Get the CPUs task `pid` may run on, 0 for current task
+pub fn sched_getaffinity(pid: usize) -> isize {
+    syscall(SYSCALL_SCHED_GETAFFINITY, [pid, 0, 0, 0, 0, 0])
+}
+pub fn fork() -> isize {
+    syscall(SYSCALL_CLONE, [0; 6])
+}
+/// Create a child sharing what `flags` select, running `entry(arg)` on
+/// `stack` (0 for a copy of the current stack) and exiting with its result
+pub fn clone(flags: usize, stack: usize, entry: extern "C" fn(usize) -> i32, arg: usize) -> isize {
+    syscall_clone(flags, stack, entry, arg)
+}
+/// `path` must end with \0
+pub fn exec(path: &str) -> isize {
+    syscall(SYSCALL_EXEC, [path.as_ptr() as usize, 0, 0, 0, 0, 0])
+}
+/// `path` must end with \0
+pub fn spawn(path: &str) -> isize {
+    syscall(SYSCALL_SPAWN, [path.as_ptr() as usize, 0, 0, 0, 0, 0])
+}
+/// Wait for the child `pid` to exit, storing its exit code
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+    loop {
+        match syscall(SYSCALL_WAITPID, [pid, exit_code as *mut _ as usize, 0, 0, 0, 0]) {
+            CHILD_RUNNING => {
+                yield_();
+            }
+            exit_pid => return exit_pid,
+        }
+    }
+}
+/// Wait for any child to exit, storing its exit code. -1 if there is none
+pub fn wait(exit_code: &mut i32) -> isize {
+    waitpid(usize::MAX, exit_code)
+}
+/// Wait for the child `pid` (-1 for any) like waitpid(), also filling
+/// `rusage` with its accounting. Blocks unless `options` has WNOHANG
+/// Copy `buf.len()` bytes at `remote_addr` in the address space of child
+/// `pid` into `buf`
+pub fn process_vm_readv(pid: usize, buf: &mut [u8], remote_addr: usize) -> isize {
+    syscall(
+        SYSCALL_PROCESS_VM_READV,
+        [pid, buf.as_mut_ptr() as usize, remote_addr, buf.len(), 0, 0],
+    )
+}
+pub fn wait4(pid: isize, exit_code: &mut i32, options: usize, rusage: &mut RUsage) -> isize {
+    syscall(
+        SYSCALL_WAIT4,
+        [
+            pid as usize,
+            exit_code as *mut _ as usize,
+            options,
+            rusage as *mut _ as usize,
+            0,
+            0,
+        ],
+    )
+}
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    syscall(SYSCALL_GETRLIMIT, [resource, rlim as *mut _ as usize, 0, 0, 0, 0])
+}
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    syscall(SYSCALL_SETRLIMIT, [resource, rlim as *const _ as usize, 0, 0, 0, 0])
+}
+pub fn clock_gettime(clock_id: usize, ts: &mut TimeVal) -> isize {
+    syscall(SYSCALL_CLOCK_GETTIME, [clock_id, ts as *mut _ as usize, 0, 0, 0, 0])
+}
+pub fn read(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_READ, [fd, buf.as_mut_ptr() as usize, buf.len(), 0, 0, 0])
+}
+pub fn write(fd: usize, buf: &[u8]) -> isize {
+    syscall(SYSCALL_WRITE, [fd, buf.as_ptr() as usize, buf.len(), 0, 0, 0])
+}
+/// `path` must end with \0
+pub fn open(path: &str, flags: OpenFlags) -> isize {
+    syscall(
+        SYSCALL_OPEN,
+        [AT_FDCWD as usize, path.as_ptr() as usize, flags.bits as usize, 0, 0, 0],
+    )
+}
+/// Open `path` relative to the directory `dirfd`, or the cwd if it is
+/// AT_FDCWD. `path` must end with \0
+pub fn openat(dirfd: isize, path: &str, flags: OpenFlags) -> isize {
+    syscall(
+        SYSCALL_OPEN,
+        [dirfd as usize, path.as_ptr() as usize, flags.bits as usize, 0, 0, 0],
+    )
+}
+/// `name` must end with \0
+pub fn memfd_create(name: &str) -> isize {
+    syscall(SYSCALL_MEMFD_CREATE, [name.as_ptr() as usize, 0, 0, 0, 0, 0])
+}
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence, 0, 0, 0])
+}
+pub fn fstat(fd: usize, st: &mut Stat) -> isize {
+    syscall(SYSCALL_FSTAT, [fd, st as *mut _ as usize, 0, 0, 0, 0])
+}
+/// Set the access and modification time of `path`, both to now if `times`
+/// is None. `path` must end with \0
+pub fn utimensat(path: &str, times: Option<&[TimeVal; 2]>) -> isize {
+    let times = times.map_or(0, |times| times.as_ptr() as usize);
+    syscall(
+        SYSCALL_UTIMENSAT,
+        [AT_FDCWD as usize, path.as_ptr() as usize, times, 0, 0, 0],
+    )
+}
+/// Fill `buf` with `linux_dirent64` records of directory `fd`
+pub fn getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETDENTS64, [fd, buf.as_mut_ptr() as usize, buf.len(), 0, 0, 0])
+}
+pub fn fadvise(fd: usize, offset: usize, len: usize, advice: usize) -> isize {
+    syscall(SYSCALL_FADVISE64, [fd, offset, len, advice, 0, 0])
+}
+/// Load [offset, offset + count) of `fd` into the block cache
+pub fn readahead(fd: usize, offset: usize, count: usize) -> isize {
+    syscall(SYSCALL_READAHEAD, [fd, offset, count, 0, 0, 0])
+}
+pub fn sync() -> isize {
+    syscall(SYSCALL_SYNC, [0; 6])
+}
+/// `path` must end with \0
+pub fn statfs(path: &str, buf: &mut StatFs) -> isize {
+    syscall(SYSCALL_STATFS, [path.as_ptr() as usize, buf as *mut _ as usize, 0, 0, 0, 0])
+}
+pub fn fstatfs(fd: usize, buf: &mut StatFs) -> isize {
+    syscall(SYSCALL_FSTATFS, [fd, buf as *mut _ as usize, 0, 0, 0, 0])
+}
+/// `path` must end with \0
+pub fn truncate(path: &str, length: isize) -> isize {
+    syscall(SYSCALL_TRUNCATE, [path.as_ptr() as usize, length as usize, 0, 0, 0, 0])
+}
+pub fn ftruncate(fd: usize, length: isize) -> isize {
+    syscall(SYSCALL_FTRUNCATE, [fd, length as usize, 0, 0, 0, 0])
+}
+/// Allocate the blocks of [offset, offset + len) of `fd`, `mode` must be 0
+pub fn fallocate(fd: usize, mode: usize, offset: isize, len: isize) -> isize {
+    syscall(SYSCALL_FALLOCATE, [fd, mode, offset as usize, len as usize, 0, 0])
+}
+/// `old_path` and `new_path` must end with \0
+pub fn link(old_path: &str, new_path: &str) -> isize {
+    syscall(
+        SYSCALL_LINKAT,
+        [
+            AT_FDCWD as usize,
+            old_path.as_ptr() as usize,
+            AT_FDCWD as usize,
+            new_path.as_ptr() as usize,
+            0,
+            0,
+        ],
+    )
+}
+/// `path` must end with \0
+pub fn unlink(path: &str) -> isize {
+    syscall(
+        SYSCALL_UNLINKAT,
+        [AT_FDCWD as usize, path.as_ptr() as usize, 0, 0, 0, 0],
+    )
+}
+/// `path` must end with \0
+pub fn access(path: &str, mode: AccessFlags) -> isize {
+    syscall(
+        SYSCALL_FACCESSAT,
+        [AT_FDCWD as usize, path.as_ptr() as usize, mode.bits as usize, 0, 0, 0],
+    )
+}
+/// `path` must end with \0
+pub fn chmod(path: &str, mode: u32) -> isize {
+    syscall(
+        SYSCALL_FCHMODAT,
+        [AT_FDCWD as usize, path.as_ptr() as usize, mode as usize, 0, 0, 0],
+    )
+}
+/// `path` must end with \0
+pub fn chown(path: &str, uid: u32, gid: u32) -> isize {
+    syscall(
+        SYSCALL_FCHOWNAT,
+        [AT_FDCWD as usize, path.as_ptr() as usize, uid as usize, gid as usize, 0, 0],
+    )
+}
+/// Take or drop the advisory lock of the open file `fd`
+pub fn flock(fd: usize, operation: usize) -> isize {
+    syscall(SYSCALL_FLOCK, [fd, operation, 0, 0, 0, 0])
+}
+/// Discard the console input typed but not read yet
+pub fn vhangup() -> isize {
+    syscall(SYSCALL_VHANGUP, [0; 6])
+}
+pub fn dup3(oldfd: usize, newfd: usize, flags: OpenFlags) -> isize {
+    syscall(SYSCALL_DUP3, [oldfd, newfd, flags.bits as usize, 0, 0, 0])
+}
+/// Copy up to `count` bytes from `in_fd` to `out_fd`, reading from and
+/// advancing `*offset` instead of the file offset of `in_fd` if given
+pub fn sendfile(out_fd: usize, in_fd: usize, offset: Option<&mut isize>, count: usize) -> isize {
+    let offset = offset.map_or(0, |offset| offset as *mut _ as usize);
+    syscall(SYSCALL_SENDFILE, [out_fd, in_fd, offset, count, 0, 0])
+}
+/// Move up to `len` bytes between a pipe and a file, the offset of the
+/// pipe end must be None
+pub fn splice(
+    fd_in: usize,
+    off_in: Option<&mut isize>,
+    fd_out: usize,
+    off_out: Option<&mut isize>,
+    len: usize,
+    flags: usize,
+) -> isize {
+    let off_in = off_in.map_or(0, |offset| offset as *mut _ as usize);
+    let off_out = off_out.map_or(0, |offset| offset as *mut _ as usize);
+    syscall(SYSCALL_SPLICE, [fd_in, off_in, fd_out, off_out, len, flags])
+}
+/// Copy up to `len` bytes buffered in the pipe `fd_in` into the pipe
+/// `fd_out`, leaving them readable from `fd_in`
+pub fn tee(fd_in: usize, fd_out: usize, len: usize, flags: usize) -> isize {
+    syscall(SYSCALL_TEE, [fd_in, fd_out, len, flags, 0, 0])
+}
+/// Read from the pipe `fd`, without consuming the bytes if `flags` has MSG_PEEK
+pub fn recv(fd: usize, buf: &mut [u8], flags: usize) -> isize {
+    syscall(SYSCALL_RECVFROM, [fd, buf.as_mut_ptr() as usize, buf.len(), flags, 0, 0])
+}
+pub fn pread(fd: usize, buf: &mut [u8], offset: isize) -> isize {
+    syscall(
+        SYSCALL_PREAD,
+        [fd, buf.as_mut_ptr() as usize, buf.len(), offset as usize, 0, 0],
+    )
+}
+pub fn pwrite(fd: usize, buf: &[u8], offset: isize) -> isize {
+    syscall(
+        SYSCALL_PWRITE,
+        [fd, buf.as_ptr() as usize, buf.len(), offset as usize, 0, 0],
+    )
+}
+/// `path` must end with \0
+pub fn mkdir(path: &str, mode: u32) -> isize {
+    syscall(
+        SYSCALL_MKDIRAT,
+        [AT_FDCWD as usize, path.as_ptr() as usize, mode as usize, 0, 0, 0],
+    )
+}
+/// `path` must end with \0
+pub fn rmdir(path: &str) -> isize {
+    syscall(
+        SYSCALL_UNLINKAT,
+        [AT_FDCWD as usize, path.as_ptr() as usize, AT_REMOVEDIR, 0, 0, 0],
+    )
+}
+/// Both paths must end with \0
+pub fn rename(old: &str, new: &str) -> isize {
+    syscall(
+        SYSCALL_RENAMEAT,
+        [
+            AT_FDCWD as usize,
+            old.as_ptr() as usize,
+            AT_FDCWD as usize,
+            new.as_ptr() as usize,
+            0,
+            0,
+        ],
+    )
+}
+/// Mount the filesystem on block device `device_id` at `target`, which
+/// must end with \0
+pub fn mount(device_id: usize, target: &str, flags: MountFlags) -> isize {
+    syscall(
+        SYSCALL_MOUNT,
+        [device_id, target.as_ptr() as usize, flags.bits as usize, 0, 0, 0],
+    )
+}
+/// `target` must end with \0
+pub fn umount(target: &str) -> isize {
+    syscall(SYSCALL_UMOUNT2, [target.as_ptr() as usize, 0, 0, 0, 0, 0])
+}
+/// `path` must end with \0
+pub fn chdir(path: &str) -> isize {
+    syscall(SYSCALL_CHDIR, [path.as_ptr() as usize, 0, 0, 0, 0, 0])
+}
+/// Make the directory `path` the root of absolute paths, `path` must end with \0
+pub fn chroot(path: &str) -> isize {
+    syscall(SYSCALL_CHROOT, [path.as_ptr() as usize, 0, 0, 0, 0, 0])
+}
+pub fn fchdir(fd: usize) -> isize {
+    syscall(SYSCALL_FCHDIR, [fd, 0, 0, 0, 0, 0])
+}
+pub fn close(fd: usize) -> isize {
+    syscall(SYSCALL_CLOSE, [fd, 0, 0, 0, 0, 0])
+}
+pub fn pipe(pipe_fd: &mut [usize; 2]) -> isize {
+    syscall(SYSCALL_PIPE, [pipe_fd.as_mut_ptr() as usize, 0, 0, 0, 0, 0])
+}
+/// Map `len` bytes of anonymous memory at `start`
+pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MMAP, [start, len, prot, 0, 0, 0])
+}
+pub fn mmap_file(start: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    syscall(SYSCALL_MMAP_FILE, [start, len, prot, flags, fd, offset])
+}
+pub fn msync(start: usize, len: usize, flags: usize) -> isize {
+    syscall(SYSCALL_MSYNC, [start, len, flags, 0, 0, 0])
+}
+pub fn munmap(start: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [start, len, 0, 0, 0, 0])
+}
+pub fn madvise(start: usize, len: usize, advice: usize) -> isize {
+    syscall(SYSCALL_MADVISE, [start, len, advice, 0, 0, 0])
+}
+pub fn getcpu(cpu: *mut u32, node: *mut u32) -> isize {
+    syscall(SYSCALL_GETCPU, [cpu as usize, node as usize, 0, 0, 0, 0])
+}
+pub fn epoll_create() -> isize {
+    syscall(SYSCALL_EPOLL_CREATE1, [0; 6])
+}
+pub fn epoll_ctl(epfd: usize, op: usize, fd: usize, event: &EpollEvent) -> isize {
+    syscall(SYSCALL_EPOLL_CTL, [epfd, op, fd, event as *const _ as usize, 0, 0])
+}
+pub fn epoll_wait(epfd: usize, events: &mut [EpollEvent], timeout: isize) -> isize {
+    syscall(
+        SYSCALL_EPOLL_PWAIT,
+        [epfd, events.as_mut_ptr() as usize, events.len(), timeout as usize, 0, 0],
+    )
+}
+pub fn poll(fds: &mut [PollFd], timeout: isize) -> isize {
+    syscall(SYSCALL_POLL, [fds.as_mut_ptr() as usize, fds.len(), timeout as usize, 0, 0, 0])
+}
@@ -0,0 +1,114 @@
+//! Raw syscalls, the numbers follow the kernel's dispatcher
+use core::arch::asm;
+
+pub const SYSCALL_CLOCK_GETTIME: usize = 113;
+pub const SYSCALL_CLONE: usize = 220;
+pub const SYSCALL_GETCPU: usize = 168;
+pub const SYSCALL_PRCTL: usize = 167;
+pub const SYSCALL_MSYNC: usize = 227;
+pub const SYSCALL_EPOLL_CREATE1: usize = 20;
+pub const SYSCALL_EPOLL_CTL: usize = 21;
+pub const SYSCALL_EPOLL_PWAIT: usize = 22;
+pub const SYSCALL_CLOSE: usize = 57;
+pub const SYSCALL_VHANGUP: usize = 58;
+pub const SYSCALL_SPLICE: usize = 76;
+pub const SYSCALL_TEE: usize = 77;
+pub const SYSCALL_RECVFROM: usize = 207;
+pub const SYSCALL_DUP3: usize = 24;
+pub const SYSCALL_FLOCK: usize = 32;
+pub const SYSCALL_EXEC: usize = 221;
+pub const SYSCALL_EXIT: usize = 93;
+pub const SYSCALL_GETPID: usize = 172;
+pub const SYSCALL_GETPPID: usize = 173;
+pub const SYSCALL_GETTID: usize = 178;
+pub const SYSCALL_LSEEK: usize = 62;
+pub const SYSCALL_MEMFD_CREATE: usize = 279;
+pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_MUNMAP: usize = 215;
+pub const SYSCALL_MADVISE: usize = 233;
+pub const SYSCALL_OPEN: usize = 56;
+pub const SYSCALL_FACCESSAT: usize = 48;
+pub const SYSCALL_CHDIR: usize = 49;
+pub const SYSCALL_FCHDIR: usize = 50;
+pub const SYSCALL_CHROOT: usize = 51;
+pub const SYSCALL_FCHMODAT: usize = 53;
+pub const SYSCALL_FCHOWNAT: usize = 54;
+pub const SYSCALL_GETRLIMIT: usize = 163;
+pub const SYSCALL_SETRLIMIT: usize = 164;
+pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_FADVISE64: usize = 223;
+pub const SYSCALL_READAHEAD: usize = 213;
+pub const SYSCALL_STATFS: usize = 43;
+pub const SYSCALL_SYNC: usize = 81;
+pub const SYSCALL_UTIMENSAT: usize = 88;
+pub const SYSCALL_FSTATFS: usize = 44;
+pub const SYSCALL_TRUNCATE: usize = 45;
+pub const SYSCALL_FTRUNCATE: usize = 46;
+pub const SYSCALL_FALLOCATE: usize = 47;
+pub const SYSCALL_MKDIRAT: usize = 34;
+pub const SYSCALL_UNLINKAT: usize = 35;
+pub const SYSCALL_LINKAT: usize = 37;
+pub const SYSCALL_RENAMEAT: usize = 38;
+pub const SYSCALL_UMOUNT2: usize = 39;
+pub const SYSCALL_MOUNT: usize = 40;
+pub const SYSCALL_PIPE: usize = 59;
+pub const SYSCALL_GETDENTS64: usize = 61;
+pub const SYSCALL_POLL: usize = 73;
+pub const SYSCALL_PREAD: usize = 67;
+pub const SYSCALL_PWRITE: usize = 68;
+pub const SYSCALL_READ: usize = 63;
+pub const SYSCALL_PROCESS_VM_READV: usize = 270;
+pub const SYSCALL_SENDFILE: usize = 71;
+pub const SYSCALL_SPAWN: usize = 400;
+pub const SYSCALL_WAITPID: usize = 260;
+/// Private number: Linux's wait4 260 is the non-blocking waitpid here
+pub const SYSCALL_WAIT4: usize = 420;
+/// Private number: mmap with flags, fd and offset
+pub const SYSCALL_MMAP_FILE: usize = 430;
+pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_YIELD: usize = 124;
+pub const SYSCALL_SCHED_SETAFFINITY: usize = 122;
+pub const SYSCALL_SCHED_GETAFFINITY: usize = 123;
+
+/// Trap into the kernel with syscall `id`, every argument register set
+pub fn syscall(id: usize, args: [usize; 6]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") args[0] => ret,
+            in("x11") args[1],
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
+            in("x17") id
+        );
+    }
+    ret
+}
+
+/// clone() whose child calls `entry(arg)` and exits with what it returns,
+/// so that a child on a new stack never returns into the frames of the caller
+pub fn syscall_clone(flags: usize, stack: usize, entry: extern "C" fn(usize) -> i32, arg: usize) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            "bnez a0, 1f",
+            // 子进程: 调用 entry(arg)，返回值作为退出码
+            "mv a0, {arg}",
+            "jalr {entry}",
+            "li a7, {exit}",
+            "ecall",
+            "1:",
+            entry = in(reg) entry,
+            arg = in(reg) arg,
+            exit = const SYSCALL_EXIT,
+            inlateout("x10") flags as isize => ret,
+            in("x11") stack,
+            in("x17") SYSCALL_CLONE
+        );
+    }
+    ret
+}